@@ -97,7 +97,7 @@ impl Animation {
     }
 
     // provides vector of drawable objects to render pipeline
-    pub fn get_drawables(&self) -> Vec<(Sprite, String)> {
+    pub fn get_drawables(&self) -> Vec<(Sprite, Option<u32>)> {
         let mut sorted_sprites: Vec<&AnimatedSprite> = self.active.iter().collect();
         sorted_sprites.sort_by_key(|sprite| sprite.strata);
 
@@ -131,7 +131,7 @@ impl Animation {
                     sprite.set_scale((scale_x, scale_y));
                 }
 
-                drawables.push((sprite, sprite_data.texture_id.clone()));
+                drawables.push((sprite, sprite_data.associated_g_entity));
             }
         }
         drawables
@@ -0,0 +1,123 @@
+use std::fs;
+use std::path::Path;
+use serde::{Serialize, Deserialize};
+use std::collections::HashMap;
+use crate::g_properties::{DamageSchool, EnemyAbility, MAX_RESISTANCE};
+use crate::spellbook::{Spellbook, SPRITES_PATH};
+
+pub static BESTIARY_PATH: &str = "./src/assets/data/enemies.json";
+
+// multiplied by the level an enemy is spawned at.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct StatsPerLevel {
+    pub health: u32,
+    pub chaos: u32,
+    pub solidity: u32,
+    pub vitality: u32,
+    pub haste: u32,
+    pub will: u32,
+    pub volatility: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct EnemyData {
+    pub id: String,
+    pub name: String,
+    pub texture: String,
+    pub stats_per_level: StatsPerLevel,
+    #[serde(default)]
+    pub resistances: HashMap<DamageSchool, u32>, // percent, not scaled by level
+    pub xp_per_level: u32,
+    pub abilities: Vec<EnemyAbility>,
+    #[serde(default)]
+    pub immortal: bool, // never dies, e.g. the training dummy
+    #[serde(default)]
+    pub drops: Vec<String>, // items the player picks up when it dies
+}
+
+// position is the top left of the sprite in unscaled coordinates, size its width and height.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SpawnData {
+    pub enemy: String,
+    pub tag: String,
+    pub level: u32,
+    pub position: (u32, u32),
+    pub size: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct EncounterData {
+    pub name: String,
+    pub spawns: Vec<SpawnData>,
+    #[serde(default)]
+    pub next_after_ms: Option<u32>, // once everything is dead, clear the corpses and spawn the next encounter
+}
+
+// enemy types and the encounters they appear in, defined in BESTIARY_PATH.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Bestiary {
+    pub enemies: Vec<EnemyData>,
+    pub encounters: Vec<EncounterData>,
+}
+
+impl Bestiary {
+    pub fn load(path: &str, spellbook: &Spellbook) -> Result<Bestiary, String> {
+        let raw = fs::read_to_string(path)
+            .map_err(|e| format!("could not read bestiary {}: {}", path, e))?;
+        let bestiary: Bestiary = serde_json::from_str(&raw)
+            .map_err(|e| format!("could not parse bestiary {}: {}", path, e))?;
+        bestiary.validate(SPRITES_PATH, spellbook)?;
+        Ok(bestiary)
+    }
+
+    pub fn validate(&self, sprites_dir: &str, spellbook: &Spellbook) -> Result<(), String> {
+        if self.encounters.is_empty() {
+            return Err("bestiary has no encounters".to_string());
+        }
+
+        for (i, enemy) in self.enemies.iter().enumerate() {
+            if self.enemies.iter().skip(i + 1).any(|other| other.id == enemy.id) {
+                return Err(format!("enemy '{}' is defined more than once", enemy.id));
+            }
+            if !Path::new(sprites_dir).join(format!("{}.png", enemy.texture)).is_file() {
+                return Err(format!("enemy '{}' uses missing texture '{}'", enemy.id, enemy.texture));
+            }
+            if enemy.stats_per_level.health == 0 {
+                return Err(format!("enemy '{}' has no health", enemy.id));
+            }
+            if let Some((school, _)) = enemy.resistances.iter().find(|&(_, &pct)| pct > MAX_RESISTANCE) {
+                return Err(format!("enemy '{}' {} resistance is over {}", enemy.id, school.name(), MAX_RESISTANCE));
+            }
+            for ability in &enemy.abilities {
+                if spellbook.get(&ability.spell).is_none() {
+                    return Err(format!("enemy '{}' uses '{}' which is not in the spellbook", enemy.id, ability.spell.tag()));
+                }
+            }
+        }
+
+        for encounter in &self.encounters {
+            if encounter.spawns.is_empty() {
+                return Err(format!("encounter '{}' spawns nothing", encounter.name));
+            }
+            for (i, spawn) in encounter.spawns.iter().enumerate() {
+                if self.get(&spawn.enemy).is_none() {
+                    return Err(format!("encounter '{}' spawns unknown enemy '{}'", encounter.name, spawn.enemy));
+                }
+                if spawn.level == 0 {
+                    return Err(format!("encounter '{}' spawns '{}' at level 0", encounter.name, spawn.tag));
+                }
+                if self.get(&spawn.enemy).is_some_and(|enemy| enemy.immortal) {
+                    return Err(format!("encounter '{}' spawns '{}' which can't die", encounter.name, spawn.tag));
+                }
+                if encounter.spawns.iter().skip(i + 1).any(|other| other.tag == spawn.tag) {
+                    return Err(format!("encounter '{}' uses tag '{}' more than once", encounter.name, spawn.tag));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn get(&self, id: &str) -> Option<&EnemyData> {
+        self.enemies.iter().find(|enemy| enemy.id == id)
+    }
+}
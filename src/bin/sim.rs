@@ -0,0 +1,610 @@
+// runs a rotation against a target without a window, many times over, and reports what it did.
+// it shares the combat code with the game, so its numbers match the training dummy's.
+//
+//   cargo run --bin sim -- --script src/assets/scripts/rotation.txt --seconds 60 --iterations 200
+//   cargo run --bin sim -- --priority umbra_mortis,infernum,miasma --enemy alpine_terror --level 3 --json
+
+// the game's own modules, shared rather than copied. parts only the game uses are allowed to go
+// unused here, anything in this file still has to be used.
+#[path = "../properties.rs"] #[allow(dead_code)] mod properties;
+#[path = "../g_properties.rs"] #[allow(dead_code)] mod g_properties;
+#[path = "../g_entities.rs"] #[allow(dead_code)] mod g_entities;
+#[path = "../spellbook.rs"] #[allow(dead_code)] mod spellbook;
+#[path = "../bestiary.rs"] #[allow(dead_code)] mod bestiary;
+#[path = "../damage_pipeline.rs"] #[allow(dead_code)] mod damage_pipeline;
+#[path = "../combat.rs"] #[allow(dead_code)] mod combat;
+#[path = "../combat_log.rs"] #[allow(dead_code)] mod combat_log;
+#[path = "../script.rs"] #[allow(dead_code)] mod script;
+#[path = "../script_vm.rs"] #[allow(dead_code)] mod script_vm;
+#[path = "../player_data.rs"] #[allow(dead_code)] mod player_data;
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::rc::Rc;
+use rand::SeedableRng;
+use rand::rngs::StdRng;
+use serde::Serialize;
+
+use bestiary::{Bestiary, EnemyData, BESTIARY_PATH};
+use combat::{CastResult, Combat, Damage, HealthChange, HitOutcome, Interrupt};
+use combat_log::TrainingStats;
+use damage_pipeline::DamagePipeline;
+use g_entities::GameEntityManager;
+use g_properties::{DamageSchool, MAX_RESISTANCE};
+use player_data::{PlayerData, PLAYER_DATA_PATH};
+use script::{Feature, FeatureGate, ScriptError, ScriptEvent, DEFAULT_SCRIPT_PATH};
+use script_vm::{Program, ScriptHost, StopAt, Value, Vm, SCRIPT_INSTRUCTION_BUDGET};
+use spellbook::{Spellbook, SPELLBOOK_PATH};
+
+pub static DEFAULT_ENEMY: &str = "training_dummy";
+pub static DEFAULT_LEVEL: u32 = 5;
+pub static DEFAULT_SECONDS: u32 = 60;
+pub static DEFAULT_ITERATIONS: u32 = 100;
+pub static DEFAULT_TICK_MS: u32 = 16; // about a frame at 60 fps
+pub static MAX_LEVEL: u32 = 1000;
+pub static MAX_SECONDS: u32 = 86_400; // a day of simulated fighting
+pub static MAX_ITERATIONS: u32 = 1_000_000;
+pub static MAX_TICK_MS: u32 = 1000;
+pub static MAX_STAT: u32 = 100_000;
+pub static TARGET_CENTER: (u32, u32) = (1608, 318); // where the training dummy stands
+
+static USAGE: &str = "usage: sim [options]
+  --script PATH        rotation script to run (default src/assets/scripts/rotation.txt)
+  --priority a,b,c     cast the first ready spell of this list instead of a script
+  --enemy ID           bestiary id of the target (default training_dummy)
+  --level N            level of the target, 1 to 1000 (default 5)
+  --stat NAME=N        override a player stat, up to 100000, e.g. --stat haste=4, can be repeated
+  --resist SCHOOL=N    give the target a resistance up to 100, e.g. --resist fire=50, can be repeated
+  --seconds N          length of each fight in simulated seconds, up to 86400 (default 60)
+  --iterations N       how many fights to average over, up to 1000000 (default 100)
+  --seed N             seed of the first fight, fight i uses seed + i (default 0)
+  --tick-ms N          simulated milliseconds per tick, 1 to 1000 (default 16)
+  --json               print the report as JSON";
+
+struct Options {
+    script: Option<String>,
+    priority: Vec<String>,
+    enemy: String,
+    level: u32,
+    stats: Vec<(String, u32)>,
+    resistances: Vec<(DamageSchool, u32)>,
+    seconds: u32,
+    iterations: u32,
+    seed: u64,
+    tick_ms: u32,
+    json: bool,
+}
+
+impl Options {
+    fn parse(args: &[String]) -> Result<Options, String> {
+        let mut options = Options {
+            script: None,
+            priority: Vec::new(),
+            enemy: DEFAULT_ENEMY.to_string(),
+            level: DEFAULT_LEVEL,
+            stats: Vec::new(),
+            resistances: Vec::new(),
+            seconds: DEFAULT_SECONDS,
+            iterations: DEFAULT_ITERATIONS,
+            seed: 0,
+            tick_ms: DEFAULT_TICK_MS,
+            json: false,
+        };
+
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            if arg == "--json" {
+                options.json = true;
+                continue;
+            }
+            let Some(value) = args.next() else {
+                return Err(format!("{} needs a value", arg));
+            };
+            match arg.as_str() {
+                "--script" => options.script = Some(value.clone()),
+                "--priority" => options.priority = value.split(',').map(|s| s.trim().to_string()).filter(|s| !s.is_empty()).collect(),
+                "--enemy" => options.enemy = value.clone(),
+                "--level" => options.level = parse_bounded(arg, value, 1, MAX_LEVEL)?,
+                "--stat" => {
+                    let (name, amount) = parse_pair(arg, value, MAX_STAT)?;
+                    options.stats.push((name.to_string(), amount));
+                }
+                "--resist" => {
+                    let (name, amount) = parse_pair(arg, value, MAX_RESISTANCE)?;
+                    let Some(school) = DamageSchool::ALL.into_iter().find(|school| school.name() == name) else {
+                        return Err(format!("unknown school '{}'", name));
+                    };
+                    options.resistances.push((school, amount));
+                }
+                "--seconds" => options.seconds = parse_bounded(arg, value, 1, MAX_SECONDS)?,
+                "--iterations" => options.iterations = parse_bounded(arg, value, 1, MAX_ITERATIONS)?,
+                "--seed" => options.seed = parse_number(arg, value)?,
+                "--tick-ms" => options.tick_ms = parse_bounded(arg, value, 1, MAX_TICK_MS)?,
+                _ => return Err(format!("unknown option {}", arg)),
+            }
+        }
+
+        if options.script.is_some() && !options.priority.is_empty() {
+            return Err("--script and --priority can't be used together".to_string());
+        }
+        Ok(options)
+    }
+}
+
+fn parse_number<T: std::str::FromStr>(arg: &str, value: &str) -> Result<T, String> {
+    value.parse().map_err(|_| format!("{} needs a number, not '{}'", arg, value))
+}
+
+// a number in min..=max, so nothing derived from it can overflow.
+fn parse_bounded(arg: &str, value: &str, min: u32, max: u32) -> Result<u32, String> {
+    let number: u32 = parse_number(arg, value)?;
+    if !(min..=max).contains(&number) {
+        return Err(format!("{} needs a number from {} to {}, not {}", arg, min, max, number));
+    }
+    Ok(number)
+}
+
+fn parse_pair<'v>(arg: &str, value: &'v str, max: u32) -> Result<(&'v str, u32), String> {
+    let Some((name, amount)) = value.split_once('=') else {
+        return Err(format!("{} needs NAME=N, not '{}'", arg, value));
+    };
+    Ok((name.trim(), parse_bounded(arg, amount.trim(), 0, max)?))
+}
+
+// a --stat override, by the name the player data file uses.
+fn set_player_stat(player: &mut PlayerData, name: &str, value: u32) -> Result<(), String> {
+    let stat = match name {
+        "chaos" => &mut player.chaos,
+        "solidity" => &mut player.solidity,
+        "vitality" => &mut player.vitality,
+        "haste" => &mut player.haste,
+        "will" => &mut player.will,
+        "volatility" => &mut player.volatility,
+        "health" => &mut player.health,
+        "level" => &mut player.level,
+        _ => return Err(format!("the player has no stat '{}'", name)),
+    };
+    *stat = value;
+    Ok(())
+}
+
+// one fight: the state the game keeps for combat, plus the script's own.
+struct Sim<'d> {
+    gem: GameEntityManager,
+    spellbook: &'d Spellbook,
+    damage_pipeline: &'d DamagePipeline,
+    rng: StdRng,
+    damage_queue: Vec<Damage>,
+    health_changes: Vec<HealthChange>,
+    interrupt_queue: Vec<Interrupt>,
+    time_ms: u32,
+    tick_ms: u32,
+    target: u32,
+    stats: TrainingStats,
+    program: Rc<Program>,
+    pending_events: Vec<(usize, Value)>,
+    last_health_pct: f64, // player's health last tick, for on_health_below
+    event: Option<Value>,
+    error: Option<ScriptError>, // the first thing the script did wrong
+}
+
+impl<'d> Sim<'d> {
+    fn new(data: &'d Data, options: &Options, program: Rc<Program>, seed: u64) -> Self {
+        let mut sim = Sim {
+            gem: GameEntityManager::new(),
+            spellbook: &data.spellbook,
+            damage_pipeline: &data.damage_pipeline,
+            rng: StdRng::seed_from_u64(seed),
+            damage_queue: Vec::new(),
+            health_changes: Vec::new(),
+            interrupt_queue: Vec::new(),
+            time_ms: 0,
+            tick_ms: options.tick_ms,
+            target: 0,
+            stats: TrainingStats::default(),
+            program,
+            pending_events: Vec::new(),
+            last_health_pct: 1.0,
+            event: None,
+            error: None,
+        };
+
+        let player_id = sim.combat().spawn_player(&data.player);
+        let target = sim.combat().spawn_enemy(&options.enemy, &data.enemy, options.level, TARGET_CENTER);
+        if let Some(resistances) = sim.gem.resistances.get_mut(&target) {
+            resistances.values.extend(options.resistances.iter().copied());
+        }
+        if let Some(t) = sim.gem.targets.get_mut(&target) {
+            t.target_entity = Some(player_id);
+        }
+        sim.combat().set_player_target(Some(target));
+        sim.target = target;
+        sim
+    }
+
+    fn combat(&mut self) -> Combat<'_> {
+        Combat {
+            gem: &mut self.gem,
+            spellbook: self.spellbook,
+            damage_pipeline: self.damage_pipeline,
+            rng: &mut self.rng,
+            damage_queue: &mut self.damage_queue,
+            health_changes: &mut self.health_changes,
+            interrupts: &mut self.interrupt_queue,
+            delta_time_ms: self.tick_ms,
+        }
+    }
+
+    fn alive(&self, unit: u32) -> bool {
+        self.gem.stats.get(&unit).is_some_and(|stats| stats.health_curr > 0)
+    }
+
+    fn player_died(&self) -> bool {
+        self.gem.player_id.is_some_and(|pid| !self.alive(pid))
+    }
+
+    fn player_health_pct(&self) -> f64 {
+        self.gem.player_id
+            .and_then(|pid| self.gem.stats.get(&pid))
+            .map(|stats| stats.health_curr as f64 / stats.health_max.max(1) as f64)
+            .unwrap_or(1.0)
+    }
+
+    // the systems in the order Game::run calls them, until the time is up or either side dies.
+    fn run(&mut self, duration_ms: u32) {
+        let (tick_ms, target) = (self.tick_ms, self.target);
+        while self.time_ms < duration_ms {
+            self.combat().refill_immortals();
+            if !self.alive(target) || self.player_died() {
+                break;
+            }
+            self.time_ms += tick_ms;
+
+            self.combat().run_enemy_ai();
+            self.run_script(0, None);
+            let expired = self.combat().tick_debuffs();
+            for (target, name) in expired {
+                self.queue_event(ScriptEvent::DebuffExpired, Some(&name), Some(target));
+            }
+            self.combat().run_damage_queue();
+            for (change, _) in self.combat().apply_health_changes() {
+                self.track_damage(&change);
+            }
+            self.combat().tick_resources();
+            self.combat().tick_cooldowns();
+            self.combat().apply_interrupts();
+
+            let finished = self.combat().advance_casts(tick_ms);
+            for cast in finished {
+                if Some(cast.caster) != self.gem.player_id {
+                    continue;
+                }
+                if let (CastResult::Success, Some(sp)) = (&cast.result, cast.spell) {
+                    self.queue_event(ScriptEvent::CastFinished, Some(sp.tag()), cast.target);
+                }
+            }
+            let health_pct = self.player_health_pct();
+            let last = std::mem::replace(&mut self.last_health_pct, health_pct);
+            let handlers = combat::script_health_handlers(&self.program, last, health_pct, self.gem.player_id);
+            self.pending_events.extend(handlers);
+            for (start, event) in std::mem::take(&mut self.pending_events) {
+                self.run_script(start, Some(event));
+            }
+
+            let debuffs = self.combat().player_debuffs_on(target);
+            self.stats.track_uptime(self.time_ms, tick_ms, debuffs);
+        }
+    }
+
+    // the main script from the top, or one of its handlers.
+    fn run_script(&mut self, start: usize, event: Option<Value>) {
+        let program = self.program.clone();
+        self.event = event;
+        let mut vm = Vm::at(&program, start);
+        if let Err(e) = vm.run(&program, self, SCRIPT_INSTRUCTION_BUDGET, StopAt::Breakpoints(&[])) {
+            self.error.get_or_insert(e);
+        }
+        self.event = None;
+    }
+
+    // like Game::script_event.
+    fn queue_event(&mut self, event: ScriptEvent, spell: Option<&str>, unit: Option<u32>) {
+        let handlers = combat::script_event_handlers(&self.program, self.spellbook, event, spell, unit);
+        self.pending_events.extend(handlers);
+    }
+
+    // like Game::track_training_damage.
+    fn track_damage(&mut self, change: &HealthChange) {
+        if change.target != self.target || self.gem.player_id != Some(change.source) || change.amount >= 0 {
+            return;
+        }
+        let spell = change.spell.as_ref()
+            .and_then(|sp| self.spellbook.get(sp))
+            .map(|spell| spell.name.clone())
+            .unwrap_or_else(|| "attack".to_string());
+        let crit = change.outcome == HitOutcome::Critical;
+        self.stats.record(self.time_ms, &spell, change.amount.unsigned_abs(), crit);
+    }
+}
+
+impl ScriptHost for Sim<'_> {
+    fn global(&mut self, name: &str) -> Result<Value, String> {
+        if name == "event" {
+            return Ok(self.event.clone().unwrap_or(Value::Nil));
+        }
+        self.combat().script_global(name)
+    }
+
+    fn field(&mut self, unit: u32, name: &str) -> Result<Value, String> {
+        self.combat().script_field(unit, name)
+    }
+
+    fn method(&mut self, unit: u32, name: &str, args: &[Value]) -> Result<Value, String> {
+        self.combat().script_method(unit, name, args)
+    }
+
+    fn call(&mut self, name: &str, args: &[Value]) -> Result<Value, String> {
+        match name {
+            "cast" => {
+                let sp = self.combat().script_spell(args, name)?;
+                Ok(Value::Bool(self.combat().try_queue_spell_cast(sp).is_ok()))
+            }
+            "print" => Ok(Value::Nil),
+            _ => self.combat().script_call(name, args),
+        }
+    }
+
+    fn unit_name(&self, unit: u32) -> String {
+        combat::script_unit_name(&self.gem, unit)
+    }
+}
+
+// everything loaded from the game's data files, shared by every fight.
+struct Data {
+    spellbook: Spellbook,
+    enemy: EnemyData,
+    player: PlayerData,
+    damage_pipeline: DamagePipeline,
+}
+
+impl Data {
+    fn load(options: &Options) -> Result<Data, String> {
+        let spellbook = Spellbook::load(SPELLBOOK_PATH)?;
+        let bestiary = Bestiary::load(BESTIARY_PATH, &spellbook)?;
+        let Some(enemy) = bestiary.get(&options.enemy).cloned() else {
+            return Err(format!("there is no enemy '{}' in {}", options.enemy, BESTIARY_PATH));
+        };
+        let mut player = PlayerData::load(PLAYER_DATA_PATH)?;
+        for (name, value) in &options.stats {
+            set_player_stat(&mut player, name, *value)?;
+        }
+        // the fight starts at full health
+        player.starting_health = player.health;
+        player.validate()?;
+        Ok(Data {
+            spellbook,
+            enemy,
+            player,
+            damage_pipeline: DamagePipeline::with_builtins(),
+        })
+    }
+
+    fn spell_names(&self) -> Vec<String> {
+        self.spellbook.spells.iter()
+            .flat_map(|spell| [spell.id.tag().to_string(), spell.name.clone()])
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+struct Summary {
+    mean: f64,
+    std_dev: f64,
+    min: f64,
+    max: f64,
+}
+
+impl Summary {
+    fn of(values: &[f64]) -> Summary {
+        if values.is_empty() {
+            return Summary::default();
+        }
+        let n = values.len() as f64;
+        let mean = values.iter().sum::<f64>() / n;
+        let variance = if values.len() > 1 {
+            values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / (n - 1.0)
+        } else {
+            0.0
+        };
+        Summary {
+            mean,
+            std_dev: variance.sqrt(),
+            min: values.iter().copied().fold(f64::INFINITY, f64::min),
+            max: values.iter().copied().fold(f64::NEG_INFINITY, f64::max),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct SpellReport {
+    spell: String,
+    damage: f64, // mean per fight
+    dps: f64,
+    share_pct: f64,
+    hits: f64,
+    crit_pct: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct Report {
+    rotation: String,
+    enemy: String,
+    level: u32,
+    seconds: u32,
+    iterations: u32,
+    seed: u64,
+    tick_ms: u32,
+    kills: u32, // fights that ended early because the target died
+    deaths: u32, // fights that ended early because the player died
+    duration_secs: Summary,
+    total_damage: Summary,
+    dps: Summary,
+    spells: Vec<SpellReport>,
+    uptime_pct: BTreeMap<String, f64>, // mean share of each fight the debuff was up
+}
+
+impl Report {
+    fn new(options: &Options, rotation: String, enemy: &EnemyData) -> Report {
+        Report {
+            rotation,
+            enemy: enemy.name.clone(),
+            level: options.level,
+            seconds: options.seconds,
+            iterations: options.iterations,
+            seed: options.seed,
+            tick_ms: options.tick_ms,
+            kills: 0,
+            deaths: 0,
+            duration_secs: Summary::default(),
+            total_damage: Summary::default(),
+            dps: Summary::default(),
+            spells: Vec::new(),
+            uptime_pct: BTreeMap::new(),
+        }
+    }
+
+    // each fight is how long it lasted, what the player dealt and whether the player died.
+    fn add_fights(&mut self, fights: &[(u32, TrainingStats, bool)]) {
+        let n = fights.len().max(1) as f64;
+        let durations: Vec<f64> = fights.iter().map(|(ms, ..)| *ms as f64 / 1000.0).collect();
+        let damage: Vec<f64> = fights.iter().map(|(_, stats, _)| stats.damage as f64).collect();
+        let dps: Vec<f64> = fights.iter()
+            .map(|(ms, stats, _)| stats.damage as f64 * 1000.0 / (*ms).max(1) as f64)
+            .collect();
+        self.deaths = fights.iter().filter(|(.., died)| *died).count() as u32;
+        self.kills = fights.iter().filter(|(ms, _, died)| !died && *ms < self.seconds.saturating_mul(1000)).count() as u32;
+        self.duration_secs = Summary::of(&durations);
+        self.total_damage = Summary::of(&damage);
+        self.dps = Summary::of(&dps);
+
+        let mut spells: BTreeMap<String, (f64, f64, u32, u32)> = BTreeMap::new(); // damage, dps, hits, crits
+        for (ms, stats, _) in fights {
+            for (name, spell) in &stats.spells {
+                let entry = spells.entry(name.clone()).or_default();
+                entry.0 += spell.damage as f64;
+                entry.1 += spell.damage as f64 * 1000.0 / (*ms).max(1) as f64;
+                entry.2 += spell.hits;
+                entry.3 += spell.crits;
+            }
+            for (name, up_ms) in &stats.uptime_ms {
+                *self.uptime_pct.entry(name.clone()).or_default() += (*up_ms as f64 * 100.0 / (*ms).max(1) as f64).min(100.0) / n;
+            }
+        }
+
+        let total: f64 = spells.values().map(|(damage, ..)| damage).sum();
+        self.spells = spells.into_iter()
+            .map(|(spell, (damage, dps, hits, crits))| SpellReport {
+                spell,
+                damage: damage / n,
+                dps: dps / n,
+                share_pct: if total > 0.0 { damage * 100.0 / total } else { 0.0 },
+                hits: hits as f64 / n,
+                crit_pct: if hits > 0 { crits as f64 * 100.0 / hits as f64 } else { 0.0 },
+            })
+            .collect();
+        self.spells.sort_by(|a, b| b.damage.total_cmp(&a.damage));
+    }
+
+    fn print_text(&self) {
+        println!("{} vs {} (level {}), {} fights of {}s from seed {}, {}ms ticks",
+            self.rotation, self.enemy, self.level, self.iterations, self.seconds, self.seed, self.tick_ms);
+        if self.kills > 0 {
+            println!("target died in {} of {} fights, after {:.1}s on average", self.kills, self.iterations, self.duration_secs.mean);
+        }
+        if self.deaths > 0 {
+            println!("the player died in {} of {} fights", self.deaths, self.iterations);
+        }
+        println!();
+        println!("{:<10} {:>10} {:>10} {:>10} {:>10}", "", "mean", "std dev", "min", "max");
+        for (name, summary) in [("dps", &self.dps), ("damage", &self.total_damage)] {
+            println!("{:<10} {:>10.1} {:>10.1} {:>10.1} {:>10.1}", name, summary.mean, summary.std_dev, summary.min, summary.max);
+        }
+
+        println!();
+        println!("{:<16} {:>10} {:>8} {:>8} {:>8} {:>8}", "spell", "damage", "dps", "share", "hits", "crit");
+        for spell in &self.spells {
+            println!("{:<16} {:>10.1} {:>8.1} {:>7.1}% {:>8.1} {:>7.1}%",
+                spell.spell, spell.damage, spell.dps, spell.share_pct, spell.hits, spell.crit_pct);
+        }
+
+        if !self.uptime_pct.is_empty() {
+            println!();
+            println!("{:<16} {:>8}", "debuff", "uptime");
+            for (name, pct) in &self.uptime_pct {
+                println!("{:<16} {:>7.1}%", name, pct);
+            }
+        }
+    }
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.iter().any(|arg| arg == "--help" || arg == "-h") {
+        println!("{}", USAGE);
+        return;
+    }
+    let options = Options::parse(&args).unwrap_or_else(|e| {
+        eprintln!("{}\n\n{}", e, USAGE);
+        std::process::exit(2);
+    });
+    let data = Data::load(&options).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    });
+
+    // a priority list is the starting rotation script written for you
+    let (rotation, source) = if options.priority.is_empty() {
+        let path = options.script.clone().unwrap_or_else(|| DEFAULT_SCRIPT_PATH.to_string());
+        let source = fs::read_to_string(&path).unwrap_or_else(|e| {
+            eprintln!("could not read script {}: {}", path, e);
+            std::process::exit(1);
+        });
+        (path, source)
+    } else {
+        let source = options.priority.iter().map(|spell| format!("cast(\"{}\")\n", spell)).collect();
+        (format!("priority {}", options.priority.join(", ")), source)
+    };
+
+    // the simulator doesn't care what the character has unlocked
+    let gate = FeatureGate { unlocked: Feature::ALL.to_vec(), hints: Vec::new() };
+    let program = match script::compile(&source, &data.spell_names(), &gate) {
+        Ok(program) => Rc::new(program),
+        Err(e) => {
+            eprintln!("{}: {}", rotation, e);
+            std::process::exit(1);
+        }
+    };
+
+    let mut fights = Vec::new();
+    let mut first_error: Option<ScriptError> = None;
+    for i in 0..options.iterations {
+        let mut sim = Sim::new(&data, &options, program.clone(), options.seed.wrapping_add(i as u64));
+        sim.run(options.seconds.saturating_mul(1000));
+        if first_error.is_none() {
+            first_error = sim.error.take();
+        }
+        let died = sim.player_died();
+        fights.push((sim.time_ms, sim.stats, died));
+    }
+    if let Some(e) = first_error {
+        eprintln!("script error: {}", e);
+    }
+
+    let mut report = Report::new(&options, rotation, &data.enemy);
+    report.add_fights(&fights);
+    if options.json {
+        println!("{}", serde_json::to_string_pretty(&report).expect("Failed to serialize report"));
+    } else {
+        report.print_text();
+    }
+}
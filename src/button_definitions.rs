@@ -1,4 +1,15 @@
-use crate::{g_properties::GPAction, game::*};
+use sfml::graphics::Color;
+use crate::{g_properties::Spells, game::*, properties::ClickAction};
+
+// which spell each action button casts.
+pub fn spell_for_action(action: &ClickAction) -> Option<Spells> {
+    match action {
+        ClickAction::A => Some(Spells::Miasma),
+        ClickAction::B => Some(Spells::Infernum),
+        ClickAction::C => Some(Spells::UmbraMortis),
+        _ => None,
+    }
+}
 
 impl Game<'_> {
     pub fn branch_from_click(&mut self, action: crate::properties::ClickAction) {
@@ -17,18 +28,76 @@ impl Game<'_> {
         else if action == crate::properties::ClickAction::D {
             self.handle_d_button();
         }
+        else if action == crate::properties::ClickAction::E {
+            self.handle_e_button();
+        }
+        else if action == crate::properties::ClickAction::F {
+            self.handle_f_button();
+        }
         else if action == crate::properties::ClickAction::G {
             self.handle_g_button();
         }
         else if action == crate::properties::ClickAction::H {
             self.handle_h_button();
         }
+        else if action == crate::properties::ClickAction::Respawn {
+            self.respawn_player(false);
+        }
+        else if action == crate::properties::ClickAction::Retry {
+            self.respawn_player(true);
+        }
+        else if action == crate::properties::ClickAction::DebugContinue {
+            self.resume_script();
+        }
+        else if action == crate::properties::ClickAction::DebugStep {
+            self.step_script();
+        }
+        else if action == crate::properties::ClickAction::LibraryLoad {
+            self.library_load();
+        }
+        else if action == crate::properties::ClickAction::LibraryDuplicate {
+            self.library_duplicate();
+        }
+        else if action == crate::properties::ClickAction::LibraryRename {
+            self.library_rename();
+        }
+        else if action == crate::properties::ClickAction::LibraryDelete {
+            self.library_delete();
+        }
+        else if action == crate::properties::ClickAction::LibraryExport {
+            self.library_export();
+        }
+        else if action == crate::properties::ClickAction::LibraryImport {
+            self.library_import();
+        }
+        else if action == crate::properties::ClickAction::ChallengeStart {
+            self.challenge_start_selected();
+        }
+        else if action == crate::properties::ClickAction::ChallengeRetry {
+            self.hide_challenge_result();
+            self.start_challenge(self.challenges.selected);
+        }
+        else if action == crate::properties::ClickAction::ChallengeDismiss {
+            self.hide_challenge_result();
+        }
+        else if action == crate::properties::ClickAction::TrainingDummy {
+            self.toggle_training_dummy();
+        }
+        else if action == crate::properties::ClickAction::TrainingReset {
+            self.reset_training_stats();
+        }
+        else if action == crate::properties::ClickAction::TrainingLevelDown {
+            self.change_dummy_level(-1);
+        }
+        else if action == crate::properties::ClickAction::TrainingLevelUp {
+            self.change_dummy_level(1);
+        }
     }
 
     fn handle_run_button(&mut self) {
-        println!("Hello from handle_run_button!");
         let run_button = self.em.get_prects_mut_by_tag("run_button");
         run_button.unwrap()[0].pressed = Some(true);
+        self.toggle_script();
     }
 
     fn handle_a_button(&mut self) {
@@ -37,161 +106,123 @@ impl Game<'_> {
         let a_button = self.em.get_prects_mut_by_tag("a_button");
         a_button.unwrap()[0].pressed = Some(true);
 
-        let player_id = self.gem.player_id.unwrap();
-        let player_target = self.gem.targets.get_mut(&player_id);
-
-        // tries to find a game entity that's not the player to set as the target.
-        let other_entity = self.gem.gids
-            .iter()
-            .find(|pair| *(pair.0) != player_id)
-            .map(|(_, entity)| entity);
-
-        if let (Some(entity), Some(target)) = (other_entity, player_target) {
-            println!("Found a non-player entity: {:?}", entity);
-            target.target_entity = Some(entity.id);
-        } else {
-            println!("No non-player entity found or player_target not found.");
-            return
+        if let Some(sp) = spell_for_action(&ClickAction::A) {
+            self.queue_spell_cast(sp);
         }
-
-        let next_id = self.gem.next_pid();
-        let a = GPAction {
-            id: next_id,
-            action: crate::g_properties::Actions::CastingSpell,
-            action_tag: "miasma".to_string(),
-            time_action_takes: 2000,
-            time_remaining: 2000,
-            spell: Some(crate::g_properties::Spells::Miasma),
-        };
-
-        self.gem.actions.insert(player_id, a.clone());
-        println!("Miasma added to action queue");
-        self.gem.actionqueue.get_mut(&player_id).unwrap().queue.push(a);
-
-        let scale_w = WINDOW_WIDTH as f32 / 1920.0;
-        let scale_h = WINDOW_HEIGHT as f32 / 1080.0;
-        let scale = scale_w.min(scale_h).floor().max(1.0) as u32;
-        let s = |x: u32| x * scale;
-
-        self.anims.remove_sprite_by_texture("my_warlock");
-        self.anims.remove_sprite_by_texture("Miasma_anim2");
-        let action_time = self.gem.actionqueue
-            .get(&self.gem.player_id.unwrap())
-            .unwrap()
-            .queue
-            .first()
-            .unwrap()
-            .time_action_takes;
-        let action_time_sec = action_time as f32 / 1000.0;
-
-        let frame_time = action_time_sec / 12.0;
-
-        self.anims.add_animation_instance(crate::animation::AnimatedSprite {
-            texture_id: "Miasma_anim2".to_string(),
-            frame_width: 64,
-            frame_height: 64,
-            total_frames: 12,
-            current_frame: 0,
-            frame_time: Some(frame_time),
-            time_accumulator: 0.0,
-            position: (s(1000) , s(207)),
-            inanimate: false,
-            strata: 20,
-            desired_width: Some(s(256)),
-            desired_height: Some(s(256)),
-            play_once: true,
-            finished: false,
-            velocity: (0.0, 0.0),
-            lifetime: None,
-            associated_g_entity: None,
-        });
     }
 
     fn handle_b_button(&mut self) {
-        let player_id = self.gem.player_id.unwrap();
-        let player_target = self.gem.targets.get_mut(&player_id);
-
         let b_button = self.em.get_prects_mut_by_tag("b_button");
         b_button.unwrap()[0].pressed = Some(true);
-        
-        let other_entity = self.gem.gids
-            .iter()
-            .find(|pair| *(pair.0) != player_id)
-            .map(|(_, entity)| entity);
 
-        if let (Some(entity), Some(target)) = (other_entity, player_target) {
-            println!("Found a non-player entity: {:?}", entity);
-            target.target_entity = Some(entity.id);
-        } else {
-            println!("No non-player entity found or player_target not found.");
-            return
+        if let Some(sp) = spell_for_action(&ClickAction::B) {
+            self.queue_spell_cast(sp);
         }
+    }
 
-        let next_id = self.gem.next_pid();
-        let a = GPAction {
-            id: next_id,
-            action: crate::g_properties::Actions::CastingSpell,
-            action_tag: "infernum".to_string(),
-            time_action_takes: 2000,
-            time_remaining: 2000,
-            spell: Some(crate::g_properties::Spells::Infernum),
-        };
-
-        self.gem.actions.insert(player_id, a.clone());
-        println!("Infernum added to action queue");
-        self.gem.actionqueue.get_mut(&player_id).unwrap().queue.push(a);
-
-        let scale_w = WINDOW_WIDTH as f32 / 1920.0;
-        let scale_h = WINDOW_HEIGHT as f32 / 1080.0;
-        let scale = scale_w.min(scale_h).floor().max(1.0) as u32;
-        let s = |x: u32| x * scale;
+    fn queue_spell_cast(&mut self, sp: Spells) {
+        // a challenge is the script's to win
+        if self.challenge.is_some() {
+            self.floating_message("Only your script casts during a challenge", MAIN_TEXT_CLR);
+            return;
+        }
+        if let Err((msg, color)) = self.try_queue_spell_cast(sp) {
+            println!("{}", msg);
+            self.floating_message(&msg, color);
+        }
+    }
 
-        self.anims.remove_sprite_by_texture("my_warlock");
-        self.anims.remove_sprite_by_texture("Miasma_anim2");
-        let action_time = self.gem.actionqueue
-            .get(&self.gem.player_id.unwrap())
-            .unwrap()
-            .queue
-            .first()
-            .unwrap()
-            .time_action_takes;
-        let action_time_sec = action_time as f32 / 1000.0;
+    // queues sp for the player, or says why it can't be cast right now without showing it;
+    // scripts try this every tick.
+    pub fn try_queue_spell_cast(&mut self, sp: Spells) -> Result<(), (String, Color)> {
+        let started = self.combat().try_queue_spell_cast(sp.clone()).map_err(|rejected| {
+            let color = if rejected.missing_resource { MANA_COLOR } else { MAIN_TEXT_CLR };
+            (rejected.message, color)
+        })?;
 
-        let frame_time = action_time_sec / 12.0;
+        if let Some(spell) = self.spellbook.get(&sp) {
+            println!("{} added to action queue", spell.name);
+        }
+        // a queued action only starts animating once it reaches the front of the queue.
+        if started {
+            self.start_cast_animation(&sp);
+        }
+        Ok(())
+    }
 
-        self.anims.add_animation_instance(crate::animation::AnimatedSprite {
-            texture_id: "Miasma_anim2".to_string(),
-            frame_width: 64,
-            frame_height: 64,
-            total_frames: 12,
-            current_frame: 0,
-            frame_time: Some(frame_time),
-            time_accumulator: 0.0,
-            position: (s(1000) , s(207)),
-            inanimate: false,
-            strata: 20,
-            desired_width: Some(s(256)),
-            desired_height: Some(s(256)),
-            play_once: true,
-            finished: false,
-            velocity: (0.0, 0.0),
-            lifetime: None,
-            associated_g_entity: None,
-        });
+    pub fn cancel_player_cast(&mut self) {
+        if let Some(player_id) = self.gem.player_id {
+            self.interrupt_queue.push(crate::combat::Interrupt {
+                target: player_id,
+                source: Some(player_id),
+                reason: crate::combat::InterruptReason::Cancelled,
+            });
+        }
     }
 
     fn handle_c_button(&mut self) {
         println!("C button pressed");
         let c_button = self.em.get_prects_mut_by_tag("c_button");
         c_button.unwrap()[0].pressed = Some(true);
-        let e_id = self.gem.get_entity_id_from_name("alpine_terror".to_string());
-        if let Some(stats) = self.gem.stats.get_mut(&e_id) {
-            stats.health_curr = stats.health_curr.saturating_sub(5);
+
+        if let Some(sp) = spell_for_action(&ClickAction::C) {
+            self.queue_spell_cast(sp);
         }
     }
 
     fn handle_d_button(&mut self) {
         println!("D button pressed");
+        if let Some(d_button) = self.em.get_prects_mut_by_tag("d_button") {
+            d_button[0].pressed = Some(true);
+        }
+
+        // the library, the challenges, the training panel and the stats menu take the same spot
+        if self.state[2] == 1 {
+            self.handle_g_button();
+        }
+        if self.challenge_panel_open() {
+            self.close_challenge_panel();
+        }
+        if self.training_panel_open() {
+            self.close_training_panel();
+        }
+        self.toggle_script_library();
+    }
+
+    fn handle_e_button(&mut self) {
+        println!("E button pressed");
+        if let Some(e_button) = self.em.get_prects_mut_by_tag("e_button") {
+            e_button[0].pressed = Some(true);
+        }
+
+        if self.state[2] == 1 {
+            self.handle_g_button();
+        }
+        if self.script_library_open() {
+            self.close_script_library();
+        }
+        if self.training_panel_open() {
+            self.close_training_panel();
+        }
+        self.toggle_challenge_panel();
+    }
+
+    fn handle_f_button(&mut self) {
+        println!("F button pressed");
+        if let Some(f_button) = self.em.get_prects_mut_by_tag("f_button") {
+            f_button[0].pressed = Some(true);
+        }
+
+        if self.state[2] == 1 {
+            self.handle_g_button();
+        }
+        if self.script_library_open() {
+            self.close_script_library();
+        }
+        if self.challenge_panel_open() {
+            self.close_challenge_panel();
+        }
+        self.toggle_training_panel();
     }
 
     fn handle_g_button(&mut self) {
@@ -214,6 +245,15 @@ impl Game<'_> {
             return;
         } else {
             self.state[2] = 1;
+            if self.script_library_open() {
+                self.close_script_library();
+            }
+            if self.challenge_panel_open() {
+                self.close_challenge_panel();
+            }
+            if self.training_panel_open() {
+                self.close_training_panel();
+            }
         }
 
         let scale_w = WINDOW_WIDTH as f32 / 1920.0;
@@ -0,0 +1,610 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use rand::SeedableRng;
+use rand::rngs::StdRng;
+use serde::{Serialize, Deserialize};
+use sfml::graphics::Color;
+use sfml::window::Key;
+use crate::bestiary::{Bestiary, SpawnData};
+use crate::combat_log::CombatStats;
+use crate::game::*;
+use crate::g_properties::GPCooldown;
+use crate::properties::*;
+use crate::render_pipeline::OVERLAY_STRATA;
+use crate::script::{self, ScriptError, TokenKind};
+
+pub static CHALLENGES_PATH: &str = "./src/assets/data/challenges.json";
+pub static LEADERBOARD_PATH: &str = "./saves/leaderboard.json";
+pub static CHALLENGE_PANEL_TAG: &str = "challenge_panel";
+pub static CHALLENGE_RESULT_TAG: &str = "challenge_result";
+pub static LEADERBOARD_SIZE: usize = 10;
+
+// how a passed challenge is scored: time left, damage dealt and how short the script is.
+pub static TIME_POINTS: u32 = 1000;      // for finishing instantly, none at the time limit
+pub static DAMAGE_PER_POINT: u32 = 10;
+pub static SIZE_POINTS: u32 = 500;       // for an empty script, none at the token par
+pub static DEFAULT_TOKEN_PAR: usize = 200; // for challenges without max_tokens
+
+static CHALLENGE_PANEL_BUTTONS: [&str; 1] = ["challenge_start_button"];
+static CHALLENGE_RESULT_BUTTONS: [&str; 2] = ["challenge_retry_button", "challenge_dismiss_button"];
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Objective {
+    Kill { tag: String, #[serde(default)] within_ms: Option<u32> }, // the enemy spawned with that tag
+    KillAll { #[serde(default)] within_ms: Option<u32> },
+    Survive,
+    DealDamage { amount: u32 },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ObjectiveStatus {
+    Pending,
+    Done,
+    Failed,
+}
+
+// what a script has to stay within to enter, checked before the challenge starts.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct ChallengeConstraints {
+    #[serde(default)]
+    pub max_tokens: Option<usize>, // comments don't count
+    #[serde(default)]
+    pub allowed_builtins: Option<Vec<String>>, // None allows everything the character has unlocked
+}
+
+impl ChallengeConstraints {
+    // the size of the script in tokens if it is allowed in.
+    pub fn check(&self, source: &str) -> Result<usize, ScriptError> {
+        let tokens: Vec<_> = script::tokenize(source)?
+            .into_iter()
+            .filter(|t| !matches!(t.kind, TokenKind::Comment | TokenKind::Eof))
+            .collect();
+        if let Some(allowed) = &self.allowed_builtins {
+            for token in &tokens {
+                if let TokenKind::Ident(name) = &token.kind && script::builtin(name).is_some() && !allowed.contains(name) {
+                    return Err(ScriptError::new(token.span, format!("'{}' isn't allowed in this challenge", name)));
+                }
+            }
+        }
+        if let Some(max) = self.max_tokens.filter(|max| tokens.len() > *max) {
+            let span = tokens.get(max).map(|t| t.span).unwrap_or_default();
+            return Err(ScriptError::new(span, format!("the script is {} tokens long, this challenge allows {}", tokens.len(), max)));
+        }
+        Ok(tokens.len())
+    }
+
+    pub fn describe(&self) -> Vec<String> {
+        let mut lines = Vec::new();
+        if let Some(max) = self.max_tokens {
+            lines.push(format!("At most {} tokens", max));
+        }
+        if let Some(allowed) = &self.allowed_builtins {
+            lines.push(format!("Only {}", allowed.join(", ")));
+        }
+        lines
+    }
+}
+
+// a fixed fight for a script to win, defined in CHALLENGES_PATH.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChallengeData {
+    pub id: String,
+    pub name: String,
+    pub description: String,
+    pub seed: u64,
+    pub time_limit_ms: u32,
+    pub spawns: Vec<SpawnData>,
+    #[serde(default)]
+    pub constraints: ChallengeConstraints,
+    pub objectives: Vec<Objective>,
+    #[serde(default)]
+    pub teaches: Option<String>, // a talent learned by passing it
+}
+
+impl ChallengeData {
+    fn enemy_name(&self, tag: &str, bestiary: &Bestiary) -> String {
+        self.spawns.iter()
+            .find(|spawn| spawn.tag == tag)
+            .and_then(|spawn| bestiary.get(&spawn.enemy))
+            .map(|enemy| enemy.name.clone())
+            .unwrap_or_else(|| tag.to_string())
+    }
+
+    pub fn describe(&self, objective: &Objective, bestiary: &Bestiary) -> String {
+        let within = |ms: &Option<u32>| ms.map(|ms| format!(" within {} s", ms as f32 / 1000.0)).unwrap_or_default();
+        match objective {
+            Objective::Kill { tag, within_ms } => format!("Kill the {}{}", self.enemy_name(tag, bestiary), within(within_ms)),
+            Objective::KillAll { within_ms } => format!("Kill every enemy{}", within(within_ms)),
+            Objective::Survive => "Don't die".to_string(),
+            Objective::DealDamage { amount } => format!("Deal {} damage", amount),
+        }
+    }
+
+    pub fn score(&self, elapsed_ms: u32, damage: u32, code_size: usize) -> ChallengeScore {
+        let time_left = self.time_limit_ms.saturating_sub(elapsed_ms);
+        let par = self.constraints.max_tokens.unwrap_or(DEFAULT_TOKEN_PAR).max(1);
+        let time = (TIME_POINTS as u64 * time_left as u64 / self.time_limit_ms.max(1) as u64) as u32;
+        let damage_points = damage / DAMAGE_PER_POINT;
+        let size = (SIZE_POINTS as usize * par.saturating_sub(code_size) / par) as u32;
+        ChallengeScore { total: time + damage_points + size, time, damage: damage_points, size }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChallengeScore {
+    pub total: u32,
+    pub time: u32,
+    pub damage: u32,
+    pub size: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChallengeBook {
+    pub challenges: Vec<ChallengeData>,
+    #[serde(skip)]
+    pub selected: usize, // in the challenge panel
+}
+
+impl ChallengeBook {
+    pub fn load(path: &str, bestiary: &Bestiary) -> Result<ChallengeBook, String> {
+        let raw = fs::read_to_string(path)
+            .map_err(|e| format!("could not read challenges {}: {}", path, e))?;
+        let book: ChallengeBook = serde_json::from_str(&raw)
+            .map_err(|e| format!("could not parse challenges {}: {}", path, e))?;
+        book.validate(bestiary)?;
+        Ok(book)
+    }
+
+    pub fn validate(&self, bestiary: &Bestiary) -> Result<(), String> {
+        for (i, challenge) in self.challenges.iter().enumerate() {
+            let name = &challenge.id;
+            if self.challenges.iter().skip(i + 1).any(|other| other.id == challenge.id) {
+                return Err(format!("challenge '{}' is defined more than once", name));
+            }
+            if challenge.time_limit_ms == 0 {
+                return Err(format!("challenge '{}' has no time limit", name));
+            }
+            if challenge.spawns.is_empty() || challenge.objectives.is_empty() {
+                return Err(format!("challenge '{}' needs enemies and objectives", name));
+            }
+            for (j, spawn) in challenge.spawns.iter().enumerate() {
+                if bestiary.get(&spawn.enemy).is_none() {
+                    return Err(format!("challenge '{}' spawns unknown enemy '{}'", name, spawn.enemy));
+                }
+                if spawn.level == 0 {
+                    return Err(format!("challenge '{}' spawns '{}' at level 0", name, spawn.tag));
+                }
+                if bestiary.get(&spawn.enemy).is_some_and(|enemy| enemy.immortal) {
+                    return Err(format!("challenge '{}' spawns '{}' which can't die", name, spawn.tag));
+                }
+                if challenge.spawns.iter().skip(j + 1).any(|other| other.tag == spawn.tag) {
+                    return Err(format!("challenge '{}' uses tag '{}' more than once", name, spawn.tag));
+                }
+            }
+            for objective in &challenge.objectives {
+                let within_ms = match objective {
+                    Objective::Kill { tag, within_ms } => {
+                        if !challenge.spawns.iter().any(|spawn| spawn.tag == *tag) {
+                            return Err(format!("challenge '{}' wants '{}' killed but doesn't spawn it", name, tag));
+                        }
+                        *within_ms
+                    }
+                    Objective::KillAll { within_ms } => *within_ms,
+                    _ => None,
+                };
+                if within_ms.is_some_and(|ms| ms > challenge.time_limit_ms) {
+                    return Err(format!("challenge '{}' has an objective that outlasts its time limit", name));
+                }
+            }
+            if challenge.teaches.as_ref().is_some_and(|talent| talent.trim().is_empty()) {
+                return Err(format!("challenge '{}' teaches a talent without a name", name));
+            }
+            for builtin in challenge.constraints.allowed_builtins.iter().flatten() {
+                if script::builtin(builtin).is_none() {
+                    return Err(format!("challenge '{}' allows unknown built-in '{}'", name, builtin));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn select_by(&mut self, delta: isize) {
+        if self.challenges.is_empty() {
+            return;
+        }
+        self.selected = (self.selected as isize + delta).clamp(0, self.challenges.len() as isize - 1) as usize;
+    }
+}
+
+// ---------------------------------------------------------------- leaderboard
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LeaderboardEntry {
+    pub score: u32,
+    pub time_ms: u32,
+    pub damage: u32,
+    pub code_size: usize,
+    pub script: String,
+}
+
+// the best passed runs of each challenge by id, best first.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct Leaderboard {
+    #[serde(default)]
+    pub challenges: BTreeMap<String, Vec<LeaderboardEntry>>,
+}
+
+impl Leaderboard {
+    // an empty leaderboard when there is no file yet.
+    pub fn load(path: &str) -> Result<Leaderboard, String> {
+        if !Path::new(path).is_file() {
+            return Ok(Leaderboard::default());
+        }
+        let raw = fs::read_to_string(path)
+            .map_err(|e| format!("could not read leaderboard {}: {}", path, e))?;
+        serde_json::from_str(&raw).map_err(|e| format!("could not parse leaderboard {}: {}", path, e))
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        if let Some(dir) = Path::new(path).parent() {
+            fs::create_dir_all(dir).map_err(|e| format!("could not create {}: {}", dir.display(), e))?;
+        }
+        let raw = serde_json::to_string_pretty(self).map_err(|e| e.to_string())?;
+        fs::write(path, raw).map_err(|e| format!("could not write leaderboard {}: {}", path, e))
+    }
+
+    pub fn entries(&self, challenge: &str) -> &[LeaderboardEntry] {
+        self.challenges.get(challenge).map(|entries| entries.as_slice()).unwrap_or_default()
+    }
+
+    // the place the entry got, None if it didn't make the board.
+    pub fn record(&mut self, challenge: &str, entry: LeaderboardEntry) -> Option<usize> {
+        let entries = self.challenges.entry(challenge.to_string()).or_default();
+        let place = entries.iter().position(|e| entry.score > e.score).unwrap_or(entries.len());
+        if place >= LEADERBOARD_SIZE {
+            return None;
+        }
+        entries.insert(place, entry);
+        entries.truncate(LEADERBOARD_SIZE);
+        Some(place)
+    }
+}
+
+// ---------------------------------------------------------------- a run
+
+pub struct ChallengeRun {
+    pub index: usize,
+    pub started_ms: u32,
+    pub code_size: usize,
+    pub spawned: Vec<(String, u32)>, // tag, entity
+    pub kills: Vec<(String, u32)>,   // tag, ms into the run it died
+}
+
+impl ChallengeRun {
+    fn kill_time(&self, tag: &str) -> Option<u32> {
+        self.kills.iter().find(|(t, _)| t == tag).map(|(_, ms)| *ms)
+    }
+
+    fn all_killed_time(&self) -> Option<u32> {
+        if self.kills.len() < self.spawned.len() {
+            return None;
+        }
+        self.kills.iter().map(|(_, ms)| *ms).max()
+    }
+
+    // over is set once the run has ended, anything still pending then has failed, except
+    // surviving.
+    pub fn status(&self, objective: &Objective, elapsed_ms: u32, damage: u32, defeated: bool, over: bool) -> ObjectiveStatus {
+        let killed = |killed_at: Option<u32>, within_ms: &Option<u32>| match killed_at {
+            Some(ms) if within_ms.is_none_or(|limit| ms <= limit) => ObjectiveStatus::Done,
+            Some(_) => ObjectiveStatus::Failed,
+            None if over || within_ms.is_some_and(|limit| elapsed_ms > limit) => ObjectiveStatus::Failed,
+            None => ObjectiveStatus::Pending,
+        };
+        match objective {
+            Objective::Kill { tag, within_ms } => killed(self.kill_time(tag), within_ms),
+            Objective::KillAll { within_ms } => killed(self.all_killed_time(), within_ms),
+            Objective::Survive if defeated => ObjectiveStatus::Failed,
+            Objective::Survive if over => ObjectiveStatus::Done,
+            Objective::Survive => ObjectiveStatus::Pending,
+            Objective::DealDamage { amount } if damage >= *amount => ObjectiveStatus::Done,
+            Objective::DealDamage { .. } if over => ObjectiveStatus::Failed,
+            Objective::DealDamage { .. } => ObjectiveStatus::Pending,
+        }
+    }
+}
+
+pub fn status_color(status: ObjectiveStatus) -> Color {
+    match status {
+        ObjectiveStatus::Pending => MAIN_TEXT_CLR,
+        ObjectiveStatus::Done => HEAL_COLOR,
+        ObjectiveStatus::Failed => DAMAGE_TAKEN_COLOR,
+    }
+}
+
+impl Game<'_> {
+    pub fn challenge_panel_open(&self) -> bool {
+        self.state[5] == 1
+    }
+
+    // the script in the editor fights the selected challenge's enemies with the challenge's
+    // seed. the normal encounter comes back once the run is over.
+    pub fn start_challenge(&mut self, index: usize) {
+        let Some(challenge) = self.challenges.challenges.get(index).cloned() else {
+            return;
+        };
+        if self.challenge.is_some() || self.is_defeated() {
+            self.set_script_status("Finish what you are doing first", WARNING_COLOR);
+            return;
+        }
+
+        let source = self.code_editor_text();
+        let checked = challenge.constraints.check(&source)
+            .and_then(|size| script::compile(&source, &self.script_spell_names(), &self.script_unlocks.gate()).map(|_| size));
+        let code_size = match checked {
+            Ok(size) => size,
+            Err(e) => {
+                self.set_script_status(&format!("Can't enter {}: {}", challenge.name, e), DAMAGE_TAKEN_COLOR);
+                return;
+            }
+        };
+
+        self.close_challenge_panel();
+        self.hide_challenge_result();
+        if self.script.running {
+            self.stop_script();
+        }
+        self.reset_arena();
+        self.rng = StdRng::seed_from_u64(challenge.seed);
+        self.combat_stats = CombatStats::new(self.time_elapsed_ms);
+        let spawned = self.spawn_enemies(&challenge.spawns);
+        println!("Challenge: {} (seed {})", challenge.name, challenge.seed);
+
+        self.toggle_script();
+        self.challenge = Some(ChallengeRun {
+            index,
+            started_ms: self.time_elapsed_ms,
+            code_size,
+            spawned,
+            kills: Vec::new(),
+        });
+    }
+
+    // notes kills and ends the run once every enemy is dead, the player is, time runs out, an
+    // objective can no longer be met or the script is stopped.
+    pub fn s_challenge(&mut self) {
+        let Some(run) = self.challenge.as_mut() else {
+            return;
+        };
+        let Some(challenge) = self.challenges.challenges.get(run.index) else {
+            return;
+        };
+        let elapsed = self.time_elapsed_ms.saturating_sub(run.started_ms);
+        let time_limit = challenge.time_limit_ms;
+        for (tag, id) in &run.spawned {
+            if !self.gem.is_alive(*id) && !run.kills.iter().any(|(t, _)| t == tag) {
+                run.kills.push((tag.clone(), elapsed));
+            }
+        }
+
+        let defeated = self.state[3] == 1;
+        let failed = challenge.objectives.iter()
+            .any(|o| run.status(o, elapsed, self.combat_stats.damage_dealt, defeated, false) == ObjectiveStatus::Failed);
+        let cleared = run.kills.len() == run.spawned.len();
+        let timed_out = elapsed >= time_limit;
+        if failed || cleared || timed_out || defeated || !self.script.running {
+            self.finish_challenge(elapsed.min(time_limit));
+        }
+    }
+
+    fn finish_challenge(&mut self, elapsed: u32) {
+        let Some(run) = self.challenge.take() else {
+            return;
+        };
+        let Some(challenge) = self.challenges.challenges.get(run.index).cloned() else {
+            return;
+        };
+        let abandoned = !self.script.running;
+        let defeated = self.is_defeated();
+        let damage = self.combat_stats.damage_dealt;
+        let objectives: Vec<(String, ObjectiveStatus)> = challenge.objectives.iter()
+            .map(|o| (challenge.describe(o, &self.bestiary), run.status(o, elapsed, damage, defeated, true)))
+            .collect();
+        let passed = !abandoned && objectives.iter().all(|(_, status)| *status == ObjectiveStatus::Done);
+
+        if self.script.running {
+            self.stop_script();
+        }
+        let mut lines = vec![];
+        let (headline, color) = match (passed, abandoned) {
+            (true, _) => (format!("{} complete", challenge.name), HEAL_COLOR),
+            (false, true) => (format!("{} abandoned", challenge.name), OFF_TEXT_CLR),
+            (false, false) => (format!("{} failed", challenge.name), DAMAGE_TAKEN_COLOR),
+        };
+        lines.push((headline, 2, color));
+        lines.push((format!("{:.1} s, {} damage, {} tokens", elapsed as f32 / 1000.0, damage, run.code_size), 1, OFF_TEXT_CLR));
+        for (objective, status) in objectives {
+            lines.push((objective, 1, status_color(status)));
+        }
+
+        if passed {
+            let score = challenge.score(elapsed, damage, run.code_size);
+            lines.push((format!("Score {} (time {}, damage {}, size {})", score.total, score.time, score.damage, score.size), 1, LEGENDARY));
+            let entry = LeaderboardEntry {
+                score: score.total,
+                time_ms: elapsed,
+                damage,
+                code_size: run.code_size,
+                script: self.script_library.active.clone().unwrap_or_default(),
+            };
+            match self.leaderboard.record(&challenge.id, entry) {
+                Some(0) => lines.push(("New best!".to_string(), 1, LEGENDARY)),
+                Some(place) => lines.push((format!("#{} on the leaderboard", place + 1), 1, MAIN_TEXT_CLR)),
+                None => {}
+            }
+            if let Err(e) = self.leaderboard.save(LEADERBOARD_PATH) {
+                println!("{}", e);
+            }
+            if let Some(talent) = challenge.teaches.clone() {
+                lines.push((format!("Learned the talent {}", talent), 1, XP_COLOR));
+                self.grant_script_unlocks(crate::script_unlocks::UnlockSource::Talent(talent));
+            }
+        }
+        println!("Challenge {} over, passed: {}", challenge.name, passed);
+
+        self.reset_arena();
+        self.rng = StdRng::from_entropy();
+        self.spawn_encounter(self.encounter_index);
+        self.show_challenge_result(run.index, lines);
+    }
+
+    // no enemies, and the player back on its feet with full health and mana.
+    fn reset_arena(&mut self) {
+        self.clear_enemies();
+        self.damage_queue.clear();
+        self.health_changes.clear();
+        self.interrupt_queue.clear();
+        let Some(player_id) = self.gem.player_id else {
+            return;
+        };
+        if let Some(stats) = self.gem.stats.get_mut(&player_id) {
+            stats.health_curr = stats.health_max;
+        }
+        if let Some(mortality) = self.gem.mortalities.get_mut(&player_id) {
+            mortality.is_alive = true;
+        }
+        self.gem.deaths.remove(&player_id);
+        if let Some(resource) = self.gem.resources.get_mut(&player_id) {
+            resource.curr = resource.max;
+        }
+        if let Some(cooldowns) = self.gem.cooldowns.get_mut(&player_id) {
+            cooldowns.global = GPCooldown::ready();
+            cooldowns.spells.clear();
+        }
+        if let Some(queue) = self.gem.actionqueue.get_mut(&player_id) {
+            queue.queue.clear();
+        }
+        if let Some(dbb) = self.gem.debuffbars.get_mut(&player_id) {
+            dbb.debuffs.clear();
+        }
+        self.state[3] = 0;
+        self.restore_idle_animation();
+    }
+
+    // ------------------------------------------------------------ the picker panel
+
+    pub fn toggle_challenge_panel(&mut self) {
+        if self.challenge_panel_open() {
+            self.close_challenge_panel();
+            return;
+        }
+        self.state[5] = 1;
+        self.show_challenge_panel();
+    }
+
+    pub fn close_challenge_panel(&mut self) {
+        self.state[5] = 0;
+        self.em.purge_entity_by_tag(CHALLENGE_PANEL_TAG);
+        for tag in CHALLENGE_PANEL_BUTTONS {
+            self.em.purge_entity_by_tag(tag);
+        }
+    }
+
+    pub fn challenge_start_selected(&mut self) {
+        self.start_challenge(self.challenges.selected);
+    }
+
+    pub fn challenge_mouse_pressed(&mut self, x: u32, y: u32) -> bool {
+        if !self.challenge_panel_open() {
+            return false;
+        }
+        let scale = get_scale();
+        let s = |x: u32| scaled(scale, x);
+        let top = s(165);
+        let rows = self.challenges.challenges.len() as u32;
+        if x < s(10) || x > s(620) || y < top || y >= top + s(30) * rows {
+            return false;
+        }
+        self.challenges.selected = ((y - top) / s(30)) as usize;
+        true
+    }
+
+    pub fn challenge_key_pressed(&mut self, code: Key) -> bool {
+        if !self.challenge_panel_open() {
+            return false;
+        }
+        match code {
+            Key::Up => self.challenges.select_by(-1),
+            Key::Down => self.challenges.select_by(1),
+            Key::Enter => self.challenge_start_selected(),
+            Key::Escape => self.close_challenge_panel(),
+            _ => return false,
+        }
+        true
+    }
+
+    // covers the editor like the script library; the list and details are drawn in
+    // render_challenges.
+    fn show_challenge_panel(&mut self) {
+        let scale = get_scale();
+        let s = |x: u32| scaled(scale, x);
+
+        let panel = self.em.add_entity(Some(CHALLENGE_PANEL_TAG.to_string()));
+        self.em.add_property_to_entity(PropertiesEnum::Rect, panel);
+        if let Some(rect) = self.em.get_prects_mut(panel).and_then(|rects| rects.last_mut()) {
+            rect.x = s(10);
+            rect.y = s(120);
+            rect.width = s(610);
+            rect.height = s(750);
+            rect.colors = ColorPair::from_colors(ENCAPSULATION_REGIONS, Some(sfml::graphics::Color::BLACK));
+            rect.draw = true;
+            rect.strata = 10;
+        }
+        self.create_panel_button("challenge_start_button", "Start", "Fight the selected challenge with the script in the editor. (Enter)", (s(20), s(810), s(140), s(50)), 20, ClickAction::ChallengeStart);
+    }
+
+    fn show_challenge_result(&mut self, index: usize, lines: Vec<(String, u32, Color)>) {
+        let scale = get_scale();
+        let s = |x: u32| scaled(scale, x);
+        self.challenges.selected = index;
+
+        let panel = self.em.add_entity(Some(CHALLENGE_RESULT_TAG.to_string()));
+        self.em.add_property_to_entity(PropertiesEnum::Rect, panel);
+        if let Some(rect) = self.em.get_prects_mut(panel).and_then(|rects| rects.last_mut()) {
+            rect.x = s(1088);
+            rect.y = s(86);
+            rect.width = s(600);
+            rect.height = s(380);
+            rect.colors = ColorPair::from_colors(ENCAPSULATION_REGIONS, Some(sfml::graphics::Color::BLACK));
+            rect.draw = true;
+            rect.strata = OVERLAY_STRATA;
+        }
+
+        let mut y = s(96);
+        for (line, size, color) in lines.into_iter().take(9) {
+            self.em.add_property_to_entity(PropertiesEnum::Text, panel);
+            if let Some(text) = self.em.get_ptexts_mut(panel).and_then(|texts| texts.last_mut()) {
+                text.text = line;
+                text.scale = scale * size;
+                text.x = s(1108);
+                text.y = y;
+                text.colors = ColorPair::from_colors(color, Some(sfml::graphics::Color::BLACK));
+                text.draw = true;
+                text.strata = OVERLAY_STRATA + 5;
+            }
+            y += s(20 * size + 12);
+        }
+
+        self.create_panel_button("challenge_retry_button", "Retry", "Fight the challenge again with the script in the editor.", (s(1108), s(400), s(140), s(50)), OVERLAY_STRATA + 10, ClickAction::ChallengeRetry);
+        self.create_panel_button("challenge_dismiss_button", "Close", "Back to the fight you were in.", (s(1408), s(400), s(140), s(50)), OVERLAY_STRATA + 10, ClickAction::ChallengeDismiss);
+    }
+
+    pub fn hide_challenge_result(&mut self) {
+        self.em.purge_entity_by_tag(CHALLENGE_RESULT_TAG);
+        for tag in CHALLENGE_RESULT_BUTTONS {
+            self.em.purge_entity_by_tag(tag);
+        }
+    }
+}
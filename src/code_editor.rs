@@ -0,0 +1,658 @@
+use sfml::graphics::Color;
+use sfml::window::{clipboard, Key};
+use crate::game::*;
+use crate::properties::{CompletionItem, EditKind, EditorSnapshot, PCodeEditor, TextPos};
+use crate::script::{self, Builtin, BuiltinKind, Token, TokenKind, BUILTINS};
+
+pub static EDITOR_TAG: &str = "textbox_encap";
+pub static COMPLETION_ROWS: usize = 6;
+pub static UNDO_LIMIT: usize = 200;
+pub static INDENT: &str = "    ";
+
+// byte offset of the col'th char, or the end of the line.
+fn byte_index(line: &str, col: usize) -> usize {
+    line.char_indices().nth(col).map(|(i, _)| i).unwrap_or(line.len())
+}
+
+impl PCodeEditor {
+    pub fn text(&self) -> String {
+        self.lines.join("\n")
+    }
+
+    // replaces everything, e.g. when a script is loaded; this can't be undone.
+    pub fn set_text(&mut self, text: &str) {
+        self.lines = text.replace("\r\n", "\n").split('\n').map(str::to_string).collect();
+        self.caret = TextPos::default();
+        self.anchor = None;
+        self.scroll = 0;
+        self.scroll_x = 0;
+        self.undo.clear();
+        self.redo.clear();
+        self.last_edit = EditKind::None;
+        self.completions.clear();
+        self.breakpoints.clear();
+        self.revision += 1;
+    }
+
+    pub fn line_len(&self, line: usize) -> usize {
+        self.lines.get(line).map(|l| l.chars().count()).unwrap_or(0)
+    }
+
+    fn clamp(&self, pos: TextPos) -> TextPos {
+        let line = pos.line.min(self.lines.len() - 1);
+        TextPos { line, col: pos.col.min(self.line_len(line)) }
+    }
+
+    // the selected range in reading order, None if nothing is selected.
+    pub fn selection(&self) -> Option<(TextPos, TextPos)> {
+        let anchor = self.anchor?;
+        if anchor == self.caret {
+            return None;
+        }
+        Some((anchor.min(self.caret), anchor.max(self.caret)))
+    }
+
+    pub fn selected_text(&self) -> String {
+        let Some((start, end)) = self.selection() else {
+            return String::new();
+        };
+        if start.line == end.line {
+            let line = &self.lines[start.line];
+            return line[byte_index(line, start.col)..byte_index(line, end.col)].to_string();
+        }
+        let first = &self.lines[start.line];
+        let last = &self.lines[end.line];
+        let mut parts = vec![first[byte_index(first, start.col)..].to_string()];
+        parts.extend(self.lines[start.line + 1..end.line].iter().cloned());
+        parts.push(last[..byte_index(last, end.col)].to_string());
+        parts.join("\n")
+    }
+
+    // remembers the text before an edit. runs of typing or deleting are undone as one.
+    fn snapshot(&mut self, kind: EditKind) {
+        if kind == EditKind::Other || kind != self.last_edit || self.selection().is_some() {
+            self.undo.push(EditorSnapshot { lines: self.lines.clone(), caret: self.caret });
+            if self.undo.len() > UNDO_LIMIT {
+                self.undo.remove(0);
+            }
+        }
+        self.redo.clear();
+        self.last_edit = kind;
+        self.revision += 1;
+    }
+
+    fn delete_selection(&mut self) -> bool {
+        let Some((start, end)) = self.selection() else {
+            self.anchor = None;
+            return false;
+        };
+        let tail = {
+            let last = &self.lines[end.line];
+            last[byte_index(last, end.col)..].to_string()
+        };
+        let first = &mut self.lines[start.line];
+        first.truncate(byte_index(first, start.col));
+        first.push_str(&tail);
+        self.lines.drain(start.line + 1..=end.line);
+        self.caret = start;
+        self.anchor = None;
+        true
+    }
+
+    fn insert_raw(&mut self, text: &str) {
+        let line = &mut self.lines[self.caret.line];
+        let at = byte_index(line, self.caret.col);
+        let tail = line.split_off(at);
+        let mut pieces = text.split('\n');
+        if let Some(first) = pieces.next() {
+            line.push_str(first);
+            self.caret.col += first.chars().count();
+        }
+        for piece in pieces {
+            self.caret.line += 1;
+            self.caret.col = piece.chars().count();
+            self.lines.insert(self.caret.line, piece.to_string());
+        }
+        let line = &mut self.lines[self.caret.line];
+        line.push_str(&tail);
+    }
+
+    pub fn insert(&mut self, text: &str) {
+        let text = text.replace("\r\n", "\n").replace('\t', INDENT);
+        let kind = if text.chars().count() == 1 && text != "\n" { EditKind::Typing } else { EditKind::Other };
+        self.snapshot(kind);
+        self.delete_selection();
+        self.insert_raw(&text);
+    }
+
+    // a new line keeps the indentation of the current one, one level deeper after a '{'.
+    pub fn newline(&mut self) {
+        self.snapshot(EditKind::Other);
+        self.delete_selection();
+        let line = &self.lines[self.caret.line];
+        let mut indent: String = line.chars().take_while(|c| *c == ' ').collect();
+        let before = &line[..byte_index(line, self.caret.col)];
+        if before.trim_end().ends_with('{') {
+            indent.push_str(INDENT);
+        }
+        self.insert_raw(&format!("\n{}", indent));
+    }
+
+    // typing '}' on an otherwise empty line lines it up with its '{'.
+    pub fn type_char(&mut self, c: char) {
+        let line = &self.lines[self.caret.line];
+        let only_indent = line.chars().all(|ch| ch == ' ') && line.len() >= INDENT.len();
+        if c == '}' && only_indent && self.selection().is_none() {
+            self.snapshot(EditKind::Other);
+            let line = &mut self.lines[self.caret.line];
+            line.truncate(line.len() - INDENT.len());
+            self.caret.col = self.line_len(self.caret.line);
+            self.insert_raw("}");
+            return;
+        }
+        self.insert(&c.to_string());
+    }
+
+    pub fn backspace(&mut self) {
+        self.snapshot(EditKind::Deleting);
+        if self.delete_selection() {
+            return;
+        }
+        if self.caret.col > 0 {
+            let line = &mut self.lines[self.caret.line];
+            let at = byte_index(line, self.caret.col - 1);
+            line.remove(at);
+            self.caret.col -= 1;
+        } else if self.caret.line > 0 {
+            let removed = self.lines.remove(self.caret.line);
+            self.caret.line -= 1;
+            self.caret.col = self.line_len(self.caret.line);
+            self.lines[self.caret.line].push_str(&removed);
+        }
+    }
+
+    pub fn delete_forward(&mut self) {
+        self.snapshot(EditKind::Deleting);
+        if self.delete_selection() {
+            return;
+        }
+        if self.caret.col < self.line_len(self.caret.line) {
+            let line = &mut self.lines[self.caret.line];
+            let at = byte_index(line, self.caret.col);
+            line.remove(at);
+        } else if self.caret.line + 1 < self.lines.len() {
+            let next = self.lines.remove(self.caret.line + 1);
+            self.lines[self.caret.line].push_str(&next);
+        }
+    }
+
+    // moves the caret, extending the selection when select is set and dropping it otherwise.
+    pub fn move_caret(&mut self, to: TextPos, select: bool) {
+        if select {
+            if self.anchor.is_none() {
+                self.anchor = Some(self.caret);
+            }
+        } else {
+            self.anchor = None;
+        }
+        self.caret = self.clamp(to);
+        self.last_edit = EditKind::None;
+        self.completions.clear();
+    }
+
+    pub fn move_left(&mut self, select: bool) {
+        if let (Some((start, _)), false) = (self.selection(), select) {
+            return self.move_caret(start, false);
+        }
+        let to = if self.caret.col > 0 {
+            TextPos { line: self.caret.line, col: self.caret.col - 1 }
+        } else if self.caret.line > 0 {
+            TextPos { line: self.caret.line - 1, col: self.line_len(self.caret.line - 1) }
+        } else {
+            self.caret
+        };
+        self.move_caret(to, select);
+    }
+
+    pub fn move_right(&mut self, select: bool) {
+        if let (Some((_, end)), false) = (self.selection(), select) {
+            return self.move_caret(end, false);
+        }
+        let to = if self.caret.col < self.line_len(self.caret.line) {
+            TextPos { line: self.caret.line, col: self.caret.col + 1 }
+        } else if self.caret.line + 1 < self.lines.len() {
+            TextPos { line: self.caret.line + 1, col: 0 }
+        } else {
+            self.caret
+        };
+        self.move_caret(to, select);
+    }
+
+    pub fn move_lines(&mut self, delta: isize, select: bool) {
+        let line = (self.caret.line as isize + delta).max(0) as usize;
+        self.move_caret(TextPos { line, col: self.caret.col }, select);
+    }
+
+    pub fn select_all(&mut self) {
+        let last = self.lines.len() - 1;
+        self.anchor = Some(TextPos::default());
+        self.caret = TextPos { line: last, col: self.line_len(last) };
+    }
+
+    pub fn undo(&mut self) {
+        if let Some(snapshot) = self.undo.pop() {
+            self.redo.push(EditorSnapshot { lines: self.lines.clone(), caret: self.caret });
+            self.restore(snapshot);
+        }
+    }
+
+    pub fn redo(&mut self) {
+        if let Some(snapshot) = self.redo.pop() {
+            self.undo.push(EditorSnapshot { lines: self.lines.clone(), caret: self.caret });
+            self.restore(snapshot);
+        }
+    }
+
+    fn restore(&mut self, snapshot: EditorSnapshot) {
+        self.lines = snapshot.lines;
+        self.caret = self.clamp(snapshot.caret);
+        self.anchor = None;
+        self.last_edit = EditKind::None;
+        self.completions.clear();
+        self.revision += 1;
+    }
+
+    // replaces the word being typed with the highlighted suggestion.
+    pub fn accept_completion(&mut self) {
+        let Some(item) = self.completions.get(self.completion_index).cloned() else {
+            return;
+        };
+        self.snapshot(EditKind::Other);
+        self.anchor = None;
+        let line = &mut self.lines[self.caret.line];
+        let start = self.caret.col.saturating_sub(self.completion_prefix);
+        line.replace_range(byte_index(line, start)..byte_index(line, self.caret.col), "");
+        self.caret.col = start;
+        self.insert_raw(&item.insert);
+        self.completions.clear();
+    }
+
+    pub fn toggle_breakpoint(&mut self, line: usize) {
+        if line >= self.lines.len() {
+            return;
+        }
+        match self.breakpoints.binary_search(&line) {
+            Ok(i) => {
+                self.breakpoints.remove(i);
+            }
+            Err(i) => self.breakpoints.insert(i, line),
+        }
+    }
+
+    // keeps breakpoints on their statements when lines after `from` were added (delta > 0) or
+    // removed (delta < 0); breakpoints on removed lines go with them.
+    pub fn shift_breakpoints(&mut self, from: usize, delta: isize) {
+        if delta == 0 {
+            return;
+        }
+        let removed_to = from as isize - delta;
+        self.breakpoints.retain(|&line| line <= from || line as isize > removed_to);
+        for line in self.breakpoints.iter_mut().filter(|line| **line > from) {
+            *line = (*line as isize + delta) as usize;
+        }
+        let last = self.lines.len() - 1;
+        self.breakpoints.retain(|&line| line <= last);
+    }
+
+    pub fn scroll_by(&mut self, lines: isize, visible_lines: usize) {
+        let max_scroll = self.lines.len().saturating_sub(visible_lines);
+        self.scroll = (self.scroll as isize + lines).clamp(0, max_scroll as isize) as usize;
+    }
+
+    pub fn ensure_caret_visible(&mut self, visible_lines: usize, visible_cols: usize) {
+        if self.caret.line < self.scroll {
+            self.scroll = self.caret.line;
+        } else if self.caret.line >= self.scroll + visible_lines {
+            self.scroll = self.caret.line + 1 - visible_lines;
+        }
+        if self.caret.col < self.scroll_x {
+            self.scroll_x = self.caret.col;
+        } else if self.caret.col >= self.scroll_x + visible_cols {
+            self.scroll_x = self.caret.col + 1 - visible_cols;
+        }
+    }
+}
+
+// sizes the editor is drawn and clicked with, all in window pixels.
+pub struct EditorMetrics {
+    pub char_size: u32,
+    pub line_height: f32,
+    pub char_width: f32,
+    pub text_x: f32, // left edge of the text, right of the line numbers
+    pub text_y: f32,
+    pub visible_lines: usize,
+    pub visible_cols: usize,
+}
+
+impl Game<'_> {
+    pub fn code_editor_id(&self) -> Option<u32> {
+        self.em.get_id_by_tag(EDITOR_TAG.to_string())
+    }
+
+    pub fn code_editor(&self) -> Option<&PCodeEditor> {
+        self.code_editor_id().and_then(|id| self.em.get_pcode_editor_non_mut(id))
+    }
+
+    fn code_editor_mut(&mut self) -> Option<&mut PCodeEditor> {
+        self.code_editor_id().and_then(|id| self.em.get_pcode_editor_mut(id))
+    }
+
+    // the stats menu and the script library cover the editor while they are open.
+    pub fn code_editor_visible(&self) -> bool {
+        self.state[2] != 1 && !self.script_library_open() && !self.challenge_panel_open() && !self.training_panel_open() && self.code_editor().is_some_and(|editor| editor.draw)
+    }
+
+    pub fn code_editor_focused(&self) -> bool {
+        self.code_editor_visible() && self.code_editor().is_some_and(|editor| editor.focused)
+    }
+
+    pub fn code_editor_text(&self) -> String {
+        self.code_editor().map(|editor| editor.text()).unwrap_or_default()
+    }
+
+    pub fn editor_metrics(&self, editor: &PCodeEditor) -> EditorMetrics {
+        let scale = get_scale();
+        let s = |x: u32| scaled(scale, x) as f32;
+        let char_size = scale * 16;
+        let line_height = s(20);
+        let char_width = self.fnt.glyph('M' as u32, char_size, false, 0.0).advance().max(1.0);
+        let text_x = editor.x as f32 + s(52);
+        let text_y = editor.y as f32 + s(6);
+        let visible_lines = ((editor.height as f32 - s(12)) / line_height).max(1.0) as usize;
+        let visible_cols = ((editor.x + editor.width) as f32 - text_x - s(12)) / char_width;
+        EditorMetrics {
+            char_size,
+            line_height,
+            char_width,
+            text_x,
+            text_y,
+            visible_lines,
+            visible_cols: visible_cols.max(1.0) as usize,
+        }
+    }
+
+    fn editor_pos_at(&self, editor: &PCodeEditor, x: u32, y: u32) -> TextPos {
+        let m = self.editor_metrics(editor);
+        let line = ((y as f32 - m.text_y).max(0.0) / m.line_height) as usize + editor.scroll;
+        let col = ((x as f32 - m.text_x).max(0.0) / m.char_width).round() as usize + editor.scroll_x;
+        TextPos { line, col }
+    }
+
+    fn editor_contains(&self, x: u32, y: u32) -> bool {
+        self.code_editor_visible() && self.code_editor().is_some_and(|e| {
+            x >= e.x && x <= e.x + e.width && y >= e.y && y <= e.y + e.height
+        })
+    }
+
+    // runs f on the editor and keeps the caret in view afterwards.
+    fn edit_code(&mut self, f: impl FnOnce(&mut PCodeEditor)) {
+        let Some(editor) = self.code_editor() else {
+            return;
+        };
+        let m = self.editor_metrics(editor);
+        let line_count = editor.lines.len();
+        let from = editor.selection().map(|(start, _)| start.line).unwrap_or(editor.caret.line);
+        if let Some(editor) = self.code_editor_mut() {
+            f(editor);
+            editor.shift_breakpoints(from, editor.lines.len() as isize - line_count as isize);
+            editor.ensure_caret_visible(m.visible_lines, m.visible_cols);
+        }
+    }
+
+    // focuses the editor and puts the caret under the mouse, returns false for clicks elsewhere.
+    pub fn editor_mouse_pressed(&mut self, x: u32, y: u32, shift: bool) -> bool {
+        if !self.editor_contains(x, y) {
+            if let Some(editor) = self.code_editor_mut() {
+                editor.focused = false;
+                editor.dragging = false;
+            }
+            return false;
+        }
+        let Some(pos) = self.code_editor().map(|editor| self.editor_pos_at(editor, x, y)) else {
+            return false;
+        };
+        // clicks on the line numbers set and clear breakpoints
+        let in_gutter = self.code_editor().is_some_and(|editor| (x as f32) < self.editor_metrics(editor).text_x - scaled(get_scale(), 6) as f32);
+        if in_gutter {
+            if let Some(editor) = self.code_editor_mut() {
+                editor.toggle_breakpoint(pos.line);
+            }
+            return true;
+        }
+        self.edit_code(|editor| {
+            editor.focused = true;
+            editor.completions.clear();
+            editor.dragging = true;
+            editor.move_caret(pos, shift);
+        });
+        true
+    }
+
+    pub fn editor_mouse_moved(&mut self, x: u32, y: u32) {
+        let Some(editor) = self.code_editor().filter(|editor| editor.dragging) else {
+            return;
+        };
+        let pos = self.editor_pos_at(editor, x, y);
+        self.edit_code(|editor| editor.move_caret(pos, true));
+    }
+
+    pub fn editor_mouse_released(&mut self) {
+        if let Some(editor) = self.code_editor_mut() {
+            editor.dragging = false;
+        }
+    }
+
+    pub fn editor_scrolled(&mut self, delta: f32, x: u32, y: u32) {
+        if !self.editor_contains(x, y) {
+            return;
+        }
+        let Some(editor) = self.code_editor() else {
+            return;
+        };
+        let visible_lines = self.editor_metrics(editor).visible_lines;
+        if let Some(editor) = self.code_editor_mut() {
+            editor.scroll_by(-(delta * 3.0) as isize, visible_lines);
+        }
+    }
+
+    pub fn editor_text_entered(&mut self, c: char) {
+        if !self.code_editor_focused() || c.is_control() {
+            return;
+        }
+        self.edit_code(|editor| editor.type_char(c));
+        if c.is_alphanumeric() || c == '_' || c == '.' || c == '"' {
+            self.update_completions();
+        } else if let Some(editor) = self.code_editor_mut() {
+            editor.completions.clear();
+        }
+    }
+
+    // suggestions for the word in front of the caret: spells inside text, fields after a dot and
+    // functions, globals and spells anywhere else.
+    fn update_completions(&mut self) {
+        let Some(editor) = self.code_editor() else {
+            return;
+        };
+        let before: Vec<char> = editor.lines[editor.caret.line].chars().take(editor.caret.col).collect();
+        let prefix_len = before.iter().rev().take_while(|c| c.is_alphanumeric() || **c == '_').count();
+        let prefix: String = before[before.len() - prefix_len..].iter().collect();
+        let previous = before.len().checked_sub(prefix_len + 1).map(|i| before[i]);
+        let in_string = before.iter().filter(|c| **c == '"').count() % 2 == 1;
+
+        let spell_item = |tag: &str, fill: (u8, u8, u8), quoted: bool| CompletionItem {
+            label: tag.to_string(),
+            insert: if quoted { format!("\"{}\"", tag) } else { tag.to_string() },
+            detail: "spell".to_string(),
+            color: fill,
+        };
+        let gate = self.script_unlocks.gate();
+        let builtin_item = |b: &Builtin| {
+            let name = b.name;
+            let (insert, detail) = match b.kind {
+                BuiltinKind::Function(0) => (format!("{}()", name), "function"),
+                BuiltinKind::Function(_) => (format!("{}(", name), "function"),
+                BuiltinKind::Method(_, _) => (format!("{}(", name), "method"),
+                BuiltinKind::Field(_) => (name.to_string(), "field"),
+                BuiltinKind::Global => (name.to_string(), "global"),
+                BuiltinKind::Handler(true) => (format!("{}(", name), "handler"),
+                BuiltinKind::Handler(false) => (format!("{} {{", name), "handler"),
+            };
+            // locked ones are still listed so players know what is coming
+            if b.feature.is_some_and(|f| !gate.allows(f)) {
+                let color = (OFF_TEXT_CLR.r, OFF_TEXT_CLR.g, OFF_TEXT_CLR.b);
+                return CompletionItem { label: name.to_string(), insert, detail: "locked".to_string(), color };
+            }
+            let color = crate::helpers::get_stat_color_by_name(name)
+                .unwrap_or((BUILTIN_COLOR.r, BUILTIN_COLOR.g, BUILTIN_COLOR.b));
+            CompletionItem { label: name.to_string(), insert, detail: detail.to_string(), color }
+        };
+
+        let mut items: Vec<CompletionItem> = Vec::new();
+        if in_string {
+            items.extend(self.spellbook.spells.iter()
+                .map(|spell| spell_item(spell.id.tag(), spell.colors.fill, false)));
+        } else if previous == Some('.') {
+            items.extend(BUILTINS.iter()
+                .filter(|b| matches!(b.kind, BuiltinKind::Field(_) | BuiltinKind::Method(..)))
+                .map(builtin_item));
+        } else if prefix_len > 0 {
+            items.extend(BUILTINS.iter()
+                .filter(|b| matches!(b.kind, BuiltinKind::Function(_) | BuiltinKind::Global | BuiltinKind::Handler(_)))
+                .map(builtin_item));
+            items.extend(self.spellbook.spells.iter()
+                .map(|spell| spell_item(spell.id.tag(), spell.colors.fill, true)));
+        }
+        items.retain(|item| item.label.starts_with(&prefix) && item.label != prefix);
+
+        if let Some(editor) = self.code_editor_mut() {
+            editor.completions = items;
+            editor.completion_index = 0;
+            editor.completion_prefix = prefix_len;
+        }
+    }
+
+    // compiles the editor's text whenever it changes so mistakes can be underlined while typing.
+    pub fn check_code_editor(&mut self) {
+        let Some(editor) = self.code_editor() else {
+            return;
+        };
+        if self.script.checked_revision == Some(editor.revision) {
+            return;
+        }
+        let revision = editor.revision;
+        let text = editor.text();
+        self.script.diagnostic = script::compile(&text, &self.script_spell_names(), &self.script_unlocks.gate()).err();
+        self.script.checked_revision = Some(revision);
+    }
+
+    pub fn script_token_color(&self, token: &Token) -> Color {
+        let rgb = |(r, g, b): (u8, u8, u8)| Color::rgb(r, g, b);
+        match &token.kind {
+            TokenKind::Keyword(_) => KEYWORD_COLOR,
+            TokenKind::Number(_) => NUMBER_COLOR,
+            TokenKind::Str(s) => self.spellbook.get_by_name(s)
+                .map(|spell| rgb(spell.colors.fill))
+                .unwrap_or(STRING_COLOR),
+            TokenKind::Ident(name) => match crate::helpers::get_stat_color_by_name(name) {
+                Some(color) => rgb(color),
+                None if script::builtin(name).is_some() => BUILTIN_COLOR,
+                None => MAIN_TEXT_CLR,
+            },
+            TokenKind::Comment => OFF_TEXT_CLR,
+            TokenKind::Invalid => DAMAGE_TAKEN_COLOR,
+            TokenKind::Symbol(_) | TokenKind::Eof => MAIN_TEXT_CLR,
+        }
+    }
+
+    // returns true if the editor took the key, hotkeys are ignored while typing.
+    pub fn editor_key_pressed(&mut self, code: Key, ctrl: bool, shift: bool) -> bool {
+        if !self.code_editor_focused() {
+            return false;
+        }
+        let Some(editor) = self.code_editor() else {
+            return false;
+        };
+        let page = self.editor_metrics(editor).visible_lines as isize;
+        let count = editor.completions.len();
+        let selected = editor.selected_text();
+
+        if count > 0 {
+            let handled = match code {
+                Key::Up => self.code_editor_mut().map(|e| e.completion_index = (e.completion_index + count - 1) % count),
+                Key::Down => self.code_editor_mut().map(|e| e.completion_index = (e.completion_index + 1) % count),
+                Key::Tab | Key::Enter => self.code_editor_mut().map(|e| e.accept_completion()),
+                Key::Escape => self.code_editor_mut().map(|e| e.completions.clear()),
+                _ => None,
+            };
+            if handled.is_some() {
+                return true;
+            }
+        }
+
+        match (code, ctrl) {
+            (Key::Escape, _) => {
+                if let Some(editor) = self.code_editor_mut() {
+                    editor.focused = false;
+                }
+            }
+            (Key::F9, _) => {
+                if let Some(editor) = self.code_editor_mut() {
+                    editor.toggle_breakpoint(editor.caret.line);
+                }
+            }
+            (Key::C, true) | (Key::X, true) if !selected.is_empty() => {
+                clipboard::set_string(selected.as_str());
+                if code == Key::X {
+                    self.edit_code(|editor| editor.backspace());
+                }
+            }
+            (Key::V, true) => {
+                let pasted = clipboard::get_string();
+                self.edit_code(|editor| editor.insert(&pasted));
+            }
+            (Key::A, true) => self.edit_code(|editor| editor.select_all()),
+            (Key::Z, true) if shift => self.edit_code(|editor| editor.redo()),
+            (Key::Z, true) => self.edit_code(|editor| editor.undo()),
+            (Key::Y, true) => self.edit_code(|editor| editor.redo()),
+            (Key::Enter, _) => self.edit_code(|editor| editor.newline()),
+            (Key::Tab, _) => self.edit_code(|editor| editor.insert(INDENT)),
+            (Key::Backspace, _) => {
+                self.edit_code(|editor| editor.backspace());
+                if count > 0 {
+                    self.update_completions();
+                }
+            }
+            (Key::Delete, _) => self.edit_code(|editor| editor.delete_forward()),
+            (Key::Left, _) => self.edit_code(|editor| editor.move_left(shift)),
+            (Key::Right, _) => self.edit_code(|editor| editor.move_right(shift)),
+            (Key::Up, _) => self.edit_code(|editor| editor.move_lines(-1, shift)),
+            (Key::Down, _) => self.edit_code(|editor| editor.move_lines(1, shift)),
+            (Key::PageUp, _) => self.edit_code(|editor| editor.move_lines(-page, shift)),
+            (Key::PageDown, _) => self.edit_code(|editor| editor.move_lines(page, shift)),
+            (Key::Home, true) => self.edit_code(|editor| editor.move_caret(TextPos::default(), shift)),
+            (Key::End, true) => self.edit_code(|editor| {
+                let last = editor.lines.len() - 1;
+                editor.move_caret(TextPos { line: last, col: editor.line_len(last) }, shift);
+            }),
+            (Key::Home, false) => self.edit_code(|editor| {
+                editor.move_caret(TextPos { line: editor.caret.line, col: 0 }, shift);
+            }),
+            (Key::End, false) => self.edit_code(|editor| {
+                let line = editor.caret.line;
+                editor.move_caret(TextPos { line, col: editor.line_len(line) }, shift);
+            }),
+            _ => {}
+        }
+        true
+    }
+}
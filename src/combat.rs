@@ -0,0 +1,932 @@
+use std::collections::HashMap;
+use std::rc::Rc;
+use rand::Rng;
+use rand::rngs::StdRng;
+use crate::bestiary::EnemyData;
+use crate::damage_pipeline::{DamageContext, DamagePipeline};
+use crate::g_entities::GameEntityManager;
+use crate::g_properties::*;
+use crate::player_data::PlayerData;
+use crate::script::ScriptEvent;
+use crate::script_vm::{Program, Value};
+use crate::spellbook::Spellbook;
+
+// the rules of a fight, shared by the game and the headless simulator in src/bin/sim.rs so their
+// numbers match. nothing in here draws anything; the game lends its state out for the length of
+// a call through Game::combat and does the showing itself.
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Damage {
+    pub amt: u32,
+    pub target: u32,
+    pub damager: u32,
+    pub spell: Option<Spells>,
+    pub school: DamageSchool,
+    pub periodic: bool, // a DoT tick rather than a direct hit
+    pub attacker_stats: Option<GPStats>, // snapshot taken when a DoT was applied, live stats when None
+    pub outcome: Option<HitOutcome>, // rolled by the damage pipeline
+}
+
+// signed: negative is damage, positive healing. applied and clamped to health_max in s_health.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct HealthChange {
+    pub target: u32,
+    pub source: u32,
+    pub amount: i32,
+    pub spell: Option<Spells>,
+    pub school: Option<DamageSchool>,
+    pub outcome: HitOutcome,
+    pub mitigated: u32,
+}
+
+pub static BASE_MISS_CHANCE: f64 = 0.05;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum HitOutcome {
+    Hit,
+    Critical,
+    PartialResist,
+    Miss,
+    Immune,
+}
+
+// why a finished cast did or didn't go off.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum CastResult {
+    Success,
+    NoTarget,
+    TargetDied,
+    NotEnoughResource(String),
+}
+
+impl CastResult {
+    pub fn message(&self) -> Option<String> {
+        match self {
+            CastResult::Success => None,
+            CastResult::NoTarget => Some("No target".to_string()),
+            CastResult::TargetDied => Some("Target died".to_string()),
+            CastResult::NotEnoughResource(name) => Some(format!("Not enough {}", name)),
+        }
+    }
+}
+
+// a cast that left the front of its caster's queue this tick, see advance_casts.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FinishedCast {
+    pub caster: u32,
+    pub spell: Option<Spells>,
+    pub target: Option<u32>,
+    pub result: CastResult,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum InterruptReason {
+    Cancelled,
+    Interrupted,
+}
+
+// stops whatever the target is casting and drops the rest of its action queue, see
+// apply_interrupts.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Interrupt {
+    pub target: u32,
+    pub source: Option<u32>,
+    pub reason: InterruptReason,
+}
+
+// why the player can't cast a spell right now.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CastRejected {
+    pub message: String,
+    pub missing_resource: bool,
+}
+
+impl CastRejected {
+    fn new(message: impl Into<String>) -> Self {
+        CastRejected { message: message.into(), missing_resource: false }
+    }
+}
+
+pub struct Combat<'c> {
+    pub gem: &'c mut GameEntityManager,
+    pub spellbook: &'c Spellbook,
+    pub damage_pipeline: &'c DamagePipeline,
+    pub rng: &'c mut StdRng,
+    pub damage_queue: &'c mut Vec<Damage>,
+    pub health_changes: &'c mut Vec<HealthChange>,
+    pub interrupts: &'c mut Vec<Interrupt>,
+    pub delta_time_ms: u32,
+}
+
+impl Combat<'_> {
+    pub fn player_target(&self) -> Option<u32> {
+        self.gem.player_id.and_then(|pid| self.gem.get_target(pid))
+    }
+
+    pub fn set_player_target(&mut self, target: Option<u32>) {
+        let Some(player_id) = self.gem.player_id else {
+            return;
+        };
+        if let Some(t) = self.gem.targets.get_mut(&player_id) {
+            t.target_entity = target;
+        }
+    }
+
+    // ------------------------------------------------------------ spawning
+
+    pub fn spawn_player(&mut self, data: &PlayerData) -> u32 {
+        let pgid = self.gem.add_entity(Some("player".to_string()));
+
+        let next_id = self.gem.next_pid();
+        self.gem.names.insert(pgid, GPName {
+            id: next_id,
+            name: data.name.clone(),});
+
+        let next_id = self.gem.next_pid();
+        self.gem.mortalities.insert(pgid, GPMortality {
+            id: next_id,
+            is_alive: true,
+            immortal: false,});
+
+        let next_id = self.gem.next_pid();
+        self.gem.allegiances.insert(pgid, GPAllegiance {
+            id: next_id,
+            allegiance: Allegiances::Player,});
+
+        let next_id = self.gem.next_pid();
+        self.gem.positions.insert(pgid, GPPosition {
+            id: next_id,
+            x: data.position.0,
+            y: data.position.1,});
+
+        let next_id = self.gem.next_pid();
+        self.gem.stats.insert(pgid, GPStats {
+            id: next_id,
+            health_max: data.health,
+            health_curr: data.starting_health,
+            chaos: data.chaos,
+            solidity: data.solidity,
+            vitality: data.vitality,
+            haste: data.haste,
+            will: data.will,
+            volatility: data.volatility,});
+
+        let next_id = self.gem.next_pid();
+        self.gem.resistances.insert(pgid, GPResistances {
+            id: next_id,
+            values: HashMap::new(),});
+
+        let next_id = self.gem.next_pid();
+        self.gem.resources.insert(pgid, GPResource {
+            id: next_id,
+            name: data.resource.clone(),
+            max: GPResource::max_from_will(data.will),
+            curr: GPResource::max_from_will(data.will),
+            regen_per_sec: GPResource::regen_from_will(data.will),
+            pending_regen: 0.0,});
+
+        let next_id = self.gem.next_pid();
+        self.gem.targets.insert(pgid, GPTarget {
+            id: (next_id),
+            target_entity: (None)});
+
+        let next_id = self.gem.next_pid();
+        self.gem.buffbars.insert(pgid, GPBuffBar {
+            id: next_id,
+            buffs: Vec::new(),});
+
+        let next_id = self.gem.next_pid();
+        self.gem.debuffbars.insert(pgid, GPDebuffBar {
+            id: next_id,
+            debuffs: Vec::new(),});
+
+        let next_id = self.gem.next_pid();
+        self.gem.actionqueue.insert(pgid, GPActionQueue {
+            id: next_id,
+            queue: Vec::new(),
+            queue_window: SPELL_QUEUE_WINDOW_MS,});
+
+        let next_id = self.gem.next_pid();
+        self.gem.cooldowns.insert(pgid, GPCooldowns {
+            id: next_id,
+            global: GPCooldown::ready(),
+            spells: HashMap::new(),});
+
+        self.gem.player_id = Some(pgid);
+
+        let next_id = self.gem.next_pid();
+        self.gem.levels.insert(pgid, GPLevel {
+            id: next_id,
+            curr_level: data.level,
+            curr_xp: data.xp,
+            next_level_xp: GPLevel::xp_for_level(data.level) });
+        pgid
+    }
+
+    // center is in unscaled coordinates, the game adds the sprite, see create_enemy.
+    pub fn spawn_enemy(&mut self, tag: &str, data: &EnemyData, level: u32, center: (u32, u32)) -> u32 {
+        let enemy_id = self.gem.add_entity(Some(tag.to_string()));
+
+        let next_id = self.gem.next_pid();
+        self.gem.names.insert(enemy_id, GPName {
+            id: next_id,
+            name: data.name.clone(),
+        });
+
+        let next_id = self.gem.next_pid();
+        self.gem.mortalities.insert(enemy_id, GPMortality {
+            id: next_id,
+            is_alive: true,
+            immortal: data.immortal,
+        });
+
+        let next_id = self.gem.next_pid();
+        self.gem.levels.insert(enemy_id, GPLevel {
+            id: next_id,
+            curr_level: level,
+            curr_xp: 0,
+            next_level_xp: 100,
+        });
+
+        let next_id = self.gem.next_pid();
+        self.gem.allegiances.insert(enemy_id, GPAllegiance {
+            id: next_id,
+            allegiance: Allegiances::Enemy,
+        });
+
+        let next_id = self.gem.next_pid();
+        self.gem.positions.insert(enemy_id, GPPosition {
+            id: next_id,
+            x: center.0,
+            y: center.1,
+        });
+
+        let per_level = &data.stats_per_level;
+        let next_id = self.gem.next_pid();
+        self.gem.stats.insert(enemy_id, GPStats {
+            id: next_id,
+            chaos: per_level.chaos.saturating_mul(level),
+            solidity: per_level.solidity.saturating_mul(level),
+            vitality: per_level.vitality.saturating_mul(level),
+            haste: per_level.haste.saturating_mul(level),
+            will: per_level.will.saturating_mul(level),
+            volatility: per_level.volatility.saturating_mul(level),
+            health_max: per_level.health.saturating_mul(level),
+            health_curr: per_level.health.saturating_mul(level),
+        });
+
+        let next_id = self.gem.next_pid();
+        self.gem.resistances.insert(enemy_id, GPResistances {
+            id: next_id,
+            values: data.resistances.clone(),
+        });
+
+        let next_id = self.gem.next_pid();
+        self.gem.rewards.insert(enemy_id, GPReward {
+            id: next_id,
+            xp: data.xp_per_level.saturating_mul(level),
+            items: data.drops.clone(),
+        });
+
+        let next_id = self.gem.next_pid();
+        self.gem.targets.insert(enemy_id, GPTarget {
+            id: (next_id),
+            target_entity: (None)
+        });
+
+        let next_id = self.gem.next_pid();
+        self.gem.buffbars.insert(enemy_id, GPBuffBar {
+            id: next_id,
+            buffs: Vec::new(),
+        });
+
+        let next_id = self.gem.next_pid();
+        self.gem.debuffbars.insert(enemy_id, GPDebuffBar {
+            id: next_id,
+            debuffs: Vec::new(),
+        });
+
+        let next_id = self.gem.next_pid();
+        self.gem.actionqueue.insert(enemy_id, GPActionQueue {
+            id: next_id,
+            queue: Vec::new(),
+            queue_window: SPELL_QUEUE_WINDOW_MS,
+        });
+
+        let next_id = self.gem.next_pid();
+        self.gem.cooldowns.insert(enemy_id, GPCooldowns {
+            id: next_id,
+            global: GPCooldown::ready(),
+            spells: HashMap::new(),
+        });
+
+        let mut abilities = data.abilities.clone();
+        abilities.sort_by_key(|ability| std::cmp::Reverse(ability.priority));
+        let next_id = self.gem.next_pid();
+        self.gem.behaviors.insert(enemy_id, GPBehavior {
+            id: next_id,
+            abilities,
+        });
+
+        enemy_id
+    }
+
+    // ------------------------------------------------------------ debuffs and damage
+
+    // DoTs deal their damage in ticks every tick_interval, counted in whole milliseconds so the
+    // result doesn't depend on the frame rate. the time since the last tick is dealt as a final
+    // partial tick when the debuff runs out. returns the player's debuffs that ran out, by target.
+    pub fn tick_debuffs(&mut self) -> Vec<(u32, String)> {
+        let mut ticks: Vec<(u32, GPDebuff, f32)> = Vec::new(); // target, debuff, fraction of a full tick
+        let mut expired: Vec<(u32, String)> = Vec::new();
+        let mut afflicted: Vec<u32> = self.gem.debuffbars.keys().copied().collect();
+        afflicted.sort();
+        for target in afflicted {
+            let Some(dbb) = self.gem.debuffbars.get_mut(&target) else {
+                continue;
+            };
+
+            for debuff in dbb.debuffs.iter_mut() {
+                let mut elapsed = self.delta_time_ms.min(debuff.time_left);
+                while elapsed > 0 {
+                    let step = elapsed.min(debuff.next_tick_in);
+                    elapsed -= step;
+                    debuff.time_left -= step;
+                    debuff.next_tick_in -= step;
+                    if debuff.next_tick_in == 0 {
+                        debuff.next_tick_in = debuff.tick_interval;
+                        ticks.push((target, debuff.clone(), 1.0));
+                    }
+                }
+
+                let since_last_tick = debuff.tick_interval - debuff.next_tick_in;
+                if debuff.time_left == 0 && since_last_tick > 0 {
+                    debuff.next_tick_in = debuff.tick_interval;
+                    ticks.push((target, debuff.clone(), since_last_tick as f32 / debuff.tick_interval as f32));
+                }
+            }
+
+            expired.extend(dbb.debuffs.iter()
+                .filter(|debuff| debuff.time_left == 0 && Some(debuff.caster) == self.gem.player_id)
+                .map(|debuff| (target, debuff.name.clone())));
+            dbb.debuffs.retain(|debuff| debuff.time_left > 0);
+        }
+
+        for (target, debuff, fraction) in ticks {
+            self.dot_tick(target, &debuff, fraction);
+        }
+        expired
+    }
+
+    // one tick of a DoT, using the caster's stats from when it was applied.
+    fn dot_tick(&mut self, target: u32, debuff: &GPDebuff, fraction: f32) {
+        let Some(spell) = self.spellbook.get_by_name(&debuff.name).cloned() else {
+            return;
+        };
+
+        // carry what doesn't round to a whole point over to the next tick of the same debuff.
+        let tick_damage = spell.tick_damage() * fraction + debuff.pending_damage;
+        let whole_damage = tick_damage.floor() as u32;
+        if let Some(live) = self.gem.debuffbars.get_mut(&target)
+            .and_then(|dbb| dbb.debuffs.iter_mut().find(|d| d.id == debuff.id)) {
+            live.pending_damage = tick_damage - whole_damage as f32;
+        }
+
+        self.damage_queue.push(Damage {
+            amt: (whole_damage),
+            target: (target),
+            damager: (debuff.caster),
+            spell: Some(spell.id.clone()),
+            school: spell.school,
+            periodic: true,
+            attacker_stats: debuff.caster_stats.clone(),
+            outcome: None });
+    }
+
+    // copies a contagious debuff from source to its living allies in range that don't have it yet.
+    pub fn spread_contagion(&mut self, source: u32, debuff: &GPDebuff) {
+        let Some(contagion) = self.spellbook.get_by_name(&debuff.name).and_then(|spell| spell.contagion.clone()) else {
+            return;
+        };
+        if debuff.time_left == 0 {
+            return;
+        }
+        let Some(source_pos) = self.gem.positions.get(&source).cloned() else {
+            return;
+        };
+
+        let Some(side) = self.gem.allegiances.get(&source).map(|a| a.allegiance.clone()) else {
+            return;
+        };
+
+        let mut candidates: Vec<u32> = self.gem.allegiances.iter()
+            .filter(|(_, a)| a.allegiance == side)
+            .map(|(&id, _)| id)
+            .filter(|&id| id != source && self.gem.is_alive(id))
+            .filter(|id| self.gem.positions.get(id)
+                .is_some_and(|pos| pos.distance_to(&source_pos) <= contagion.range as f32))
+            .filter(|id| self.gem.debuffbars.get(id)
+                .is_some_and(|dbb| !dbb.debuffs.iter().any(|d| d.name == debuff.name)))
+            .collect();
+        candidates.sort();
+
+        let mut spread_count = 0;
+        for target in candidates {
+            if spread_count >= contagion.max_spreads_per_tick {
+                break;
+            }
+            if !self.rng.gen_bool(contagion.chance as f64) {
+                continue;
+            }
+
+            let next_id = self.gem.next_pid();
+            if let Some(dbb) = self.gem.debuffbars.get_mut(&target) {
+                dbb.debuffs.push(GPDebuff {
+                    id: next_id,
+                    pending_damage: 0.0,
+                    next_tick_in: debuff.tick_interval,
+                    ..debuff.clone()
+                });
+                spread_count += 1;
+            }
+        }
+    }
+
+    // runs every damage event through the registered stages of the damage pipeline.
+    pub fn run_damage_queue(&mut self) {
+        for damage_event in std::mem::take(&mut *self.damage_queue) {
+            if damage_event.amt == 0 {
+                continue;
+            }
+            let live_stats = self.gem.stats.get(&damage_event.damager).cloned();
+            let mut ctx = DamageContext::new(damage_event, live_stats);
+            for modifier in self.damage_pipeline.modifiers() {
+                modifier(self, &mut ctx);
+            }
+        }
+    }
+
+    // applies damage and healing, healing past health_max is returned as overheal next to the
+    // change.
+    pub fn apply_health_changes(&mut self) -> Vec<(HealthChange, u32)> {
+        let mut applied = Vec::new();
+        for change in std::mem::take(&mut *self.health_changes) {
+            if change.amount > 0 && !self.gem.is_alive(change.target) {
+                continue;
+            }
+            let Some(stats) = self.gem.stats.get_mut(&change.target) else {
+                continue;
+            };
+
+            let mut overheal = 0;
+            if change.amount < 0 {
+                stats.health_curr = stats.health_curr.saturating_sub(change.amount.unsigned_abs());
+            } else {
+                let missing = stats.health_max.saturating_sub(stats.health_curr);
+                let healed = (change.amount as u32).min(missing);
+                overheal = change.amount as u32 - healed;
+                stats.health_curr += healed;
+            }
+            applied.push((change, overheal));
+        }
+        applied
+    }
+
+    // immortal entities, like the training dummy, are back at full health instead of dying.
+    pub fn refill_immortals(&mut self) {
+        for (entity_id, stats) in self.gem.stats.iter_mut() {
+            if stats.health_curr == 0 && self.gem.mortalities.get(entity_id).is_some_and(|m| m.immortal) {
+                stats.health_curr = stats.health_max;
+            }
+        }
+    }
+
+    pub fn tick_resources(&mut self) {
+        let dt_sec = self.delta_time_ms as f32 / 1000.0;
+        for (entity_id, resource) in self.gem.resources.iter_mut() {
+            // max and regen follow will, so points spent on will apply immediately.
+            if let Some(stats) = self.gem.stats.get(entity_id) {
+                resource.max = GPResource::max_from_will(stats.will);
+                resource.regen_per_sec = GPResource::regen_from_will(stats.will);
+            }
+
+            resource.pending_regen += resource.regen_per_sec as f32 * dt_sec;
+            let whole_regen = resource.pending_regen.floor() as u32;
+            resource.pending_regen -= whole_regen as f32;
+            resource.curr = (resource.curr + whole_regen).min(resource.max);
+        }
+    }
+
+    pub fn tick_cooldowns(&mut self) {
+        for cooldowns in self.gem.cooldowns.values_mut() {
+            cooldowns.global.remaining = cooldowns.global.remaining.saturating_sub(self.delta_time_ms);
+            for cd in cooldowns.spells.values_mut() {
+                cd.remaining = cd.remaining.saturating_sub(self.delta_time_ms);
+            }
+        }
+    }
+
+    // ------------------------------------------------------------ casting
+
+    // puts sp at target on the caster's queue and starts the global cooldown, the cast itself
+    // only progresses once it reaches the front of the queue. true if it is there right away.
+    pub fn queue_cast(&mut self, caster: u32, sp: Spells, target: u32) -> bool {
+        let Some(cast_time) = self.spellbook.get(&sp).map(|spell| spell.cast_time) else {
+            return false;
+        };
+
+        let next_id = self.gem.next_pid();
+        let a = GPAction {
+            id: next_id,
+            action: Actions::CastingSpell,
+            action_tag: sp.tag().to_string(),
+            time_action_takes: cast_time,
+            time_remaining: cast_time,
+            spell: Some(sp.clone()),
+            target: Some(target),
+        };
+
+        if let Some(cooldowns) = self.gem.cooldowns.get_mut(&caster) {
+            cooldowns.global.start(GLOBAL_COOLDOWN_MS);
+        }
+
+        self.gem.actions.insert(caster, a.clone());
+        let Some(queue) = self.gem.actionqueue.get_mut(&caster) else {
+            return false;
+        };
+        queue.queue.push(a);
+        queue.queue.len() == 1
+    }
+
+    // queues sp for the player, or says why it can't be cast right now. true if it starts
+    // casting right away.
+    pub fn try_queue_spell_cast(&mut self, sp: Spells) -> Result<bool, CastRejected> {
+        let Some(spell) = self.spellbook.get(&sp).cloned() else {
+            return Err(CastRejected::new(format!("{:?} is not in the spellbook", sp)));
+        };
+
+        let Some(player_id) = self.gem.player_id else {
+            return Err(CastRejected::new("There is no player"));
+        };
+        if !self.gem.is_alive(player_id) {
+            return Err(CastRejected::new("You are dead"));
+        }
+
+        if let Some(cooldowns) = self.gem.cooldowns.get(&player_id) {
+            let already_queued = self.gem.actionqueue.get(&player_id)
+                .is_some_and(|q| q.queue.iter().any(|a| a.spell.as_ref() == Some(&sp)));
+            if cooldowns.global.remaining > 0 {
+                return Err(CastRejected::new("Global cooldown"));
+            }
+            if cooldowns.remaining(&sp) > 0 || (spell.cooldown > 0 && already_queued) {
+                return Err(CastRejected::new(format!("{} is on cooldown", spell.name)));
+            }
+        }
+
+        if self.gem.actionqueue.get(&player_id).is_some_and(|q| !q.can_queue()) {
+            return Err(CastRejected::new("Already casting"));
+        }
+
+        // spells are paid for when they finish, so already queued casts count against the pool.
+        let queued_cost: u32 = self.gem.actionqueue.get(&player_id)
+            .map(|q| q.queue.iter()
+                .filter_map(|a| a.spell.as_ref())
+                .filter_map(|queued| self.spellbook.get(queued))
+                .map(|queued| queued.cost)
+                .sum())
+            .unwrap_or(0);
+        if let Some(resource) = self.gem.resources.get(&player_id) && resource.curr < queued_cost + spell.cost {
+            return Err(CastRejected {
+                message: format!("Not enough {}", resource.name),
+                missing_resource: true,
+            });
+        }
+
+        // spells go to the player's target; with no living target, pick the nearest enemy.
+        let target = match self.player_target().filter(|&t| self.gem.is_alive(t)) {
+            Some(t) => t,
+            None => {
+                let Some(nearest) = self.gem.nearest_enemy(player_id) else {
+                    return Err(CastRejected::new("No target"));
+                };
+                self.set_player_target(Some(nearest));
+                nearest
+            }
+        };
+
+        Ok(self.queue_cast(player_id, sp, target))
+    }
+
+    // counts down the cast at the front of every queue, and finishes the ones that are done.
+    pub fn advance_casts(&mut self, dt_ms: u32) -> Vec<FinishedCast> {
+        let mut finished = Vec::new();
+        let mut casters: Vec<u32> = self.gem.actionqueue.keys().copied().collect();
+        casters.sort();
+        for caster in casters {
+            let alive = self.gem.is_alive(caster);
+            let mut finished_action: Option<GPAction> = None;
+            if let Some(queue) = self.gem.actionqueue.get_mut(&caster) {
+                // the dead don't finish their casts.
+                if !alive {
+                    queue.queue.clear();
+                }
+                if let Some(current_action) = queue.queue.first_mut() {
+                    current_action.time_remaining =
+                        current_action.time_remaining.saturating_sub(dt_ms);
+
+                    if current_action.time_remaining == 0 {
+                        finished_action = Some(queue.queue.remove(0));
+                    }
+                }
+            }
+            let Some(action) = finished_action else {
+                continue;
+            };
+            let (spell, target) = (action.spell.clone(), action.target);
+            let result = self.finish_cast(caster, action);
+            finished.push(FinishedCast { caster, spell, target, result });
+        }
+        finished
+    }
+
+    // the cast bar has filled; pay for the spell and apply it to the target locked in at queue time.
+    fn finish_cast(&mut self, caster: u32, action: GPAction) -> CastResult {
+        let Some(sp) = action.spell.clone() else {
+            return CastResult::Success;
+        };
+        let Some(target) = action.target else {
+            return CastResult::NoTarget;
+        };
+        if !self.gem.is_alive(target) {
+            return CastResult::TargetDied;
+        }
+
+        let (cost, cooldown) = self.spellbook.get(&sp)
+            .map(|spell| (spell.cost, spell.cooldown))
+            .unwrap_or((0, 0));
+
+        if let Some(resource) = self.gem.resources.get_mut(&caster) {
+            if resource.curr < cost {
+                return CastResult::NotEnoughResource(resource.name.clone());
+            }
+            resource.curr -= cost;
+        }
+
+        if let Some(cooldowns) = self.gem.cooldowns.get_mut(&caster) && cooldown > 0 {
+            cooldowns.spells.entry(sp.clone()).or_insert_with(GPCooldown::ready).start(cooldown);
+        }
+
+        self.apply_spell(caster, sp, target);
+        CastResult::Success
+    }
+
+    // stops the casts queued this tick, returns the interrupts that actually stopped something.
+    pub fn apply_interrupts(&mut self) -> Vec<Interrupt> {
+        let mut applied = Vec::new();
+        for interrupt in std::mem::take(&mut *self.interrupts) {
+            let Some(queue) = self.gem.actionqueue.get_mut(&interrupt.target) else {
+                continue;
+            };
+            if queue.queue.is_empty() {
+                continue;
+            }
+            queue.queue.clear();
+            applied.push(interrupt);
+        }
+        applied
+    }
+
+    // idle enemies start casting their highest priority usable ability at their target.
+    pub fn run_enemy_ai(&mut self) {
+        for enemy in self.gem.get_living_enemies() {
+            let idle = self.gem.actionqueue.get(&enemy).is_some_and(|q| q.queue.is_empty());
+            let Some(cooldowns) = self.gem.cooldowns.get(&enemy) else {
+                continue;
+            };
+            if !idle || cooldowns.global.remaining > 0 {
+                continue;
+            }
+            let Some(target) = self.gem.get_target(enemy).filter(|&t| self.gem.is_alive(t)) else {
+                continue;
+            };
+            let Some(behavior) = self.gem.behaviors.get(&enemy) else {
+                continue;
+            };
+
+            let health_pct = self.gem.stats.get(&enemy)
+                .map(|stats| stats.health_curr * 100 / stats.health_max.max(1))
+                .unwrap_or(100);
+            let resource = self.gem.resources.get(&enemy).map(|r| r.curr);
+
+            let choice = behavior.abilities.iter()
+                .filter(|ability| ability.below_health_pct.is_none_or(|pct| health_pct < pct))
+                .filter(|ability| cooldowns.remaining(&ability.spell) == 0)
+                .find(|ability| self.spellbook.get(&ability.spell)
+                    .is_some_and(|spell| resource.is_none_or(|curr| curr >= spell.cost)))
+                .map(|ability| ability.spell.clone());
+
+            if let Some(sp) = choice {
+                self.queue_cast(enemy, sp, target);
+            }
+        }
+    }
+
+    // the cast has finished on a living target: deal the upfront damage and apply the DoT.
+    pub fn apply_spell(&mut self, caster: u32, sp: Spells, target: u32) {
+        let Some(spell) = self.spellbook.get(&sp).cloned() else {
+            return;
+        };
+
+        // deal upfront damage
+        if spell.upfront_dam > 0 {
+            self.damage_queue.push(Damage {
+                amt: (spell.upfront_dam),
+                target: (target),
+                damager: (caster),
+                spell: Some(sp.clone()),
+                school: spell.school,
+                periodic: false,
+                attacker_stats: None,
+                outcome: None });
+        }
+
+        // add debuff, snapshotting the caster's stats for its ticks
+        if spell.dps > 0 {
+            let caster_stats = self.gem.stats.get(&caster).cloned();
+            let tick_interval = match &caster_stats {
+                Some(stats) if spell.haste_affects_ticks => {
+                    (spell.tick_interval as f32 / stats.haste_multiplier()).round().max(1.0) as u32
+                }
+                _ => spell.tick_interval,
+            };
+            let next_id = self.gem.next_pid();
+            if let Some(dbb) = self.gem.debuffbars.get_mut(&target) {
+                dbb.debuffs.push(GPDebuff {
+                    id: (next_id),
+                    name: (sp.tag().to_string()),
+                    caster: (caster),
+                    total_duration: (spell.duration),
+                    time_left: (spell.duration),
+                    stacks: (1),
+                    pending_damage: 0.0,
+                    tick_interval,
+                    next_tick_in: tick_interval,
+                    caster_stats });
+            }
+        }
+    }
+
+    // names of the spells whose debuffs the player has on target, for uptimes.
+    pub fn player_debuffs_on(&self, target: u32) -> Vec<String> {
+        let mut debuffs: Vec<String> = self.gem.debuffbars.get(&target)
+            .map(|dbb| dbb.debuffs.iter()
+                .filter(|debuff| Some(debuff.caster) == self.gem.player_id)
+                .map(|debuff| self.spellbook.get_by_name(&debuff.name)
+                    .map(|spell| spell.name.clone())
+                    .unwrap_or_else(|| debuff.name.clone()))
+                .collect())
+            .unwrap_or_default();
+        debuffs.sort();
+        debuffs.dedup();
+        debuffs
+    }
+
+    // ------------------------------------------------------------ what scripts can see
+    // the hosts in scripting.rs and the simulator answer "event", cast() and print() themselves.
+
+    pub fn script_global(&self, name: &str) -> Result<Value, String> {
+        let unit = match name {
+            "player" => self.gem.player_id,
+            "target" => self.player_target(),
+            _ => return Err(format!("unknown name '{}'", name)),
+        };
+        Ok(unit.map(Value::Unit).unwrap_or(Value::Nil))
+    }
+
+    pub fn script_field(&self, unit: u32, name: &str) -> Result<Value, String> {
+        let Some(stats) = self.gem.stats.get(&unit) else {
+            return Err(format!("unit #{} no longer exists", unit));
+        };
+        let resource = self.gem.resources.get(&unit);
+        let value = match name {
+            "health" => Value::Number(stats.health_curr as f64),
+            "health_max" => Value::Number(stats.health_max as f64),
+            "health_pct" => Value::Number(stats.health_curr as f64 / stats.health_max.max(1) as f64),
+            "mana" => Value::Number(resource.map(|r| r.curr).unwrap_or(0) as f64),
+            "mana_max" => Value::Number(resource.map(|r| r.max).unwrap_or(0) as f64),
+            "alive" => Value::Bool(self.gem.is_alive(unit)),
+            "casting" => Value::Bool(self.gem.actionqueue.get(&unit).is_some_and(|q| !q.queue.is_empty())),
+            "gcd" => Value::Number(self.gem.cooldowns.get(&unit).map(|cd| cd.global.remaining).unwrap_or(0) as f64 / 1000.0),
+            "level" => Value::Number(self.gem.levels.get(&unit).map(|l| l.curr_level).unwrap_or(1) as f64),
+            "chaos" => Value::Number(stats.chaos as f64),
+            "solidity" => Value::Number(stats.solidity as f64),
+            "vitality" => Value::Number(stats.vitality as f64),
+            "haste" => Value::Number(stats.haste as f64),
+            "will" => Value::Number(stats.will as f64),
+            "volatility" => Value::Number(stats.volatility as f64),
+            _ => return Err(format!("unit has no field '{}'", name)),
+        };
+        Ok(value)
+    }
+
+    pub fn script_method(&self, unit: u32, name: &str, args: &[Value]) -> Result<Value, String> {
+        if name != "debuff" {
+            return Err(format!("unit has no method '{}'", name));
+        }
+        let sp = self.script_spell(args, name)?;
+        let caster = self.gem.player_id;
+        let debuff = self.gem.debuffbars.get(&unit)
+            .and_then(|dbb| dbb.debuffs.iter()
+                .find(|d| d.name == sp.tag() && Some(d.caster) == caster && d.time_left > 0));
+        let (remaining, stacks) = debuff
+            .map(|d| (d.time_left as f64 / 1000.0, d.stacks as f64))
+            .unwrap_or((0.0, 0.0));
+        Ok(Value::Record("debuff", Rc::new(vec![
+            ("remaining", Value::Number(remaining)),
+            ("stacks", Value::Number(stacks)),
+            ("active", Value::Bool(debuff.is_some())),
+        ])))
+    }
+
+    pub fn script_call(&mut self, name: &str, args: &[Value]) -> Result<Value, String> {
+        match name {
+            "cooldown" => {
+                let sp = self.script_spell(args, name)?;
+                let remaining = self.gem.player_id
+                    .and_then(|pid| self.gem.cooldowns.get(&pid))
+                    .map(|cd| cd.remaining(&sp))
+                    .unwrap_or(0);
+                Ok(Value::Number(remaining as f64 / 1000.0))
+            }
+            "enemy_count" => Ok(Value::Number(self.gem.get_living_enemies().len() as f64)),
+            "nearest_enemy" => {
+                let nearest = self.gem.player_id.and_then(|pid| self.gem.nearest_enemy(pid));
+                Ok(nearest.map(Value::Unit).unwrap_or(Value::Nil))
+            }
+            "weakest_enemy" => Ok(self.gem.lowest_health_enemy().map(Value::Unit).unwrap_or(Value::Nil)),
+            "next_enemy" => Ok(self.gem.next_enemy_after(self.player_target()).map(Value::Unit).unwrap_or(Value::Nil)),
+            "set_target" => {
+                let unit = match args.first() {
+                    Some(Value::Unit(id)) => *id,
+                    Some(Value::Nil) | None => return Ok(Value::Bool(false)),
+                    Some(other) => return Err(format!("set_target() needs a unit, not {}", other.type_name())),
+                };
+                let valid = self.gem.get_living_enemies().contains(&unit);
+                if valid {
+                    self.set_player_target(Some(unit));
+                }
+                Ok(Value::Bool(valid))
+            }
+            _ => Err(format!("unknown function '{}'", name)),
+        }
+    }
+
+    pub fn script_spell(&self, args: &[Value], function: &str) -> Result<Spells, String> {
+        let Some(Value::Str(name)) = args.first() else {
+            return Err(format!("{}() needs a spell name like \"miasma\"", function));
+        };
+        self.spellbook.get_by_name(name)
+            .map(|spell| spell.id.clone())
+            .ok_or_else(|| format!("unknown spell \"{}\"", name))
+    }
+}
+
+// how a unit is shown in the script debugger.
+pub fn script_unit_name(gem: &GameEntityManager, unit: u32) -> String {
+    if Some(unit) == gem.player_id {
+        return "player".to_string();
+    }
+    match gem.get_name(unit) {
+        name if name.is_empty() => format!("unit #{}", unit),
+        name => name,
+    }
+}
+
+// the handlers of program that run for something that just happened, each with the event record
+// it is run with. spell is the spell's tag.
+pub fn script_event_handlers(program: &Program, spellbook: &Spellbook, event: ScriptEvent, spell: Option<&str>, unit: Option<u32>) -> Vec<(usize, Value)> {
+    let record = Value::Record("event", Rc::new(vec![
+        ("spell", spell.map(|tag| Value::Str(tag.to_string())).unwrap_or(Value::Nil)),
+        ("unit", unit.map(Value::Unit).unwrap_or(Value::Nil)),
+    ]));
+    program.handlers.iter()
+        .filter(|handler| handler.event == event)
+        .filter(|handler| match &handler.filter {
+            Value::Str(name) => spellbook.get_by_name(name).map(|s| s.id.tag()) == spell,
+            _ => true,
+        })
+        .map(|handler| (handler.start, record.clone()))
+        .collect()
+}
+
+// the on_health_below handlers of program whose line the player's health crossed since last tick.
+pub fn script_health_handlers(program: &Program, last_pct: f64, health_pct: f64, player: Option<u32>) -> Vec<(usize, Value)> {
+    program.handlers.iter()
+        .filter(|handler| handler.event == ScriptEvent::HealthBelow)
+        .filter(|handler| matches!(handler.filter, Value::Number(threshold) if health_pct < threshold && last_pct >= threshold))
+        .map(|handler| (handler.start, Value::Record("event", Rc::new(vec![
+            ("spell", Value::Nil),
+            ("unit", player.map(Value::Unit).unwrap_or(Value::Nil)),
+        ]))))
+        .collect()
+}
@@ -0,0 +1,172 @@
+use std::collections::{BTreeMap, VecDeque};
+use crate::g_properties::DamageSchool;
+use crate::combat::HitOutcome;
+
+pub static COMBAT_LOG_CAPACITY: usize = 200;
+pub static CURRENT_DPS_WINDOW_MS: u32 = 5000;
+
+// one resolved hit or heal, names are resolved when it is logged so entries outlive their entities.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CombatLogEntry {
+    pub time_ms: u32,
+    pub source: String,
+    pub target: String,
+    pub ability: String,
+    pub school: Option<DamageSchool>,
+    pub outcome: HitOutcome,
+    pub amount: i32, // negative for damage
+    pub mitigated: u32,
+    pub overheal: u32,
+}
+
+impl CombatLogEntry {
+    pub fn describe(&self) -> String {
+        let head = format!("{} {} -> {}", self.source, self.ability, self.target);
+        let mut line = match self.outcome {
+            HitOutcome::Miss => return format!("{}: miss", head),
+            HitOutcome::Immune => return format!("{}: immune", head),
+            _ if self.amount >= 0 => format!("{}: {:+}", head, self.amount),
+            _ => format!("{}: {} {}", head, self.amount, self.school.map(|school| school.name()).unwrap_or("")),
+        };
+        match self.outcome {
+            HitOutcome::Critical => line.push_str(" crit"),
+            HitOutcome::PartialResist => line.push_str(" partially resisted"),
+            _ => {}
+        }
+        if self.mitigated > 0 {
+            line.push_str(&format!(" ({} mitigated)", self.mitigated));
+        }
+        if self.overheal > 0 {
+            line.push_str(&format!(" ({} overheal)", self.overheal));
+        }
+        line
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CombatLog {
+    pub entries: VecDeque<CombatLogEntry>,
+}
+
+impl CombatLog {
+    pub fn new() -> Self {
+        CombatLog {
+            entries: VecDeque::new(),
+        }
+    }
+
+    pub fn push(&mut self, entry: CombatLogEntry) {
+        if self.entries.len() >= COMBAT_LOG_CAPACITY {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(entry);
+    }
+
+    // newest last.
+    pub fn recent(&self, count: usize) -> impl Iterator<Item = &CombatLogEntry> {
+        self.entries.iter().skip(self.entries.len().saturating_sub(count))
+    }
+}
+
+// running totals for the player over the current encounter, shown on the defeat screen.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CombatStats {
+    pub started_ms: u32,
+    pub damage_dealt: u32,
+    pub damage_taken: u32,
+    pub healing_received: u32,
+    pub overhealing: u32,
+    pub kills: u32,
+}
+
+impl CombatStats {
+    pub fn new(started_ms: u32) -> Self {
+        CombatStats {
+            started_ms,
+            ..Default::default()
+        }
+    }
+
+    pub fn elapsed_secs(&self, now_ms: u32) -> f32 {
+        now_ms.saturating_sub(self.started_ms) as f32 / 1000.0
+    }
+
+    pub fn dps(&self, now_ms: u32) -> f32 {
+        self.damage_dealt as f32 / self.elapsed_secs(now_ms).max(1.0)
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SpellDamage {
+    pub damage: u32,
+    pub hits: u32,
+    pub crits: u32,
+}
+
+// what the player has done to a target, for the training dummy and the simulator.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TrainingStats {
+    pub started_ms: Option<u32>, // the first hit or debuff, standing around before that doesn't count
+    pub damage: u32,
+    pub spells: BTreeMap<String, SpellDamage>, // by spell name
+    pub uptime_ms: BTreeMap<String, u32>,     // how long each of the player's debuffs was up
+    recent: VecDeque<(u32, u32)>,             // time and damage of the hits in the current dps window
+}
+
+impl TrainingStats {
+    fn start(&mut self, now_ms: u32) {
+        self.started_ms.get_or_insert(now_ms);
+    }
+
+    pub fn elapsed_ms(&self, now_ms: u32) -> u32 {
+        self.started_ms.map(|started| now_ms.saturating_sub(started)).unwrap_or(0)
+    }
+
+    pub fn record(&mut self, now_ms: u32, spell: &str, amount: u32, crit: bool) {
+        self.start(now_ms);
+        self.damage += amount;
+        let entry = self.spells.entry(spell.to_string()).or_default();
+        entry.damage += amount;
+        entry.hits += 1;
+        if crit {
+            entry.crits += 1;
+        }
+
+        self.recent.push_back((now_ms, amount));
+        while self.recent.front().is_some_and(|(at, _)| now_ms.saturating_sub(*at) > CURRENT_DPS_WINDOW_MS) {
+            self.recent.pop_front();
+        }
+    }
+
+    // over the last CURRENT_DPS_WINDOW_MS, or since the start if that was more recent.
+    pub fn current_dps(&self, now_ms: u32) -> f32 {
+        let window = self.elapsed_ms(now_ms).min(CURRENT_DPS_WINDOW_MS).max(1000);
+        let damage: u32 = self.recent.iter()
+            .filter(|(at, _)| now_ms.saturating_sub(*at) <= CURRENT_DPS_WINDOW_MS)
+            .map(|(_, amount)| amount)
+            .sum();
+        damage as f32 * 1000.0 / window as f32
+    }
+
+    pub fn average_dps(&self, now_ms: u32) -> f32 {
+        self.damage as f32 * 1000.0 / self.elapsed_ms(now_ms).max(1000) as f32
+    }
+
+    // adds dt_ms to each debuff that is up, nothing counts until the first hit or debuff.
+    pub fn track_uptime(&mut self, now_ms: u32, dt_ms: u32, debuffs: Vec<String>) {
+        if self.started_ms.is_none() {
+            if debuffs.is_empty() {
+                return;
+            }
+            self.start(now_ms);
+        }
+        for debuff in debuffs {
+            *self.uptime_ms.entry(debuff).or_default() += dt_ms;
+        }
+    }
+
+    pub fn uptime_pct(&self, debuff: &str, now_ms: u32) -> f32 {
+        let up = self.uptime_ms.get(debuff).copied().unwrap_or(0);
+        (up as f32 * 100.0 / self.elapsed_ms(now_ms).max(1) as f32).min(100.0)
+    }
+}
@@ -1,12 +1,5 @@
-use crate::g_properties::Allegiances;
-use crate::g_properties::GPActionQueue;
-use crate::g_properties::GPAllegiance;
-use crate::g_properties::GPBuffBar;
-use crate::g_properties::GPDebuff;
-use crate::g_properties::GPLevel;
-use crate::g_properties::GPMortality;
-use crate::g_properties::GPStats;
-use crate::g_properties::GPTarget;
+use crate::animation::AnimatedSprite;
+use crate::bestiary::{EnemyData, SpawnData};
 use crate::game::*;
 use crate::properties::*;
 use std::fs;
@@ -14,128 +7,83 @@ use std::fs;
 impl Game<'_> {
     pub fn init_game(&mut self) {
         self.create_player();
-        self.create_enemy();
+        self.load_character(); // branch to save.rs
+        self.init_encounter();
     }
 
-    fn create_player(&mut self) {
-        let pgid = self.gem.add_entity(Some("player".to_string()));
-
-        let next_id = self.gem.next_pid();
-        self.gem.mortalities.insert(pgid, GPMortality {
-            id: next_id,
-            is_alive: true,});
-
-        let next_id = self.gem.next_pid();
-        self.gem.allegiances.insert(pgid, GPAllegiance {
-            id: next_id,
-            allegiance: Allegiances::Player,});
-
-        let next_id = self.gem.next_pid();
-        self.gem.stats.insert(pgid, GPStats {
-            id: next_id,
-            health_max: 100,
-            health_curr: 80,
-            chaos: 1,
-            solidity: 1,
-            vitality: 1,
-            haste: 1,
-            will: 1,
-            volatility: 1,});
-
-        let next_id = self.gem.next_pid();
-        self.gem.targets.insert(pgid, GPTarget { 
-            id: (next_id), 
-            target_entity: (None)});
+    fn init_encounter(&mut self) {
+        self.spawn_encounter(0);
+    }
 
-        let next_id = self.gem.next_pid();
-        self.gem.buffbars.insert(pgid, GPBuffBar {
-            id: next_id,
-            buffs: Vec::new(),});
+    // spawns every enemy of an encounter from the bestiary, all of them going after the player.
+    pub fn spawn_encounter(&mut self, index: usize) {
+        let Some(encounter) = self.bestiary.encounters.get(index).cloned() else {
+            println!("No encounter {}", index);
+            return;
+        };
+        self.encounter_index = index;
+        self.combat_stats = crate::combat_log::CombatStats::new(self.time_elapsed_ms);
+        println!("Encounter: {}", encounter.name);
+        self.spawn_enemies(&encounter.spawns);
+    }
 
-        let next_id = self.gem.next_pid();
-        self.gem.debuffbars.insert(pgid, crate::g_properties::GPDebuffBar {
-            id: next_id,
-            debuffs: Vec::new(),});
+    // returns the tag and entity of everything spawned, challenges track their enemies by tag.
+    pub fn spawn_enemies(&mut self, spawns: &[SpawnData]) -> Vec<(String, u32)> {
+        let player_id = self.gem.player_id.unwrap();
+        let mut spawned = Vec::new();
+
+        for spawn in spawns {
+            let Some(data) = self.bestiary.get(&spawn.enemy).cloned() else {
+                continue;
+            };
+            let enemy_id = self.create_enemy(&spawn.tag, &data, spawn.level, spawn.position, spawn.size);
+            if let Some(target) = self.gem.targets.get_mut(&enemy_id) {
+                target.target_entity = Some(player_id);
+            }
+            spawned.push((spawn.tag.clone(), enemy_id));
+        }
 
-        let next_id = self.gem.next_pid();
-        self.gem.actionqueue.insert(pgid, GPActionQueue {
-            id: next_id,
-            queue: Vec::new(),});
+        let target = self.gem.nearest_enemy(player_id);
+        self.set_player_target(target);
+        spawned
+    }
 
-        self.gem.player_id = Some(pgid);
+    fn create_player(&mut self) {
+        let data = self.player_data.clone();
+        let pgid = self.combat().spawn_player(&data);
         if let Some(em_player_id) = self.em.get_player_id() {
             self.em_gem_link.insert(em_player_id, pgid);
         }
-
-        let next_id = self.gem.next_pid();
-        self.gem.levels.insert(pgid, crate::g_properties::GPLevel { 
-            id: next_id,
-            curr_level: (5),
-            curr_xp: (50),
-            next_level_xp: (100) });
     }
 
-    fn create_enemy(&mut self) {
-        let enemy_id = self.gem.add_entity(Some("alpine_terror".to_string()));
-        self.gem.texture_to_entity.insert("Alpe".to_string(), enemy_id);
-        let next_id = self.gem.next_pid();
-        self.gem.mortalities.insert(enemy_id, GPMortality {
-            id: next_id,
-            is_alive: true,
-        });
-
-        let level = 2;
-        let next_id = self.gem.next_pid();
-        self.gem.levels.insert(next_id, GPLevel {
-            id: next_id,
-            curr_level: level,
-            curr_xp: 0,
-            next_level_xp: 100,
+    // position is the top left of the sprite in unscaled coordinates, size its width and height.
+    fn create_enemy(&mut self, tag: &str, data: &EnemyData, level: u32, position: (u32, u32), size: u32) -> u32 {
+        let scale = get_scale();
+        let s = |x: u32| scaled(scale, x);
+
+        let center = (position.0 + size / 2, position.1 + size / 2);
+        let enemy_id = self.combat().spawn_enemy(tag, data, level, center);
+
+        self.anims.add_animation_instance(AnimatedSprite {
+            texture_id: data.texture.clone(),
+            frame_width: 64,
+            frame_height: 64,
+            total_frames: 1,
+            current_frame: 0,
+            frame_time: None,
+            time_accumulator: 0.0,
+            position: (s(position.0), s(position.1)),
+            inanimate: true,
+            strata: 10,
+            desired_width: Some(s(size)),
+            desired_height: Some(s(size)),
+            play_once: false,
+            finished: true,
+            velocity: (0.0, 0.0),
+            lifetime: None,
+            associated_g_entity: Some(enemy_id),
         });
 
-        let next_id = self.gem.next_pid();
-        self.gem.allegiances.insert(enemy_id, GPAllegiance {
-            id: next_id,
-            allegiance: Allegiances::Enemy,
-        });
-
-        let next_id = self.gem.next_pid();
-        self.gem.stats.insert(enemy_id, GPStats {
-            id: next_id,
-            chaos: 1 * level,
-            solidity: 2 * level,
-            vitality: 2 * level,
-            haste: 1 * level,
-            will: 1 * level,
-            volatility: 1 * level,
-            health_max: 100 * (2 * level),
-            health_curr: 100 * (2 * level),
-        });
-
-        let next_id = self.gem.next_pid();
-        self.gem.targets.insert(enemy_id, GPTarget { 
-            id: (next_id), 
-            target_entity: (None)
-        });
-
-        let next_id = self.gem.next_pid();
-        self.gem.buffbars.insert(enemy_id, GPBuffBar {
-            id: next_id,
-            buffs: Vec::new(),
-        });
-
-        let next_id = self.gem.next_pid();
-        self.gem.debuffbars.insert(enemy_id, crate::g_properties::GPDebuffBar {
-            id: next_id,
-            debuffs: Vec::new(),
-        });
-
-        let next_id = self.gem.next_pid();
-        self.gem.actionqueue.insert(enemy_id, GPActionQueue {
-            id: next_id,
-            queue: Vec::new(),
-        });
-
-
+        enemy_id
     }
 }
\ No newline at end of file
@@ -1,4 +1,5 @@
 use crate::animation::AnimatedSprite;
+use crate::g_properties::Spells;
 use crate::game::*;
 use crate::properties::*;
 use std::fs;
@@ -8,6 +9,9 @@ impl Game<'_> {
         self.init_game();
         self.fnt.set_smooth(true);
         self.create_run_button();
+        self.create_script_status();
+        self.create_code_editor();
+        self.init_script_library(); // branch to script_library.rs
         self.create_side_buttons();
         self.spawn_player();
         self.anims.load_textures("./src/assets/sprites");
@@ -51,7 +55,7 @@ impl Game<'_> {
 
         if let Some(tt) = self.em.get_tooltip_data_mut(button_eid) {
             tt.header = "Run Code Button".to_string();
-            tt.body = "Runs the code that is currently placed in the text editor section. Be careful! Arbitrary code execution can be dangerous.".to_string();
+            tt.body = "Runs the code that is currently placed in the text editor section every tick, press again to stop it. Click a line number (or press F9) to set a breakpoint where the game pauses for inspection. Be careful! Arbitrary code execution can be dangerous.".to_string();
             tt.x = s(10);
             tt.y = s(50);
             tt.width = s(200);
@@ -66,6 +70,52 @@ impl Game<'_> {
         }
     }
 
+    // the script editor fills the encapsulation region under the run button.
+    fn create_code_editor(&mut self) {
+        let scale = get_scale();
+        let s = |x: u32| x * scale;
+
+        let eid = self.em.add_entity(Some(crate::code_editor::EDITOR_TAG.to_string()));
+        self.em.add_property_to_entity(PropertiesEnum::Rect, eid);
+        self.em.add_property_to_entity(PropertiesEnum::CodeEditor, eid);
+        if let Some(rect) = self.em.get_prects_mut(eid).and_then(|rects| rects.last_mut()) {
+            rect.x = s(10);
+            rect.y = s(120);
+            rect.width = s(610);
+            rect.height = s(750);
+            rect.colors = ColorPair::from_colors(ENCAPSULATION_REGIONS, Some(sfml::graphics::Color::BLACK));
+            rect.draw = true;
+            rect.strata = 5;
+        }
+
+        if let Some(editor) = self.em.get_pcode_editor_mut(eid) {
+            editor.x = s(10);
+            editor.y = s(120);
+            editor.width = s(610);
+            editor.height = s(750);
+            editor.draw = true;
+            editor.strata = 6;
+        }
+    }
+
+    // one or two lines under the text editor saying whether the script runs and what went wrong.
+    fn create_script_status(&mut self) {
+        let scale = get_scale();
+        let s = |x: u32| x * scale;
+
+        let eid = self.em.add_entity(Some("script_status".to_string()));
+        self.em.add_property_to_entity(PropertiesEnum::Text, eid);
+        if let Some(text) = self.em.get_ptexts_mut(eid).and_then(|texts| texts.get_mut(0)) {
+            text.text = "Script stopped".to_string();
+            text.x = s(15);
+            text.y = s(874);
+            text.scale = scale;
+            text.colors = ColorPair::from_colors(OFF_TEXT_CLR, Some(MAIN_OUTLINE_CLR));
+            text.draw = true;
+            text.strata = 15;
+        }
+    }
+
     fn create_side_buttons(&mut self) {
         let scale = get_scale();
         let s = |x: u32| x * scale;
@@ -139,14 +189,35 @@ impl Game<'_> {
                 }
             }
 
+            let spell = crate::button_definitions::spell_for_action(action)
+                .and_then(|sp| self.spellbook.get(&sp))
+                .cloned();
             if let Some(tt) = self.em.get_tooltip_data_mut(eid) {
-                tt.header = label.to_string();
-                tt.body = format!("{} button functionality.", label);
+                if let Some(spell) = spell {
+                    tt.header = spell.name;
+                    tt.body = format!("{} Costs {} mana.", spell.tooltip, spell.cost);
+                    tt.icon = Some(spell.icon);
+                } else if *action == ClickAction::D {
+                    tt.header = "Scripts".to_string();
+                    tt.body = "Open your script library to load, duplicate, rename, delete, export and import scripts.".to_string();
+                    tt.icon = None;
+                } else if *action == ClickAction::E {
+                    tt.header = "Challenges".to_string();
+                    tt.body = "Pit your script against fixed fights with objectives, and chase your best scores.".to_string();
+                    tt.icon = None;
+                } else if *action == ClickAction::F {
+                    tt.header = "Training".to_string();
+                    tt.body = "Test your rotation on a training dummy that can't die, and see your DPS, damage per spell and debuff uptimes.".to_string();
+                    tt.icon = None;
+                } else {
+                    tt.header = label.to_string();
+                    tt.body = format!("{} button functionality.", label);
+                    tt.icon = None;
+                }
                 tt.x = button_x;
                 tt.y = button_y;
                 tt.width = button_width;
                 tt.height = button_height;
-                tt.icon = None;
             }
 
             if let (Some(rect_id), Some(clickable)) = (
@@ -202,28 +273,6 @@ impl Game<'_> {
             associated_g_entity: None,
         });
 
-        // alpine terror sprite
-        self.anims.add_animation_instance(AnimatedSprite {
-            texture_id: "Alpe".to_string(),
-            frame_width: 64,
-            frame_height: 64,
-            total_frames: 1,
-            current_frame: 0,
-            frame_time: None,
-            time_accumulator: 0.0,
-            position: (s(1500) , s(207)),
-            inanimate: true,
-            strata: 10,
-            desired_width: Some(s(256)),
-            desired_height: Some(s(256)),
-            play_once: false,
-            finished: true,
-            velocity: (0.0, 0.0),
-            lifetime: None,
-            associated_g_entity: None,
-        });
-        
-
         // ground overlay sprite
         self.anims.add_animation_instance(AnimatedSprite {
             texture_id: "ground_overlay3".to_string(),
@@ -272,7 +321,7 @@ impl Game<'_> {
                 text.colors.outline = Some((0, 0, 0));
                 text.draw = true;
                 text.strata = 10;
-                text.text = "Alpine Terror".to_string();
+                text.text = "No target".to_string();
             }
         }
 
@@ -293,7 +342,7 @@ impl Game<'_> {
                 fill: (255, 100, 100),
                 outline: Some((0, 0, 0)),
             };
-            hb.gem_entity_id = Some(self.gem.get_entity_id_from_name("alpine_terror".to_string()));
+            hb.gem_entity_id = None; // follows the player's target, see update_target_frame
         }
 
         // tooltip region
@@ -312,6 +361,20 @@ impl Game<'_> {
             }
         }
 
+        // combat log region, filled in by render_combat_log
+        let combat_log_region = self.em.add_entity(Some("combat_log_region".to_string()));
+        self.em.add_property_to_entity(PropertiesEnum::Rect, combat_log_region);
+        if let Some(rects) = self.em.rectangles.get_mut(&combat_log_region) && let Some(rect) = rects.get_mut(0) {
+            rect.width = s(512);
+            rect.height = s(200);
+            rect.x = s(1920 - 512) - s(10);
+            rect.y = s(532 + 250);
+            rect.colors.fill = (29, 33, 37);
+            rect.colors.outline = Some((0, 0, 0));
+            rect.draw = true;
+            rect.strata = 10;
+        }
+
         self.anims.add_animation_instance(AnimatedSprite {
             texture_id: "tree_icon".to_string(),
             frame_width: 64,
@@ -361,26 +424,7 @@ impl Game<'_> {
 
         let player_id = self.em.add_entity(Some("player".to_string()));
 
-        // player sprite
-        self.anims.add_animation_instance(AnimatedSprite {
-            texture_id: "my_warlock".to_string(),
-            frame_width: 64,
-            frame_height: 64,
-            total_frames: 1,
-            current_frame: 0,
-            frame_time: None,
-            time_accumulator: 0.0,
-            position: (s(1000) , s(207)),
-            inanimate: true,
-            strata: 10,
-            desired_width: Some(s(256)),
-            desired_height: Some(s(256)),
-            play_once: true,
-            finished: false,
-            velocity: (0.0, 0.0),
-            lifetime: None,
-            associated_g_entity: None,
-        });
+        self.add_player_idle_sprite();
 
         // player info background encapsulation region
         self.em.add_property_to_entity(PropertiesEnum::Rect, player_id);
@@ -416,7 +460,7 @@ impl Game<'_> {
             hb.x = s(1920) - s(1047);
             hb.y = s(532 + 60);
             hb.width = s(502);
-            hb.height = s(50);
+            hb.height = s(34);
             hb.draw = true;
             hb.strata = 30;
             hb.base_colors = ColorPair {
@@ -430,6 +474,21 @@ impl Game<'_> {
             hb.gem_entity_id = Some(self.gem.get_entity_id_from_name("player".to_string()));
         }
 
+        // === RESOURCEBAR ===
+        self.em.add_property_to_entity(PropertiesEnum::Resourcebar, player_id);
+        if let Some(rb) = self.em.get_presourcebar_mut(player_id) {
+            rb.x = s(1920) - s(1047);
+            rb.y = s(532 + 98);
+            rb.width = s(502);
+            rb.height = s(16);
+            rb.draw = true;
+            rb.strata = 30;
+            rb.inner_colors = ColorPair::from_colors(MANA_COLOR, Some(sfml::graphics::Color::BLACK));
+            rb.gem_entity_id = Some(self.gem.get_entity_id_from_name("player".to_string()));
+        }
+
+        let miasma = self.spellbook.get(&Spells::Miasma).unwrap().clone();
+
         // === CASTBAR ===
         self.em.add_property_to_entity(PropertiesEnum::Castbar, player_id);
         if let Some(cb) = self.em.get_pcastbar_mut(player_id) {
@@ -448,25 +507,51 @@ impl Game<'_> {
                 fill: (100, 100, 255),
                 outline: Some((0, 0, 0)),
             };
-            cb.icon_name = "miasma".into();
+            cb.icon_name = miasma.icon.clone();
         }
 
         // === TOOLTIP ===
         self.em.add_property_to_entity(PropertiesEnum::TooltipData, player_id);
         if let Some(tt) = self.em.get_tooltip_data_mut(player_id) {
-            tt.header = "Miasma".into();
-            tt.body = "    A contagious metaphysical impurity. \nSpreads to any nearby enemies each time it \ndeals damage, haste does not affect its \ntickrate.".into();
+            tt.header = miasma.name;
+            tt.body = miasma.tooltip;
             tt.x = s(1920) - s(1047);
             tt.y = s(532 + 120);
             tt.width = s(502);
             tt.height = s(50);
-            tt.icon = Some("miasma".into());
+            tt.icon = Some(miasma.icon);
         }
 
         // === STATE ===
         self.em.add_property_to_entity(PropertiesEnum::State, player_id);
     }
 
+    pub fn add_player_idle_sprite(&mut self) {
+        let scale = get_scale();
+        let s = |x: u32| scaled(scale, x);
+
+        // player sprite
+        self.anims.add_animation_instance(AnimatedSprite {
+            texture_id: "my_warlock".to_string(),
+            frame_width: 64,
+            frame_height: 64,
+            total_frames: 1,
+            current_frame: 0,
+            frame_time: None,
+            time_accumulator: 0.0,
+            position: (s(1000) , s(207)),
+            inanimate: true,
+            strata: 10,
+            desired_width: Some(s(256)),
+            desired_height: Some(s(256)),
+            play_once: true,
+            finished: false,
+            velocity: (0.0, 0.0),
+            lifetime: None,
+            associated_g_entity: self.gem.player_id,
+        });
+    }
+
     fn load_textures(&mut self, folder_path: &str) {
         let entries = fs::read_dir(folder_path)
             .expect("Failed to read sprites folder");
@@ -495,4 +580,50 @@ impl Game<'_> {
             }
         }
     }
+
+    // a labelled button with a tooltip, as the side panels and overlays use. bounds are
+    // (x, y, width, height) already scaled, the label is drawn just above the button's strata.
+    pub fn create_panel_button(&mut self, tag: &str, label: &str, tooltip: &str, (x, y, width, height): (u32, u32, u32, u32), strata: u8, action: ClickAction) {
+        let scale = get_scale();
+        let s = |x: u32| scaled(scale, x);
+
+        let button_eid = self.em.create_button(Some(tag.to_string()));
+        let mut rect_id: Option<u32> = None;
+        if let Some(rect) = self.em.get_prects_mut(button_eid).and_then(|rects| rects.last_mut()) {
+            rect.x = x;
+            rect.y = y;
+            rect.width = width;
+            rect.height = height;
+            rect.colors = ColorPair::from_colors(BUTTON, Some(sfml::graphics::Color::BLACK));
+            rect.pressed_color = Some(ColorPair::from_colors(BUTTON_PRESSED, None));
+            rect.hovered_color = Some(ColorPair::from_colors(BUTTON_HOVERED, None));
+            rect.draw = true;
+            rect.hovered = Some(false);
+            rect.strata = strata;
+            rect_id = Some(rect.id);
+        }
+        if let Some(text) = self.em.get_ptexts_mut(button_eid).and_then(|texts| texts.last_mut()) {
+            text.text = label.to_string();
+            text.scale = scale;
+            text.x = x + s(10);
+            text.y = y;
+            text.colors = ColorPair::from_colors(MAIN_TEXT_CLR, Some(sfml::graphics::Color::BLACK));
+            text.draw = true;
+            text.strata = strata + 5;
+        }
+        if let Some(tt) = self.em.get_tooltip_data_mut(button_eid) {
+            tt.header = label.to_string();
+            tt.body = tooltip.to_string();
+            tt.x = x;
+            tt.y = y;
+            tt.width = width;
+            tt.height = height;
+            tt.icon = None;
+        }
+        if let (Some(id), Some(clickable)) = (rect_id, self.em.get_pclickable_mut(button_eid)) {
+            clickable.clickable = true;
+            clickable.action = action;
+            clickable.rect_reference_id = Some(id);
+        }
+    }
 }
@@ -0,0 +1,234 @@
+use rand::Rng;
+use crate::combat::{Combat, Damage, HealthChange, HitOutcome, Interrupt, InterruptReason, BASE_MISS_CHANCE};
+use crate::g_properties::{GPStats, MAX_RESISTANCE};
+
+// every damage event passes through these in order, see s_damage.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum DamageStage {
+    Base,        // the raw amount
+    Attacker,    // stats and buffs of whoever dealt it
+    Target,      // resists, shields and vulnerabilities of whoever takes it
+    Outcome,     // hit, crit, miss, ...
+    Application, // turns what's left into a health change
+    PostHit,     // leech, procs and other reactions to what landed
+}
+
+// a damage event on its way through the pipeline.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DamageContext {
+    pub damage: Damage,
+    pub attacker_stats: Option<GPStats>, // the DoT's snapshot, or the attacker's live stats
+    pub amount: f32,
+    pub mitigated: f32, // taken off by target modifiers
+    pub outcome: HitOutcome,
+    pub dealt: u32, // set by the application stage
+}
+
+impl DamageContext {
+    pub fn new(damage: Damage, live_stats: Option<GPStats>) -> Self {
+        let attacker_stats = damage.attacker_stats.clone().or(live_stats);
+        DamageContext {
+            damage,
+            attacker_stats,
+            amount: 0.0,
+            mitigated: 0.0,
+            outcome: HitOutcome::Hit,
+            dealt: 0,
+        }
+    }
+
+    // moves part of the amount into mitigated, e.g. for a shield or a resistance.
+    pub fn mitigate(&mut self, fraction: f32) {
+        let reduced = self.amount * fraction.clamp(0.0, 1.0);
+        self.amount -= reduced;
+        self.mitigated += reduced;
+    }
+}
+
+pub type DamageModifier = for<'c> fn(&mut Combat<'c>, &mut DamageContext);
+
+pub struct DamagePipeline {
+    modifiers: Vec<(DamageStage, &'static str, DamageModifier)>,
+}
+
+impl DamagePipeline {
+    pub fn new() -> Self {
+        DamagePipeline {
+            modifiers: Vec::new(),
+        }
+    }
+
+    pub fn with_builtins() -> Self {
+        let mut pipeline = DamagePipeline::new();
+        pipeline.register(DamageStage::Base, "base_amount", base_amount);
+        pipeline.register(DamageStage::Attacker, "spell_power", spell_power);
+        pipeline.register(DamageStage::Attacker, "execute", execute);
+        pipeline.register(DamageStage::Target, "resistance", resistance);
+        pipeline.register(DamageStage::Target, "solidity", solidity);
+        pipeline.register(DamageStage::Outcome, "outcome_roll", outcome_roll);
+        pipeline.register(DamageStage::Application, "apply_damage", apply_damage);
+        pipeline.register(DamageStage::PostHit, "leech", leech);
+        pipeline.register(DamageStage::PostHit, "contagion", contagion);
+        pipeline.register(DamageStage::PostHit, "interrupt", interrupt);
+        pipeline
+    }
+
+    // runs after everything already registered for the same stage.
+    pub fn register(&mut self, stage: DamageStage, name: &'static str, modifier: DamageModifier) {
+        let index = self.modifiers.iter()
+            .position(|(other, _, _)| *other > stage)
+            .unwrap_or(self.modifiers.len());
+        self.modifiers.insert(index, (stage, name, modifier));
+    }
+
+    // copied out so the modifiers can borrow the combat state mutably while they run.
+    pub fn modifiers(&self) -> Vec<DamageModifier> {
+        self.modifiers.iter().map(|(_, _, modifier)| *modifier).collect()
+    }
+}
+
+fn base_amount(_combat: &mut Combat, ctx: &mut DamageContext) {
+    ctx.amount = ctx.damage.amt as f32;
+}
+
+fn spell_power(_combat: &mut Combat, ctx: &mut DamageContext) {
+    if let Some(stats) = &ctx.attacker_stats {
+        ctx.amount *= stats.spell_power_multiplier();
+    }
+}
+
+// direct hits of execute spells grow with the target's missing health and with every DoT the
+// attacker already has ticking on it.
+fn execute(combat: &mut Combat, ctx: &mut DamageContext) {
+    if ctx.damage.periodic {
+        return;
+    }
+    let Some(execute) = ctx.damage.spell.as_ref()
+        .and_then(|sp| combat.spellbook.get(sp))
+        .and_then(|spell| spell.execute.clone()) else {
+        return;
+    };
+
+    let missing = combat.gem.stats.get(&ctx.damage.target)
+        .filter(|stats| stats.health_max > 0)
+        .map(|stats| 1.0 - stats.health_curr as f32 / stats.health_max as f32)
+        .unwrap_or(0.0);
+    let dots = combat.gem.debuffbars.get(&ctx.damage.target)
+        .map(|dbb| dbb.debuffs.iter()
+            .filter(|debuff| debuff.caster == ctx.damage.damager && debuff.time_left > 0)
+            .count())
+        .unwrap_or(0);
+
+    ctx.amount *= 1.0 + missing * execute.missing_health_bonus + dots as f32 * execute.per_dot_bonus;
+}
+
+fn resistance(combat: &mut Combat, ctx: &mut DamageContext) {
+    let resistance = combat.gem.resistances.get(&ctx.damage.target)
+        .map(|r| r.get(ctx.damage.school))
+        .unwrap_or(0);
+    ctx.mitigate(resistance as f32 / 100.0);
+}
+
+fn solidity(combat: &mut Combat, ctx: &mut DamageContext) {
+    if let Some(stats) = combat.gem.stats.get(&ctx.damage.target) {
+        ctx.mitigate(stats.solidity_mitigation());
+    }
+}
+
+// immune if fully resistant, direct hits can miss, then either a crit from the attacker's
+// volatility or a partial resist from the target's resistance to the school.
+fn outcome_roll(combat: &mut Combat, ctx: &mut DamageContext) {
+    let resistance = combat.gem.resistances.get(&ctx.damage.target)
+        .map(|r| r.get(ctx.damage.school))
+        .unwrap_or(0);
+    let crit_chance = ctx.attacker_stats.as_ref().map(|stats| stats.crit_chance()).unwrap_or(0.0);
+
+    ctx.outcome = if resistance >= MAX_RESISTANCE {
+        HitOutcome::Immune
+    } else if !ctx.damage.periodic && combat.rng.gen_bool(BASE_MISS_CHANCE) {
+        HitOutcome::Miss
+    } else if combat.rng.gen_bool(crit_chance as f64) {
+        HitOutcome::Critical
+    } else if combat.rng.gen_bool(resistance as f64 / 200.0) {
+        HitOutcome::PartialResist
+    } else {
+        HitOutcome::Hit
+    };
+
+    match ctx.outcome {
+        HitOutcome::Critical => {
+            ctx.amount *= ctx.attacker_stats.as_ref().map(|stats| stats.crit_multiplier()).unwrap_or(1.0);
+        }
+        HitOutcome::PartialResist => ctx.mitigate(0.5),
+        HitOutcome::Miss | HitOutcome::Immune => ctx.amount = 0.0,
+        HitOutcome::Hit => {}
+    }
+    ctx.damage.outcome = Some(ctx.outcome);
+}
+
+fn apply_damage(combat: &mut Combat, ctx: &mut DamageContext) {
+    ctx.dealt = ctx.amount.max(0.0).round() as u32;
+    combat.health_changes.push(HealthChange {
+        target: ctx.damage.target,
+        source: ctx.damage.damager,
+        amount: -(ctx.dealt as i32),
+        spell: ctx.damage.spell.clone(),
+        school: Some(ctx.damage.school),
+        outcome: ctx.outcome,
+        mitigated: ctx.mitigated.round() as u32,
+    });
+}
+
+// heals the attacker for part of what actually landed.
+fn leech(combat: &mut Combat, ctx: &mut DamageContext) {
+    let leech_pct = ctx.damage.spell.as_ref()
+        .and_then(|sp| combat.spellbook.get(sp))
+        .map(|spell| spell.leech_pct)
+        .unwrap_or(0);
+    let leeched = ctx.dealt * leech_pct / 100;
+    if leeched > 0 {
+        combat.health_changes.push(HealthChange {
+            target: ctx.damage.damager,
+            source: ctx.damage.damager,
+            amount: leeched as i32,
+            spell: ctx.damage.spell.clone(),
+            school: Some(ctx.damage.school),
+            outcome: HitOutcome::Hit,
+            mitigated: 0,
+        });
+    }
+}
+
+// a DoT tick that landed can jump to the target's allies, a missed or fully resisted one can't.
+fn contagion(combat: &mut Combat, ctx: &mut DamageContext) {
+    if !ctx.damage.periodic || ctx.dealt == 0 {
+        return;
+    }
+    let Some(tag) = ctx.damage.spell.as_ref()
+        .and_then(|sp| combat.spellbook.get(sp))
+        .filter(|spell| spell.contagion.is_some())
+        .map(|spell| spell.id.tag()) else {
+        return;
+    };
+    // the debuff that ticked, gone already if that was its last tick
+    let debuff = combat.gem.debuffbars.get(&ctx.damage.target)
+        .and_then(|dbb| dbb.debuffs.iter().find(|d| d.name == tag && d.caster == ctx.damage.damager))
+        .cloned();
+    if let Some(debuff) = debuff {
+        combat.spread_contagion(ctx.damage.target, &debuff);
+    }
+}
+
+// a direct hit of an interrupting spell stops whatever the target is casting, if it landed.
+fn interrupt(combat: &mut Combat, ctx: &mut DamageContext) {
+    if ctx.damage.periodic || ctx.dealt == 0 {
+        return;
+    }
+    if ctx.damage.spell.as_ref().and_then(|sp| combat.spellbook.get(sp)).is_some_and(|spell| spell.interrupts) {
+        combat.interrupts.push(Interrupt {
+            target: ctx.damage.target,
+            source: Some(ctx.damage.damager),
+            reason: InterruptReason::Interrupted,
+        });
+    }
+}
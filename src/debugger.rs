@@ -0,0 +1,125 @@
+use sfml::window::Key;
+use crate::game::*;
+use crate::properties::*;
+use crate::script_vm::{Exit, StopAt, Vm, SCRIPT_INSTRUCTION_BUDGET};
+
+pub static DEBUG_PANEL_TAG: &str = "debug_panel";
+
+impl Game<'_> {
+    // 1-based lines with a breakpoint, none once the editor's text is no longer what is running.
+    pub fn script_breakpoints(&self) -> Vec<u32> {
+        self.code_editor()
+            .filter(|editor| editor.revision == self.script.run_revision)
+            .map(|editor| editor.breakpoints.iter().map(|line| *line as u32 + 1).collect())
+            .unwrap_or_default()
+    }
+
+    // the line the stopped run will carry on from.
+    pub fn paused_line(&self) -> Option<u32> {
+        let program = self.script.program.as_ref()?;
+        self.script.suspended.as_ref().filter(|_| self.script.paused)?.line(program)
+    }
+
+    // stops the game before the statement vm is about to run. Game::run skips the systems
+    // while paused, so no game time passes until the player continues or steps.
+    pub fn pause_script(&mut self, vm: Vm) {
+        let line = self.script.program.as_ref().and_then(|program| vm.line(program)).unwrap_or(0);
+        self.script.suspended = Some(vm);
+        self.script.break_on_next = false;
+        if !self.script.paused {
+            self.script.paused = true;
+            self.show_debug_panel();
+        }
+        println!("Script paused on line {}", line);
+        self.set_script_status(&format!("Paused on line {}. F5 continues, F10 steps, Shift+F5 stops", line), WARNING_COLOR);
+
+        // bring the line into view without moving the caret
+        let Some(editor) = self.code_editor() else {
+            return;
+        };
+        let visible_lines = self.editor_metrics(editor).visible_lines;
+        if let Some(editor) = self.code_editor_id().and_then(|id| self.em.get_pcode_editor_mut(id)) {
+            let line = (line as usize).saturating_sub(1);
+            if line < editor.scroll || line >= editor.scroll + visible_lines {
+                editor.scroll = line.saturating_sub(visible_lines / 2);
+            }
+        }
+    }
+
+    // the rest of the stopped run happens in s_script on the next tick.
+    pub fn resume_script(&mut self) {
+        if !self.script.paused {
+            return;
+        }
+        self.leave_debugger();
+        self.set_script_status("Script running", HEAL_COLOR);
+    }
+
+    // runs the stopped run up to its next statement. when the run ends first, the game plays
+    // one tick and stops again at the top of the next run.
+    pub fn step_script(&mut self) {
+        if !self.script.paused {
+            return;
+        }
+        let (Some(program), Some(mut vm)) = (self.script.program.clone(), self.script.suspended.take()) else {
+            return;
+        };
+        match vm.run(&program, self, SCRIPT_INSTRUCTION_BUDGET, StopAt::NextStatement) {
+            Ok(Exit::Paused) => self.pause_script(vm),
+            Ok(Exit::Finished) => {
+                self.leave_debugger();
+                self.script.break_on_next = true;
+                self.script_finished();
+            }
+            Err(e) => {
+                self.leave_debugger();
+                self.script_failed(e);
+            }
+        }
+    }
+
+    pub fn leave_debugger(&mut self) {
+        self.script.paused = false;
+        for tag in [DEBUG_PANEL_TAG, "debug_continue_button", "debug_step_button"] {
+            self.em.purge_entity_by_tag(tag);
+        }
+    }
+
+    // F5 continues, Shift+F5 stops the script and F10 steps while a run is stopped.
+    pub fn debugger_key_pressed(&mut self, code: Key, shift: bool) -> bool {
+        if !self.script.paused {
+            return false;
+        }
+        match code {
+            Key::F5 if shift => {
+                self.stop_script();
+                self.set_script_status("Script stopped", OFF_TEXT_CLR);
+            }
+            Key::F5 => self.resume_script(),
+            Key::F10 => self.step_script(),
+            _ => return false,
+        }
+        true
+    }
+
+    // the panel right of the editor; what it lists is drawn in render_debug_panel.
+    fn show_debug_panel(&mut self) {
+        let scale = get_scale();
+        let s = |x: u32| scaled(scale, x);
+
+        let panel = self.em.add_entity(Some(DEBUG_PANEL_TAG.to_string()));
+        self.em.add_property_to_entity(PropertiesEnum::Rect, panel);
+        if let Some(rect) = self.em.get_prects_mut(panel).and_then(|rects| rects.last_mut()) {
+            rect.x = s(630);
+            rect.y = s(120);
+            rect.width = s(226);
+            rect.height = s(750);
+            rect.colors = ColorPair::from_colors(ENCAPSULATION_REGIONS, Some(sfml::graphics::Color::BLACK));
+            rect.draw = true;
+            rect.strata = 5;
+        }
+
+        self.create_panel_button("debug_continue_button", "Continue", "Let the game and the script carry on until the next breakpoint. (F5)", (s(640), s(810), s(98), s(50)), 10, ClickAction::DebugContinue);
+        self.create_panel_button("debug_step_button", "Step", "Run the script up to its next statement. (F10)", (s(748), s(810), s(98), s(50)), 10, ClickAction::DebugStep);
+    }
+}
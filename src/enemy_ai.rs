@@ -0,0 +1,7 @@
+use crate::game::*;
+
+impl Game<'_> {
+    pub fn s_enemy_ai(&mut self) {
+        self.combat().run_enemy_ai(); // branch to combat.rs
+    }
+}
@@ -9,10 +9,12 @@ pub struct EntityManager {
     pub rectangles: HashMap<u32, Vec<PRect>>,     // 0..n
     pub texts: HashMap<u32, Vec<PText>>,          // 0..n
     pub healthbars: HashMap<u32, PHealthbar>,     // 0..1
+    pub resourcebars: HashMap<u32, PResourcebar>, // 0..1
     pub castbars: HashMap<u32, PCastbar>,         // 0..1
     pub state_vecs: HashMap<u32, PState>,         // 0..1
     pub tooltip_data: HashMap<u32, PTooltipData>, // 0..1
     pub clickables: HashMap<u32, PClickable>,
+    pub code_editors: HashMap<u32, PCodeEditor>,  // 0..1
 
     entity_id_counter: u32,
     property_id_counter: u32,
@@ -25,10 +27,12 @@ impl EntityManager {
             rectangles: HashMap::new(),
             texts: HashMap::new(),
             healthbars: HashMap::new(),
+            resourcebars: HashMap::new(),
             castbars: HashMap::new(),
             state_vecs: HashMap::new(),
             tooltip_data: HashMap::new(),
             clickables: HashMap::new(),
+            code_editors: HashMap::new(),
 
             entity_id_counter: 0,
             property_id_counter: 0,
@@ -59,10 +63,12 @@ impl EntityManager {
             PropertiesEnum::Rect => self.add_rect(id),
             PropertiesEnum::Text => self.add_text(id),
             PropertiesEnum::Healthbar => self.add_healthbar(id),
+            PropertiesEnum::Resourcebar => self.add_resourcebar(id),
             PropertiesEnum::Castbar => self.add_castbar(id),
             PropertiesEnum::State => self.add_state(id),
             PropertiesEnum::TooltipData => self.add_tooltip(id),
             PropertiesEnum::Clickable => self.add_clickable(id),
+            PropertiesEnum::CodeEditor => self.add_code_editor(id),
         }
     }
 
@@ -115,6 +121,22 @@ impl EntityManager {
         });
     }
 
+    fn add_resourcebar(&mut self, id: u32) {
+        let pid = self.next_pid();
+        self.resourcebars.insert(id, PResourcebar {
+            id: pid,
+            x: 10,
+            y: 10,
+            width: 10,
+            height: 10,
+            draw: true,
+            strata: 20,
+            base_colors: ColorPair { fill: (64, 64, 64), outline: Some((0, 0, 0)) },
+            inner_colors: ColorPair { fill: (0, 0, 254), outline: Some((0, 0, 0)) },
+            gem_entity_id: None,
+        });
+    }
+
     fn add_castbar(&mut self, id: u32) {
         let pid = self.next_pid();
         self.castbars.insert(id, PCastbar {
@@ -164,10 +186,12 @@ impl EntityManager {
         self.rectangles.remove(&id);
         self.texts.remove(&id);
         self.healthbars.remove(&id);
+        self.resourcebars.remove(&id);
         self.castbars.remove(&id);
         self.state_vecs.remove(&id);
         self.tooltip_data.remove(&id);
         self.clickables.remove(&id);
+        self.code_editors.remove(&id);
     }
 
     fn add_clickable(&mut self, id: u32) {
@@ -176,6 +200,34 @@ impl EntityManager {
             id: (pid), clickable: (true), rect_reference_id: (None), action: (ClickAction::RunButton) });
     }
 
+    fn add_code_editor(&mut self, id: u32) {
+        let pid = self.next_pid();
+        self.code_editors.insert(id, PCodeEditor {
+            id: pid,
+            x: 10,
+            y: 10,
+            width: 10,
+            height: 10,
+            lines: vec![String::new()],
+            caret: TextPos::default(),
+            anchor: None,
+            scroll: 0,
+            scroll_x: 0,
+            focused: false,
+            dragging: false,
+            undo: Vec::new(),
+            redo: Vec::new(),
+            last_edit: EditKind::None,
+            revision: 0,
+            completions: Vec::new(),
+            completion_index: 0,
+            completion_prefix: 0,
+            breakpoints: Vec::new(),
+            draw: false,
+            strata: 0,
+        });
+    }
+
     pub fn get_id_by_tag(&self, tag: String) -> Option<u32> {
         self.ids.iter()
         .find_map(|(&id, pid)| if pid.tag == tag { Some(id) } else { None })
@@ -198,6 +250,10 @@ impl EntityManager {
         self.healthbars.get_mut(&id)
     }
 
+    pub fn get_presourcebar_mut(&mut self, id: u32) -> Option<&mut PResourcebar> {
+        self.resourcebars.get_mut(&id)
+    }
+
     pub fn get_pcastbar_mut(&mut self, id: u32) -> Option<&mut PCastbar> {
         self.castbars.get_mut(&id)
     }
@@ -214,6 +270,10 @@ impl EntityManager {
         self.clickables.get_mut(&id)
     }
 
+    pub fn get_pcode_editor_mut(&mut self, id: u32) -> Option<&mut PCodeEditor> {
+        self.code_editors.get_mut(&id)
+    }
+
     pub fn get_pid_mut(&mut self, id: u32) -> Option<&mut PId> {
         self.ids.get_mut(&id)
     }
@@ -230,6 +290,10 @@ impl EntityManager {
         self.healthbars.get(&id)
     }
 
+    pub fn get_presourcebar_non_mut(&self, id: u32) -> Option<&PResourcebar> {
+        self.resourcebars.get(&id)
+    }
+
     pub fn get_pcastbar_non_mut(&self, id: u32) -> Option<&PCastbar> {
         self.castbars.get(&id)
     }
@@ -246,6 +310,10 @@ impl EntityManager {
         self.clickables.get(&id)
     }
 
+    pub fn get_pcode_editor_non_mut(&self, id: u32) -> Option<&PCodeEditor> {
+        self.code_editors.get(&id)
+    }
+
     pub fn get_pid_non_mut(&self, id: u32) -> Option<&PId> {
         self.ids.get(&id)
     }
@@ -1,13 +1,16 @@
 use std::{collections::HashMap, hash::Hash};
 use serde::{Serialize, Deserialize};
-use crate::{entities::EntityManager, g_properties::{Allegiances, GPAction, GPActionQueue, GPAllegiance, GPBuffBar, GPDebuff, GPDebuffBar, GPId, GPLevel, GPMortality, GPStats, GPTarget}, *};
+use crate::{g_properties::{Allegiances, GPAction, GPActionQueue, GPAllegiance, GPBehavior, GPBuffBar, GPCooldowns, GPDebuff, GPDeath, GPDebuffBar, GPId, GPLevel, GPMortality, GPName, GPPosition, GPResistances, GPResource, GPReward, GPStats, GPTarget}, *};
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct GameEntityManager {
     pub gids: HashMap<u32, GPId>,
+    pub names: HashMap<u32, GPName>,
     pub mortalities: HashMap<u32, GPMortality>,
     pub allegiances: HashMap<u32, GPAllegiance>,
+    pub positions: HashMap<u32, GPPosition>,
     pub stats: HashMap<u32, GPStats>,
+    pub resources: HashMap<u32, GPResource>,
     pub targets: HashMap<u32, GPTarget>,
     pub buffs: HashMap<u32, g_properties::GPBuff>,
     pub buffbars: HashMap<u32, GPBuffBar>,
@@ -15,8 +18,12 @@ pub struct GameEntityManager {
     pub debuffbars: HashMap<u32, GPDebuffBar>,
     pub actions: HashMap<u32, GPAction>,
     pub actionqueue: HashMap<u32, GPActionQueue>,
+    pub cooldowns: HashMap<u32, GPCooldowns>,
     pub levels: HashMap<u32, GPLevel>,
-    pub texture_to_entity: HashMap<String, u32>,
+    pub behaviors: HashMap<u32, GPBehavior>,
+    pub resistances: HashMap<u32, GPResistances>,
+    pub rewards: HashMap<u32, GPReward>,
+    pub deaths: HashMap<u32, GPDeath>,
 
     pub player_id: Option<u32>,
     game_entity_id_counter: u32,
@@ -27,9 +34,12 @@ impl GameEntityManager {
     pub fn new() -> Self {
         GameEntityManager {
             gids: HashMap::new(),
+            names: HashMap::new(),
             mortalities: HashMap::new(),
             allegiances: HashMap::new(),
+            positions: HashMap::new(),
             stats: HashMap::new(),
+            resources: HashMap::new(),
             targets: HashMap::new(),
             buffs: HashMap::new(),
             buffbars: HashMap::new(),
@@ -37,8 +47,12 @@ impl GameEntityManager {
             debuffbars: HashMap::new(),
             actions: HashMap::new(),
             actionqueue: HashMap::new(),
+            cooldowns: HashMap::new(),
             levels: HashMap::new(),
-            texture_to_entity: HashMap::new(),
+            behaviors: HashMap::new(),
+            resistances: HashMap::new(),
+            rewards: HashMap::new(),
+            deaths: HashMap::new(),
 
             player_id: None,
             game_entity_id_counter: 0,
@@ -54,15 +68,6 @@ impl GameEntityManager {
         self.add_entity(Some(tag))
     }
 
-    pub fn get_enemy(&mut self) -> Option<u32> {
-        for (id, allegiance) in &self.allegiances {
-            if allegiance.allegiance == Allegiances::Enemy  {
-                return Some(*id);
-            }
-        }
-        None
-    }
-
     pub fn get_all_enemies(&self) -> Vec<u32> {
         self.allegiances
             .iter()
@@ -76,6 +81,79 @@ impl GameEntityManager {
             .collect()
     }
 
+    pub fn is_alive(&self, id: u32) -> bool {
+        self.mortalities.get(&id).is_some_and(|m| m.is_alive)
+    }
+
+    pub fn get_name(&self, id: u32) -> String {
+        self.names.get(&id)
+            .map(|n| n.name.clone())
+            .unwrap_or_else(|| self.gids.get(&id).map(|gid| gid.tag.clone()).unwrap_or_default())
+    }
+
+    pub fn get_target(&self, id: u32) -> Option<u32> {
+        self.targets.get(&id).and_then(|t| t.target_entity)
+    }
+
+    // living enemies ordered left to right, the order tab-targeting walks through.
+    pub fn get_living_enemies(&self) -> Vec<u32> {
+        let mut enemies: Vec<u32> = self.get_all_enemies()
+            .into_iter()
+            .filter(|&id| self.is_alive(id))
+            .collect();
+        enemies.sort_by_key(|id| (self.positions.get(id).map(|p| p.x).unwrap_or(0), *id));
+        enemies
+    }
+
+    pub fn next_enemy_after(&self, current: Option<u32>) -> Option<u32> {
+        let enemies = self.get_living_enemies();
+        match current.and_then(|c| enemies.iter().position(|&id| id == c)) {
+            Some(i) => enemies.get((i + 1) % enemies.len()).copied(),
+            None => enemies.first().copied(),
+        }
+    }
+
+    pub fn nearest_enemy(&self, from: u32) -> Option<u32> {
+        let origin = self.positions.get(&from)?;
+        self.get_living_enemies()
+            .into_iter()
+            .filter_map(|id| self.positions.get(&id).map(|pos| (id, pos.distance_to(origin))))
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(id, _)| id)
+    }
+
+    pub fn lowest_health_enemy(&self) -> Option<u32> {
+        self.get_living_enemies()
+            .into_iter()
+            .filter_map(|id| self.stats.get(&id).map(|stats| (id, stats.health_curr)))
+            .min_by_key(|&(_, health)| health)
+            .map(|(id, _)| id)
+    }
+
+    // drops the entity and every property it has.
+    pub fn remove_entity(&mut self, id: u32) {
+        self.gids.remove(&id);
+        self.names.remove(&id);
+        self.mortalities.remove(&id);
+        self.allegiances.remove(&id);
+        self.positions.remove(&id);
+        self.stats.remove(&id);
+        self.resources.remove(&id);
+        self.targets.remove(&id);
+        self.buffs.remove(&id);
+        self.buffbars.remove(&id);
+        self.debuffs.remove(&id);
+        self.debuffbars.remove(&id);
+        self.actions.remove(&id);
+        self.actionqueue.remove(&id);
+        self.cooldowns.remove(&id);
+        self.levels.remove(&id);
+        self.behaviors.remove(&id);
+        self.resistances.remove(&id);
+        self.rewards.remove(&id);
+        self.deaths.remove(&id);
+    }
+
     pub fn add_entity(&mut self, tag: Option<String>) -> u32 {
         let id = self.next_eid();
         let final_tag = tag.unwrap_or_else(|| format!("entity_{}", id));
@@ -1,6 +1,7 @@
+use std::collections::HashMap;
 use serde::{Serialize, Deserialize};
 
-use crate::{game::{INFERNUM_COLOR, MAIN_OUTLINE_CLR, MIASMA_COLOR, OFF_OUTLINE_CLR}, properties::ColorPair};
+use crate::properties::ColorPair;
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 pub enum GPEnum {
@@ -13,6 +14,12 @@ pub struct GPId { // each entity must have 1 and only 1 PId
     pub tag: String, // will be specified for you if not specified.
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub struct GPName { // display name, e.g. shown in the enemy info region
+    pub id: u32,
+    pub name: String,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
 pub enum Allegiances {
     Player,
@@ -29,7 +36,8 @@ pub struct GPAllegiance {
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
 pub struct GPMortality {
     pub id: u32,
-    pub is_alive: bool
+    pub is_alive: bool,
+    pub immortal: bool, // health that runs out is topped back up instead, see s_mortality
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
@@ -44,6 +52,37 @@ pub struct GPStats {
     pub will: u32,
     pub volatility: u32
 }
+pub static SOLIDITY_MITIGATION_FACTOR: f32 = 50.0;
+pub static BASE_CRIT_CHANCE: f32 = 0.05;
+pub static MAX_CRIT_CHANCE: f32 = 0.5;
+pub static BASE_CRIT_MULTIPLIER: f32 = 1.5;
+
+impl GPStats {
+    // every point of volatility adds 1% crit chance and 2% crit damage.
+    pub fn crit_chance(&self) -> f32 {
+        (BASE_CRIT_CHANCE + self.volatility as f32 * 0.01).min(MAX_CRIT_CHANCE)
+    }
+
+    pub fn crit_multiplier(&self) -> f32 {
+        BASE_CRIT_MULTIPLIER + self.volatility as f32 * 0.02
+    }
+
+    // every point of chaos adds 10% damage.
+    pub fn spell_power_multiplier(&self) -> f32 {
+        1.0 + self.chaos as f32 * 0.1
+    }
+
+    // every point of haste speeds up hasted tick rates by 2%.
+    pub fn haste_multiplier(&self) -> f32 {
+        1.0 + self.haste as f32 * 0.02
+    }
+
+    // fraction of every hit that solidity absorbs, approaching but never reaching 1.
+    pub fn solidity_mitigation(&self) -> f32 {
+        self.solidity as f32 / (self.solidity as f32 + SOLIDITY_MITIGATION_FACTOR)
+    }
+}
+
 pub enum StatsEnum {
     Chaos,
     Solidity,
@@ -52,6 +91,78 @@ pub enum StatsEnum {
     Will,
     Volatility,
 }
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum DamageSchool {
+    Plague,
+    Fire,
+    Shadow,
+    Physical,
+}
+
+impl DamageSchool {
+    pub const ALL: [DamageSchool; 4] = [DamageSchool::Plague, DamageSchool::Fire, DamageSchool::Shadow, DamageSchool::Physical];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            DamageSchool::Plague => "plague",
+            DamageSchool::Fire => "fire",
+            DamageSchool::Shadow => "shadow",
+            DamageSchool::Physical => "physical",
+        }
+    }
+}
+
+pub static MAX_RESISTANCE: u32 = 100; // percent, a target at this is immune to the school
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct GPResistances {
+    pub id: u32,
+    pub values: HashMap<DamageSchool, u32>, // percent of damage of that school ignored
+}
+
+impl GPResistances {
+    pub fn get(&self, school: DamageSchool) -> u32 {
+        self.values.get(&school).copied().unwrap_or(0).min(MAX_RESISTANCE)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct GPResource {
+    pub id: u32,
+    pub name: String,
+    pub max: u32,
+    pub curr: u32,
+    pub regen_per_sec: u32,
+    pub pending_regen: f32,
+}
+
+impl GPResource {
+    // max and regen are derived from will, see s_resources.
+    pub fn max_from_will(will: u32) -> u32 {
+        will.saturating_mul(10).saturating_add(100)
+    }
+
+    pub fn regen_from_will(will: u32) -> u32 {
+        will.saturating_add(5)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub struct GPPosition { // unscaled 1920x1080 coordinates
+    pub id: u32,
+    pub x: u32,
+    pub y: u32,
+}
+
+impl GPPosition {
+    pub fn distance_to(&self, other: &GPPosition) -> f32 {
+        let dx = self.x as f32 - other.x as f32;
+        let dy = self.y as f32 - other.y as f32;
+        (dx * dx + dy * dy).sqrt()
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
 pub struct GPTarget {
     pub id: u32,
@@ -82,10 +193,14 @@ pub struct GPDebuffBar {
 pub struct GPDebuff {
     pub id: u32,
     pub name: String,
+    pub caster: u32,
     pub total_duration: u32,
     pub time_left: u32,
     pub stacks: u32,
-    pub pending_damage: f32,
+    pub pending_damage: f32, // fraction of a point carried between ticks
+    pub tick_interval: u32,  // ms, fixed when applied
+    pub next_tick_in: u32,   // ms
+    pub caster_stats: Option<GPStats>, // snapshot taken when applied
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
@@ -103,30 +218,172 @@ pub struct GPAction {
     pub time_action_takes: u32,
     pub time_remaining: u32,
     pub spell: Option<Spells>,
+    pub target: Option<u32>, // locked in when the action is queued
 }
 
+pub static SPELL_QUEUE_WINDOW_MS: u32 = 400;
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
 pub struct GPActionQueue {
     pub id: u32,
     pub queue: Vec<GPAction>,
+    pub queue_window: u32, // ms, the next action can only be queued this close to the end of the current one
+}
+
+impl GPActionQueue {
+    pub fn can_queue(&self) -> bool {
+        match self.queue.as_slice() {
+            [] => true,
+            [current] => current.time_remaining <= self.queue_window,
+            _ => false,
+        }
+    }
 }
 
+pub static GLOBAL_COOLDOWN_MS: u32 = 1500;
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub struct GPCooldown {
+    pub total: u32,     // ms
+    pub remaining: u32, // ms
+}
+
+impl GPCooldown {
+    pub fn ready() -> Self {
+        GPCooldown { total: 0, remaining: 0 }
+    }
+
+    pub fn start(&mut self, duration: u32) {
+        self.total = duration;
+        self.remaining = duration;
+    }
+
+    // 1.0 right after starting, 0.0 when ready.
+    pub fn fraction(&self) -> f32 {
+        if self.total == 0 {
+            return 0.0;
+        }
+        self.remaining as f32 / self.total as f32
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct GPCooldowns {
+    pub id: u32,
+    pub global: GPCooldown,
+    pub spells: HashMap<Spells, GPCooldown>,
+}
+
+impl GPCooldowns {
+    pub fn remaining(&self, sp: &Spells) -> u32 {
+        self.spells.get(sp).map(|cd| cd.remaining).unwrap_or(0)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
 pub enum Spells {
     Miasma,
     Infernum,
     UmbraMortis,
+    NightTerror,
+    CrushingWeight,
+}
+
+impl Spells {
+    // the id used in spells.json, action tags, debuff names and damage types.
+    pub fn tag(&self) -> &'static str {
+        match self {
+            Spells::Miasma => "miasma",
+            Spells::Infernum => "infernum",
+            Spells::UmbraMortis => "umbra_mortis",
+            Spells::NightTerror => "night_terror",
+            Spells::CrushingWeight => "crushing_weight",
+        }
+    }
+}
+
+// a DoT with contagion may copy itself to nearby enemies each time it deals damage.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Contagion {
+    pub range: u32,
+    pub chance: f32,
+    pub max_spreads_per_tick: u32,
+}
+
+// bonus damage against targets that are already in bad shape, see the execute damage modifier.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Execute {
+    pub missing_health_bonus: f32, // extra damage at 0 health, scales down linearly to none at full
+    pub per_dot_bonus: f32,        // extra damage for each of the caster's DoTs on the target
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub struct AnimRef {
+    pub texture: String,
+    pub frames: u32,
+}
+
+pub static DEFAULT_TICK_INTERVAL_MS: u32 = 1000;
+
+fn default_tick_interval() -> u32 {
+    DEFAULT_TICK_INTERVAL_MS
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct SpellData {
+    pub id: Spells,
+    pub name: String,
     pub icon: String,
     pub colors: ColorPair,
+    pub school: DamageSchool,
+    pub cast_time: u32, // ms
+    pub cost: u32,
+    pub cooldown: u32, // ms
     pub upfront_dam: u32,
     pub coefficient: u32,
     pub dps: u32,
-    pub duration: u32,
+    pub duration: u32, // ms
+    #[serde(default = "default_tick_interval")]
+    pub tick_interval: u32, // ms between DoT ticks
+    #[serde(default)]
+    pub haste_affects_ticks: bool,
+    pub tooltip: String,
+    #[serde(default)]
+    pub cast_anim: Option<AnimRef>, // enemy spells have none
+    #[serde(default)]
+    pub projectile_anim: Option<AnimRef>,
+    #[serde(default)]
+    pub contagion: Option<Contagion>,
+    #[serde(default)]
+    pub leech_pct: u32, // percent of the damage dealt that heals the caster
+    #[serde(default)]
+    pub execute: Option<Execute>,
+    #[serde(default)]
+    pub interrupts: bool, // stops whatever the target is casting when it lands
+}
 
+impl SpellData {
+    // base damage of one full DoT tick, before the caster's stats; a hasted tick rate adds ticks
+    // rather than shrinking them.
+    pub fn tick_damage(&self) -> f32 {
+        self.dps as f32 * self.coefficient as f32 * self.tick_interval as f32 / 1000.0
+    }
+}
+
+// one entry of an enemy's ability list, see enemies.json.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub struct EnemyAbility {
+    pub spell: Spells,
+    pub priority: u32, // higher goes first when several abilities are ready
+    #[serde(default)]
+    pub below_health_pct: Option<u32>, // only used once the caster is under this much health
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub struct GPBehavior { // drives s_enemy_ai, abilities are kept sorted by priority
+    pub id: u32,
+    pub abilities: Vec<EnemyAbility>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
@@ -137,37 +394,37 @@ pub struct GPLevel {
     pub next_level_xp: u32,
 }
 
-pub fn get_spelldata_from_string(spell: &String) -> Option<SpellData> {
-    if spell == "miasma" || spell == "Miasma" {
-        return get_spell_data(Spells::Miasma);
+impl GPLevel {
+    pub fn xp_for_level(level: u32) -> u32 {
+        20 * level.max(1)
     }
-    else if spell == "infernum" || spell == "Infernum" {
-        return get_spell_data(Spells::Infernum);
+
+    // returns how many levels were gained.
+    pub fn gain_xp(&mut self, xp: u32) -> u32 {
+        self.curr_xp += xp;
+        let mut gained = 0;
+        while self.curr_xp >= self.next_level_xp {
+            self.curr_xp -= self.next_level_xp;
+            self.curr_level += 1;
+            self.next_level_xp = GPLevel::xp_for_level(self.curr_level);
+            gained += 1;
+        }
+        gained
     }
-    return None;
 }
 
-pub fn get_spell_data(sp: Spells) -> Option<SpellData> {
-    if sp == Spells::Miasma {
-        Some(SpellData {
-            icon: "miasma".to_string(),
-            colors: ColorPair::from_colors(MIASMA_COLOR, Some(MAIN_OUTLINE_CLR)),
-            upfront_dam: 0,
-            coefficient: 3,
-            dps: 5,
-            duration: 5,
-        })
-    }
-    else if sp == Spells::Infernum {
-        Some(SpellData { 
-            icon: ("infernum".to_string()), 
-            colors: (ColorPair::from_colors(INFERNUM_COLOR, Some(MAIN_OUTLINE_CLR))),
-            upfront_dam: 5,
-            coefficient: 2,
-            dps: 1,
-            duration: 10 })
-    }
-    else {
-        None
-    }
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub struct GPReward { // granted to the player when the entity dies
+    pub id: u32,
+    pub xp: u32,
+    pub items: Vec<String>,
+}
+
+pub static DEATH_ANIMATION_MS: u32 = 600;
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub struct GPDeath { // added when an entity dies
+    pub id: u32,
+    pub time_since_death: u32, // ms
+    pub sprite_height: Option<u32>, // before the death animation started shrinking it
 }
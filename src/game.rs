@@ -2,6 +2,9 @@ use std::collections::HashMap;
 use std::mem;
 use std::time::Instant;
 
+use rand::SeedableRng;
+use rand::rngs::StdRng;
+
 use sfml::cpp::FBox;
 use sfml::graphics::{Color, Font, Texture, RenderWindow};
 use sfml::graphics::{Text, RectangleShape, Sprite, RenderTarget, Transformable, Shape, Shader};
@@ -14,6 +17,17 @@ use crate::{entities, g_entities};
 use crate::properties::*;
 use crate::user_input::*;
 use crate::construct_window::*;
+use crate::spellbook::{Spellbook, SPELLBOOK_PATH};
+use crate::bestiary::{Bestiary, BESTIARY_PATH};
+use crate::combat_log::{CombatLog, CombatStats};
+use crate::damage_pipeline::DamagePipeline;
+use crate::scripting::RotationScript;
+use crate::script_unlocks::{ScriptUnlocks, SCRIPT_UNLOCKS_PATH};
+use crate::script_library::{ScriptLibrary, SCRIPTS_DIR};
+use crate::challenge::{ChallengeBook, ChallengeRun, Leaderboard, CHALLENGES_PATH, LEADERBOARD_PATH};
+use crate::training::Training;
+use crate::player_data::{PlayerData, PLAYER_DATA_PATH};
+use crate::save::{CharacterSave, CHARACTER_SAVE_PATH};
 
 pub static BASE: Color    = Color::rgba(43,49,55,255);
 pub static ALT_BASE: Color = Color::rgba(36,41,46,255);
@@ -31,9 +45,18 @@ pub static LEGENDARY: Color = Color::rgba(250,214,104,255);
 pub static EPIC: Color = Color::rgba(112,36,163,255);
 pub static MAIN_TEXT_CLR: Color = Color::rgba(246,248,250,255); 
 pub static OFF_TEXT_CLR: Color = Color::rgba(101,126,150,255);
-pub static MIASMA_COLOR: Color = Color::rgba(125,185,112,255);
-pub static INFERNUM_COLOR: Color = Color::rgba(233,103,6,255);
 pub static XP_COLOR: Color = Color::rgba(98,67,211,255);
+pub static MANA_COLOR: Color = Color::rgba(64,128,230,255);
+pub static HEAL_COLOR: Color = Color::rgba(90,220,90,255);
+pub static DAMAGE_TAKEN_COLOR: Color = Color::rgba(230,60,60,255);
+pub static SELECTION_COLOR: Color = Color::rgba(38,79,120,255);
+pub static KEYWORD_COLOR: Color = Color::rgba(198,120,221,255);
+pub static NUMBER_COLOR: Color = Color::rgba(209,154,102,255);
+pub static STRING_COLOR: Color = Color::rgba(152,195,121,255);
+pub static BUILTIN_COLOR: Color = Color::rgba(97,175,239,255);
+pub static WARNING_COLOR: Color = Color::rgba(240,190,88,255);
+pub static PAUSED_LINE_COLOR: Color = Color::rgba(92,78,32,255);
+pub static BREAKPOINT_COLOR: Color = Color::rgba(229,72,77,255);
 
 pub static WINDOW_WIDTH: u32 = 3840;
 pub static WINDOW_HEIGHT: u32  = 2160;
@@ -63,16 +86,33 @@ pub struct Game<'a> {
     pub gbfnt: FBox<Font>,
     pub textures: HashMap<String, FBox<Texture>>,
     pub anims: Animation,
-    pub damage_queue: Vec<crate::systems::Damage>,
+    pub damage_queue: Vec<crate::combat::Damage>,
+    pub damage_pipeline: DamagePipeline,
+    pub health_changes: Vec<crate::combat::HealthChange>,
+    pub interrupt_queue: Vec<crate::combat::Interrupt>,
     pub desat_shader: FBox<Shader<'a>>,
     pub floating_texts: Vec<crate::systems::FloatingText>,
+    pub combat_log: CombatLog,
+    pub combat_stats: CombatStats,
+    pub spellbook: Spellbook,
+    pub bestiary: Bestiary,
+    pub player_data: PlayerData,
+    pub encounter_index: usize,
+    pub script: RotationScript,
+    pub script_unlocks: ScriptUnlocks,
+    pub script_library: ScriptLibrary,
+    pub challenges: ChallengeBook,
+    pub challenge: Option<ChallengeRun>, // the challenge being fought, if any
+    pub leaderboard: Leaderboard,
+    pub training: Training,
+    pub rng: StdRng,
 
     pub time_elapsed: f32,    // total time in seconds (float)
     pub delta_time: f32,      // delta time in seconds (float)
     pub time_elapsed_ms: u32, // total time in milliseconds (int)
     pub delta_time_ms: u32,
     last_frame_time: Instant,
-    pub miasma_has_spawned: bool,
+    pub projectile_has_spawned: bool,
 }
 
 impl<'a> Game<'a> {
@@ -101,6 +141,32 @@ impl<'a> Game<'a> {
         let mut shader = Shader::from_file("./src/shaders/desaturate.frag", sfml::graphics::ShaderType::Fragment)
             .expect("Failed to load shader");
         shader.set_uniform_float("desaturation", 1.0).unwrap();
+
+        let spellbook = Spellbook::load(SPELLBOOK_PATH)
+            .expect("Failed to load spellbook");
+        let bestiary = Bestiary::load(BESTIARY_PATH, &spellbook)
+            .expect("Failed to load bestiary");
+        let mut player_data = PlayerData::load(PLAYER_DATA_PATH)
+            .expect("Failed to load player data");
+        // the player is spawned at the saved level, load_character restores the rest and reports errors
+        if let Ok(Some(save)) = CharacterSave::load(CHARACTER_SAVE_PATH) {
+            save.restore_progress(&mut player_data);
+        }
+        let script_unlocks = ScriptUnlocks::load(SCRIPT_UNLOCKS_PATH)
+            .expect("Failed to load script unlocks");
+        let challenges = ChallengeBook::load(CHALLENGES_PATH, &bestiary)
+            .expect("Failed to load challenges");
+        // a leaderboard that can't be read starts over rather than keeping the game from starting,
+        // the bad file is kept as .bak so the next save doesn't overwrite it
+        let leaderboard = Leaderboard::load(LEADERBOARD_PATH).unwrap_or_else(|e| {
+            println!("{}", e);
+            let backup = format!("{}.bak", LEADERBOARD_PATH);
+            match std::fs::rename(LEADERBOARD_PATH, &backup) {
+                Ok(()) => println!("moved the unreadable leaderboard to {}", backup),
+                Err(e) => println!("could not move the leaderboard to {}: {}", backup, e),
+            }
+            Leaderboard::default()
+        });
         
         Game {
             window,
@@ -117,15 +183,32 @@ impl<'a> Game<'a> {
             textures: HashMap::new(),
             anims: Animation::new(),
             damage_queue: Vec::new(),
+            damage_pipeline: DamagePipeline::with_builtins(),
+            health_changes: Vec::new(),
+            interrupt_queue: Vec::new(),
             desat_shader: shader,
             floating_texts: Vec::new(),
+            combat_log: CombatLog::new(),
+            combat_stats: CombatStats::new(0),
+            spellbook,
+            bestiary,
+            player_data,
+            encounter_index: 0,
+            script: RotationScript::new(),
+            script_unlocks,
+            script_library: ScriptLibrary::new(SCRIPTS_DIR),
+            challenges,
+            challenge: None,
+            leaderboard,
+            training: Training::new(),
+            rng: StdRng::from_entropy(),
 
             time_elapsed: 0.0,
             delta_time: 0.0,
             time_elapsed_ms: 0,
             delta_time_ms: 0,
             last_frame_time: Instant::now(),
-            miasma_has_spawned: false,
+            projectile_has_spawned: false,
         }
     }
 
@@ -133,6 +216,15 @@ impl<'a> Game<'a> {
         while self.window.is_open() {
             self.user_input_main_entry(); // branch to user_input.rs
 
+            // the game stands still while the debugger has the script stopped, and picks up
+            // from there once it carries on.
+            if self.script.paused {
+                self.last_frame_time = Instant::now();
+                self.check_code_editor(); // branch to code_editor.rs
+                self.render_main_entry(); // branch to render_pipeline.rs
+                continue;
+            }
+
             // timekeeping
             let now = Instant::now();
             let frame_duration = now - self.last_frame_time;
@@ -148,9 +240,19 @@ impl<'a> Game<'a> {
             // game systems
             
             self.s_mortality(); // branch to systems.rs
+            self.s_enemy_ai(); // branch to enemy_ai.rs
+            self.s_script(); // branch to scripting.rs
             self.s_debuffs(); // branch to systems.rs
             self.s_damage(); // branch to systems.rs
+            self.s_health(); // branch to systems.rs
+            self.s_resources(); // branch to systems.rs
+            self.s_cooldowns(); // branch to systems.rs
+            self.s_interrupts(); // branch to systems.rs
+            self.s_encounter(); // branch to lifecycle.rs
             self.update_game_main_entry(); // branch to update_game.rs
+            self.s_script_events(); // branch to scripting.rs
+            self.s_challenge(); // branch to challenge.rs
+            self.s_training(); // branch to training.rs
             self.anims.update(self.delta_time);
 
             // render last
@@ -163,5 +265,8 @@ impl<'a> Game<'a> {
 
 // STATE DEFINITIONS:
 
-// state[0] == miasma
-// state[1] == infernum
\ No newline at end of file
+// state[2] == stats menu open
+// state[3] == player is dead, defeat screen shown
+// state[4] == script library open
+// state[5] == challenge panel open
+// state[6] == training panel open
\ No newline at end of file
@@ -49,6 +49,10 @@ pub fn get_stat_color(n: u32) -> Option<(u8, u8, u8)> {
     }
 }
 
+pub fn get_stat_color_by_name(name: &str) -> Option<(u8, u8, u8)> {
+    (2..7).find(|n| get_stat(*n).as_deref() == Some(name)).and_then(get_stat_color)
+}
+
 pub fn wrap_text(text: &str, font: &Font, char_size: u32, max_width: f32) -> String {
     let mut wrapped = String::new();
     let mut line = String::new();
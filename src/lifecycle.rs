@@ -0,0 +1,287 @@
+use crate::game::*;
+use crate::g_properties::{GPDeath, GPResource, DEATH_ANIMATION_MS};
+use crate::properties::*;
+use crate::render_pipeline::OVERLAY_STRATA;
+use crate::script::ScriptEvent;
+
+// what dying costs, applied when the player respawns or retries; zero turns a penalty off.
+pub struct DeathPenalty {
+    pub xp_pct: u32,       // of the xp gathered towards the next level
+    pub resource_pct: u32, // of max mana missing after respawning
+}
+
+pub static DEATH_PENALTY: DeathPenalty = DeathPenalty {
+    xp_pct: 10,
+    resource_pct: 50,
+};
+
+impl Game<'_> {
+    // everything that happens once when an entity dies, see s_mortality.
+    pub fn on_death(&mut self, entity: u32) {
+        println!("{} died", self.gem.get_name(entity));
+
+        let next_id = self.gem.next_pid();
+        self.gem.deaths.insert(entity, GPDeath {
+            id: next_id,
+            time_since_death: 0,
+            sprite_height: None,
+        });
+
+        // auras fall off and whatever it was casting stops.
+        if let Some(dbb) = self.gem.debuffbars.get_mut(&entity) {
+            dbb.debuffs.clear();
+        }
+        if let Some(bb) = self.gem.buffbars.get_mut(&entity) {
+            bb.buffs.clear();
+        }
+        if let Some(queue) = self.gem.actionqueue.get_mut(&entity) {
+            queue.queue.clear();
+        }
+
+        self.cancel_casts_on(entity);
+
+        if self.player_target() == Some(entity) {
+            self.script_event(ScriptEvent::TargetDied, None, Some(entity));
+            let next = self.gem.player_id.and_then(|pid| self.gem.nearest_enemy(pid));
+            self.set_player_target(next);
+        }
+
+        if Some(entity) == self.gem.player_id {
+            self.on_player_death();
+        } else if self.gem.get_all_enemies().contains(&entity) {
+            self.combat_stats.kills += 1;
+            // challenges are practice, they give no xp
+            if self.challenge.is_none() {
+                self.grant_rewards(entity);
+            }
+        }
+    }
+
+    pub fn is_defeated(&self) -> bool {
+        self.state[3] == 1
+    }
+
+    // during a challenge s_challenge ends the run instead of showing the defeat screen.
+    fn on_player_death(&mut self) {
+        self.state[3] = 1;
+        self.restore_idle_animation();
+        if self.challenge.is_none() {
+            self.show_defeat_overlay();
+        }
+    }
+
+    // brings the player back at full health; with retry the encounter starts over as well.
+    pub fn respawn_player(&mut self, retry: bool) {
+        let Some(player_id) = self.gem.player_id else {
+            return;
+        };
+        if !self.is_defeated() {
+            return;
+        }
+
+        if retry {
+            self.clear_enemies();
+            self.damage_queue.clear();
+            self.health_changes.clear();
+        }
+
+        if let Some(stats) = self.gem.stats.get_mut(&player_id) {
+            stats.health_curr = stats.health_max;
+        }
+        if let Some(mortality) = self.gem.mortalities.get_mut(&player_id) {
+            mortality.is_alive = true;
+        }
+        self.gem.deaths.remove(&player_id);
+        if let Some(cooldowns) = self.gem.cooldowns.get_mut(&player_id) {
+            cooldowns.global = crate::g_properties::GPCooldown::ready();
+            cooldowns.spells.clear();
+        }
+        self.apply_death_penalty(player_id);
+
+        self.state[3] = 0;
+        self.hide_defeat_overlay();
+        self.restore_idle_animation();
+
+        if retry {
+            self.spawn_encounter(self.encounter_index);
+        }
+    }
+
+    fn apply_death_penalty(&mut self, player_id: u32) {
+        if let Some(level) = self.gem.levels.get_mut(&player_id) {
+            level.curr_xp -= level.curr_xp * DEATH_PENALTY.xp_pct / 100;
+        }
+        self.save_character();
+        let will = self.gem.stats.get(&player_id).map(|stats| stats.will).unwrap_or(0);
+        if let Some(resource) = self.gem.resources.get_mut(&player_id) {
+            let max = GPResource::max_from_will(will);
+            resource.curr = max - max * DEATH_PENALTY.resource_pct / 100;
+        }
+    }
+
+    fn show_defeat_overlay(&mut self) {
+        let scale = get_scale();
+        let s = |x: u32| scaled(scale, x);
+        let now = self.time_elapsed_ms;
+        let stats = self.combat_stats.clone();
+        let encounter = self.bestiary.encounters.get(self.encounter_index)
+            .map(|e| e.name.clone())
+            .unwrap_or_default();
+
+        let panel = self.em.add_entity(Some("defeat_overlay".to_string()));
+        self.em.add_property_to_entity(PropertiesEnum::Rect, panel);
+        if let Some(rect) = self.em.get_prects_mut(panel).and_then(|rects| rects.last_mut()) {
+            rect.x = s(1088);
+            rect.y = s(86);
+            rect.width = s(600);
+            rect.height = s(380);
+            rect.colors = ColorPair::from_colors(ENCAPSULATION_REGIONS, Some(sfml::graphics::Color::BLACK));
+            rect.draw = true;
+            rect.strata = OVERLAY_STRATA;
+        }
+
+        let mut lines = vec![
+            ("Defeated".to_string(), 4, DAMAGE_TAKEN_COLOR),
+            (format!("{} lasted {:.1} s", encounter, stats.elapsed_secs(now)), 1, OFF_TEXT_CLR),
+            (format!("Damage dealt: {} ({:.1} dps)", stats.damage_dealt, stats.dps(now)), 1, MAIN_TEXT_CLR),
+            (format!("Damage taken: {}", stats.damage_taken), 1, MAIN_TEXT_CLR),
+            (format!("Healing received: {} ({} overheal)", stats.healing_received, stats.overhealing), 1, MAIN_TEXT_CLR),
+            (format!("Enemies killed: {}", stats.kills), 1, MAIN_TEXT_CLR),
+        ];
+        if DEATH_PENALTY.xp_pct > 0 || DEATH_PENALTY.resource_pct > 0 {
+            let penalty = format!("Coming back costs {}% xp and {}% mana", DEATH_PENALTY.xp_pct, DEATH_PENALTY.resource_pct);
+            lines.push((penalty, 1, OFF_TEXT_CLR));
+        }
+
+        let mut y = s(96);
+        for (line, size, color) in lines {
+            self.em.add_property_to_entity(PropertiesEnum::Text, panel);
+            if let Some(text) = self.em.get_ptexts_mut(panel).and_then(|texts| texts.last_mut()) {
+                text.text = line;
+                text.scale = scale * size;
+                text.x = s(1108);
+                text.y = y;
+                text.colors = ColorPair::from_colors(color, Some(sfml::graphics::Color::BLACK));
+                text.draw = true;
+                text.strata = OVERLAY_STRATA + 5;
+            }
+            y += s(20 * size + 12);
+        }
+
+        self.create_panel_button("respawn_button", "Respawn", "Get back up and keep fighting the enemies as they are.", (s(1108), s(400), s(260), s(50)), OVERLAY_STRATA + 10, ClickAction::Respawn);
+        self.create_panel_button("retry_button", "Retry", "Start the encounter over from the beginning.", (s(1408), s(400), s(260), s(50)), OVERLAY_STRATA + 10, ClickAction::Retry);
+    }
+
+    fn hide_defeat_overlay(&mut self) {
+        for tag in ["defeat_overlay", "respawn_button", "retry_button"] {
+            self.em.purge_entity_by_tag(tag);
+        }
+    }
+
+    // drops every queued action aimed at the entity; the player is told if its current cast was one.
+    fn cancel_casts_on(&mut self, entity: u32) {
+        let mut player_cast_lost = false;
+        for (caster, queue) in self.gem.actionqueue.iter_mut() {
+            let head_lost = queue.queue.first().is_some_and(|a| a.target == Some(entity));
+            queue.queue.retain(|a| a.target != Some(entity));
+            if head_lost && Some(*caster) == self.gem.player_id {
+                player_cast_lost = true;
+            }
+        }
+        if !player_cast_lost {
+            return;
+        }
+
+        self.floating_message("Target died", MAIN_TEXT_CLR);
+        let next_spell = self.gem.player_id
+            .and_then(|pid| self.gem.actionqueue.get(&pid))
+            .and_then(|q| q.queue.first())
+            .and_then(|a| a.spell.clone());
+        match next_spell {
+            Some(sp) => self.start_cast_animation(&sp),
+            None => self.restore_idle_animation(),
+        }
+    }
+
+    fn grant_rewards(&mut self, entity: u32) {
+        let Some(reward) = self.gem.rewards.get(&entity).cloned() else {
+            return;
+        };
+        for item in reward.items {
+            println!("picked up {}", item);
+            self.grant_script_unlocks(crate::script_unlocks::UnlockSource::Item(item));
+        }
+        let xp = reward.xp;
+        let Some(player_id) = self.gem.player_id else {
+            return;
+        };
+        let Some(level) = self.gem.levels.get_mut(&player_id) else {
+            return;
+        };
+
+        let gained = level.gain_xp(xp);
+        let curr_level = level.curr_level;
+        println!("+{} xp", xp);
+        if gained > 0 {
+            let msg = format!("Level {}!", curr_level);
+            self.floating_message(&msg, XP_COLOR);
+            self.grant_script_unlocks(crate::script_unlocks::UnlockSource::Level(curr_level));
+        }
+        self.save_character();
+    }
+
+    // corpses sink into the ground, squashing from the top down.
+    pub fn animate_deaths(&mut self) {
+        for (entity, death) in self.gem.deaths.iter_mut() {
+            let Some(sprite) = self.anims.active.iter_mut().find(|spr| spr.associated_g_entity == Some(*entity)) else {
+                continue;
+            };
+            let Some(height) = sprite.desired_height else {
+                continue;
+            };
+            let full_height = *death.sprite_height.get_or_insert(height);
+
+            let progress = death.time_since_death.min(DEATH_ANIMATION_MS) as f32 / DEATH_ANIMATION_MS as f32;
+            let new_height = (full_height as f32 * (1.0 - 0.5 * progress)).round() as u32;
+            let bottom = sprite.position.1 + height;
+            sprite.desired_height = Some(new_height);
+            sprite.position.1 = bottom - new_height;
+        }
+    }
+
+    // removes every enemy along with its sprite, dead or alive.
+    pub fn clear_enemies(&mut self) {
+        for enemy in self.gem.get_all_enemies() {
+            self.anims.active.retain(|spr| spr.associated_g_entity != Some(enemy));
+            self.gem.remove_entity(enemy);
+        }
+    }
+
+    // when every enemy of an encounter with next_after_ms is dead and has been for that long, the
+    // corpses are cleared and the next encounter spawns.
+    pub fn s_encounter(&mut self) {
+        // a challenge's enemies stay until it is over, see challenge.rs
+        if self.challenge.is_some() {
+            return;
+        }
+        let Some(delay) = self.bestiary.encounters.get(self.encounter_index).and_then(|e| e.next_after_ms) else {
+            return;
+        };
+        let enemies = self.gem.get_all_enemies();
+        if enemies.is_empty() || enemies.iter().any(|&id| self.gem.is_alive(id)) {
+            return;
+        }
+        let all_dead_for = enemies.iter()
+            .filter_map(|id| self.gem.deaths.get(id))
+            .map(|death| death.time_since_death)
+            .min()
+            .unwrap_or(0);
+        if all_dead_for < delay {
+            return;
+        }
+
+        self.clear_enemies();
+        let next = (self.encounter_index + 1) % self.bestiary.encounters.len();
+        self.spawn_encounter(next);
+    }
+}
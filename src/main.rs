@@ -12,6 +12,25 @@ mod construct_game;
 mod update_game;
 mod animation;
 mod systems;
+mod spellbook;
+mod targeting;
+mod bestiary;
+mod enemy_ai;
+mod combat_log;
+mod damage_pipeline;
+mod lifecycle;
+mod script;
+mod script_vm;
+mod scripting;
+mod code_editor;
+mod debugger;
+mod script_unlocks;
+mod save;
+mod script_library;
+mod challenge;
+mod training;
+mod combat;
+mod player_data;
 
 #[link(name = "Advapi32")]
 unsafe extern "system" {}
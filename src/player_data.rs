@@ -0,0 +1,43 @@
+use std::fs;
+use serde::{Serialize, Deserialize};
+
+pub static PLAYER_DATA_PATH: &str = "./src/assets/data/player.json";
+
+// the character the player starts as, defined in PLAYER_DATA_PATH.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PlayerData {
+    pub name: String,
+    pub level: u32,
+    pub xp: u32,
+    pub health: u32,
+    pub starting_health: u32,
+    pub chaos: u32,
+    pub solidity: u32,
+    pub vitality: u32,
+    pub haste: u32,
+    pub will: u32,
+    pub volatility: u32,
+    pub resource: String, // name of what spells cost, its max and regen follow will
+    pub position: (u32, u32), // center, in unscaled coordinates
+}
+
+impl PlayerData {
+    pub fn load(path: &str) -> Result<PlayerData, String> {
+        let raw = fs::read_to_string(path)
+            .map_err(|e| format!("could not read player data {}: {}", path, e))?;
+        let data: PlayerData = serde_json::from_str(&raw)
+            .map_err(|e| format!("could not parse player data {}: {}", path, e))?;
+        data.validate()?;
+        Ok(data)
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.level == 0 {
+            return Err("the player starts at level 0".to_string());
+        }
+        if self.health == 0 || self.starting_health > self.health {
+            return Err(format!("the player starts with {} of {} health", self.starting_health, self.health));
+        }
+        Ok(())
+    }
+}
@@ -5,10 +5,12 @@ pub enum PropertiesEnum {
     Rect,
     Text,
     Healthbar,
+    Resourcebar,
     Castbar,
     State,
     TooltipData,
     Clickable,
+    CodeEditor,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
@@ -83,6 +85,20 @@ pub struct PHealthbar {
     pub gem_entity_id: Option<u32>, // this should point to a valid entity in gem (GameEntityManager) if used.
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub struct PResourcebar {
+    pub id: u32,
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+    pub base_colors: ColorPair,
+    pub inner_colors: ColorPair,
+    pub draw: bool,
+    pub strata: u8,
+    pub gem_entity_id: Option<u32>, // this should point to an entity in gem with a GPResource.
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct PCastbar {
     pub id: u32,
@@ -116,6 +132,63 @@ pub struct PTooltipData {
     pub icon: Option<String>
 }
 
+// a place in the editor's text, col counts chars rather than bytes.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct TextPos {
+    pub line: usize,
+    pub col: usize,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub enum EditKind {
+    None,
+    Typing,
+    Deleting,
+    Other,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub struct EditorSnapshot {
+    pub lines: Vec<String>,
+    pub caret: TextPos,
+}
+
+// one suggestion of the editor's autocomplete popup.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub struct CompletionItem {
+    pub label: String,
+    pub insert: String,
+    pub detail: String,
+    pub color: (u8, u8, u8),
+}
+
+// the script editor, see code_editor.rs for the editing and render_pipeline.rs for drawing it.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub struct PCodeEditor {
+    pub id: u32,
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+    pub lines: Vec<String>, // never empty
+    pub caret: TextPos,
+    pub anchor: Option<TextPos>, // the other end of the selection, if there is one
+    pub scroll: usize,           // first visible line
+    pub scroll_x: usize,         // first visible column
+    pub focused: bool,
+    pub dragging: bool,
+    pub undo: Vec<EditorSnapshot>,
+    pub redo: Vec<EditorSnapshot>,
+    pub last_edit: EditKind,
+    pub revision: u64, // goes up with every change to the text
+    pub completions: Vec<CompletionItem>, // the autocomplete popup is open while there are any
+    pub completion_index: usize,
+    pub completion_prefix: usize, // chars before the caret a completion replaces
+    pub breakpoints: Vec<usize>,  // lines the debugger stops at, sorted
+    pub draw: bool,
+    pub strata: u8,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
 pub struct PClickable {
     pub id: u32,
@@ -136,4 +209,21 @@ pub enum ClickAction {
     F,
     G,
     H,
+    Respawn,
+    Retry,
+    DebugContinue,
+    DebugStep,
+    LibraryLoad,
+    LibraryDuplicate,
+    LibraryRename,
+    LibraryDelete,
+    LibraryExport,
+    LibraryImport,
+    ChallengeStart,
+    ChallengeRetry,
+    ChallengeDismiss,
+    TrainingDummy,
+    TrainingReset,
+    TrainingLevelDown,
+    TrainingLevelUp,
 }
@@ -1,7 +1,7 @@
 use std::time::Instant;
 use sfml::cpp::FBox;
 use sfml::graphics::{Color, Font, RenderStates, RenderWindow, Texture};
-use sfml::graphics::{Text, RectangleShape, Sprite, RenderTarget, Transformable, Shape};
+use sfml::graphics::{Text, RectangleShape, CircleShape, Sprite, RenderTarget, Transformable, Shape};
 use sfml::window::{Style};
 use crate::g_properties::Actions;
 use crate::{entities, properties::*};
@@ -9,11 +9,15 @@ use crate::game::*;
 use crate::user_input::*;
 use crate::helpers::*;
 
+// em items at or above this strata are drawn over everything else, e.g. the defeat screen.
+pub static OVERLAY_STRATA: u8 = 100;
+
 #[derive(Debug, Clone)]
 enum DrawableItem {
     Rect(PRect),
     Text(PText),
     Healthbar(PHealthbar),
+    Resourcebar(PResourcebar),
     //Castbar(PCastbar),
 }
 
@@ -23,13 +27,27 @@ impl Game<'_> {
 
         let draw_list = self.render_construct_draw_list();
 
+        // overlays go on top of the sprites and floating text, see the end of this function.
+        let (overlays, draw_list): (Vec<_>, Vec<_>) = draw_list.into_iter()
+            .partition(|(strata, _)| *strata >= OVERLAY_STRATA);
         for (_strata, item) in draw_list {
             self.dispatch_item(item);
         }
+        self.render_code_editor();
+        self.render_debug_panel();
+        self.render_script_library();
+        self.render_challenges();
+        self.render_training();
+        self.render_challenge_hud();
         self.render_player_castbar();
+        self.render_cooldown_sweeps();
         self.render_tooltips();
         self.handle_sprites();
+        self.render_target_marker();
+        self.render_enemy_castbars();
+        self.render_target_debuffs();
         self.render_xp_bar();
+        self.render_combat_log();
 
         for ft in &self.floating_texts {
             let mut text = Text::new(&ft.value, &self.gbfnt, ft.scale);
@@ -39,6 +57,10 @@ impl Game<'_> {
             text.set_outline_thickness(1.0);
             self.window.draw(&text);
         }
+
+        for (_strata, item) in overlays {
+            self.dispatch_item(item);
+        }
         self.window.display();
     }
 
@@ -47,6 +69,7 @@ impl Game<'_> {
             DrawableItem::Rect(rect) => self.render_rect(&rect),
             DrawableItem::Text(text) => self.render_text(&text),
             DrawableItem::Healthbar(hb) => self.render_healthbar(&hb),
+            DrawableItem::Resourcebar(rb) => self.render_resourcebar(&rb),
         }
     }
 
@@ -75,17 +98,21 @@ impl Game<'_> {
             }
         }
 
+        for resourcebar in self.em.resourcebars.values() {
+            if resourcebar.draw {
+                draw_list.push((resourcebar.strata, DrawableItem::Resourcebar(resourcebar.clone())));
+            }
+        }
+
         draw_list.sort_by_key(|(strata, _)| *strata);
         draw_list
     }
 
     fn handle_sprites(&mut self) {
         let sprs = self.anims.get_drawables();
-        for (sprite, texture_id) in sprs {
-            let is_alive = self
-                .gem.texture_to_entity
-                .get(&texture_id)
-                .and_then(|id| self.gem.mortalities.get(id))
+        for (sprite, g_entity) in sprs {
+            let is_alive = g_entity
+                .and_then(|id| self.gem.mortalities.get(&id))
                 .map(|m| m.is_alive)
                 .unwrap_or(true);
 
@@ -99,6 +126,133 @@ impl Game<'_> {
         }
     }
 
+    fn render_target_marker(&mut self) {
+        let Some(target) = self.player_target() else {
+            return;
+        };
+        let Some(sprite) = self.anims.active.iter().find(|s| s.associated_g_entity == Some(target)) else {
+            return;
+        };
+
+        let w = sprite.desired_width.unwrap_or(sprite.frame_width) as f32;
+        let h = sprite.desired_height.unwrap_or(sprite.frame_height) as f32;
+        let mut marker = RectangleShape::new();
+        marker.set_size((w, h));
+        marker.set_position((sprite.position.0 as f32, sprite.position.1 as f32));
+        marker.set_fill_color(Color::TRANSPARENT);
+        marker.set_outline_color(LEGENDARY);
+        marker.set_outline_thickness(2.0);
+        self.window.draw(&marker);
+    }
+
+    // a thin bar over each casting enemy's sprite with the name of what it's casting.
+    fn render_enemy_castbars(&mut self) {
+        let scale = get_scale();
+        let s = |x: u32| scaled(scale, x);
+
+        for enemy in self.gem.get_living_enemies() {
+            let Some(action) = self.gem.actionqueue.get(&enemy).and_then(|q| q.queue.first()) else {
+                continue;
+            };
+            let Some(spell) = action.spell.as_ref().and_then(|sp| self.spellbook.get(sp)) else {
+                continue;
+            };
+            let Some(sprite) = self.anims.active.iter().find(|spr| spr.associated_g_entity == Some(enemy)) else {
+                continue;
+            };
+
+            let width = sprite.desired_width.unwrap_or(sprite.frame_width) as f32;
+            let height = s(10) as f32;
+            let x = sprite.position.0 as f32;
+            let y = sprite.position.1.saturating_sub(s(14)) as f32;
+            let time_total = action.time_action_takes.max(1) as f32;
+            let time_remaining = action.time_remaining.min(action.time_action_takes) as f32;
+            let cast_progress = 1.0 - (time_remaining / time_total);
+
+            let mut base_rect = RectangleShape::new();
+            base_rect.set_size((width, height));
+            base_rect.set_position((x, y));
+            base_rect.set_fill_color(ENCAPSULATION_REGIONS);
+            base_rect.set_outline_color(MAIN_OUTLINE_CLR);
+            base_rect.set_outline_thickness(2.0);
+            self.window.draw(&base_rect);
+
+            let mut inner_rect = RectangleShape::new();
+            inner_rect.set_size((width * cast_progress, height));
+            inner_rect.set_position((x, y));
+            inner_rect.set_fill_color(Color::rgb(spell.colors.fill.0, spell.colors.fill.1, spell.colors.fill.2));
+            self.window.draw(&inner_rect);
+
+            let mut text = Text::new(&spell.name, &self.gbfnt, s(14));
+            let bounds = text.local_bounds();
+            text.set_position((x + (width - bounds.width) / 2.0 - bounds.left, y - bounds.height - bounds.top - s(4) as f32));
+            text.set_fill_color(MAIN_TEXT_CLR);
+            text.set_outline_color(Color::BLACK);
+            text.set_outline_thickness(1.0);
+            self.window.draw(&text);
+        }
+    }
+
+    // debuff icons on the target start at the left under its healthbar, with the seconds left on each.
+    fn render_target_debuffs(&mut self) {
+        let scale = get_scale();
+        let s = |x: u32| scaled(scale, x);
+
+        let Some(target) = self.player_target() else {
+            return;
+        };
+        let Some(dbb) = self.gem.debuffbars.get(&target) else {
+            return;
+        };
+
+        for (i, debuff) in dbb.debuffs.iter().enumerate() {
+            let Some(icon) = self.spellbook.get_by_name(&debuff.name).map(|spell| spell.icon.clone()) else {
+                continue;
+            };
+            let Some(texture) = self.anims.textures.get(&icon) else {
+                continue;
+            };
+            let tex_size = texture.size();
+            let icon_x = s(1403) + i as u32 * s(64);
+            let icon_y = s(647);
+
+            let aspr = crate::animation::AnimatedSprite {
+                texture_id: icon,
+                frame_width: tex_size.x,
+                frame_height: tex_size.y,
+                total_frames: 1,
+                current_frame: 0,
+                frame_time: None,
+                time_accumulator: 0.0,
+                position: (icon_x, icon_y),
+                inanimate: true,
+                strata: 30,
+                desired_width: Some(s(64)),
+                desired_height: Some(s(64)),
+                play_once: false,
+                finished: false,
+                velocity: (0.0, 0.0),
+                lifetime: None,
+                associated_g_entity: None,
+            };
+            if let Some(sprite) = self.anims.get_drawable(&aspr) {
+                self.window.draw(&sprite);
+            }
+
+            let seconds_left = (debuff.time_left as f32 / 1000.0).ceil() as u32;
+            let mut text = Text::new(&seconds_left.to_string(), &self.gbfnt, s(18));
+            let bounds = text.local_bounds();
+            text.set_position((
+                (icon_x + s(62)) as f32 - bounds.width - bounds.left,
+                (icon_y + s(62)) as f32 - bounds.height - bounds.top,
+            ));
+            text.set_fill_color(MAIN_TEXT_CLR);
+            text.set_outline_color(Color::BLACK);
+            text.set_outline_thickness(2.0);
+            self.window.draw(&text);
+        }
+    }
+
     fn render_tooltips(&mut self) {
         let scale = get_scale();
         let s = |x: u32| scaled(scale, x);
@@ -273,6 +427,38 @@ impl Game<'_> {
         self.window.draw(&inner_rect);
     }
 
+    fn render_resourcebar(&mut self, resourcebar: &PResourcebar) {
+        let resource_ratio = resourcebar.gem_entity_id
+            .and_then(|gem_id| self.gem.resources.get(&gem_id))
+            .map(|r| r.curr as f32 / r.max.max(1) as f32)
+            .unwrap_or(0.0);
+
+        let mut base_rect = RectangleShape::new();
+        base_rect.set_size((resourcebar.width as f32, resourcebar.height as f32));
+        base_rect.set_position((resourcebar.x as f32, resourcebar.y as f32));
+        base_rect.set_fill_color(Color::rgb(
+            resourcebar.base_colors.fill.0,
+            resourcebar.base_colors.fill.1,
+            resourcebar.base_colors.fill.2,
+        ));
+        if let Some(outline) = resourcebar.base_colors.outline {
+            base_rect.set_outline_color(Color::rgb(outline.0, outline.1, outline.2));
+            base_rect.set_outline_thickness(2.0);
+        }
+        self.window.draw(&base_rect);
+
+        let inner_width = (resourcebar.width as f32 * resource_ratio.min(1.0)).max(0.0);
+        let mut inner_rect = RectangleShape::new();
+        inner_rect.set_size((inner_width, resourcebar.height as f32));
+        inner_rect.set_position((resourcebar.x as f32, resourcebar.y as f32));
+        inner_rect.set_fill_color(Color::rgb(
+            resourcebar.inner_colors.fill.0,
+            resourcebar.inner_colors.fill.1,
+            resourcebar.inner_colors.fill.2,
+        ));
+        self.window.draw(&inner_rect);
+    }
+
     fn render_player_castbar(&mut self) {
         let player_id_gem = self.gem.player_id.unwrap();
         let player_id_em = self.em.get_player_id().unwrap();
@@ -293,7 +479,7 @@ impl Game<'_> {
 
         let castbar = self.em.castbars.get(&player_id_em).unwrap();
         let spell = current_action.spell.as_ref().unwrap().clone();
-        let spell_data = crate::g_properties::get_spell_data(spell.clone()).unwrap();
+        let spell_data = self.spellbook.get(&spell).unwrap().clone();
         let time_total = current_action.time_action_takes.max(1) as f32;
         let time_remaining = current_action.time_remaining.min(current_action.time_action_takes) as f32;
         let cast_progress = 1.0 - (time_remaining / time_total);
@@ -341,13 +527,13 @@ impl Game<'_> {
         self.window.draw(&inner_rect);
 
         // spell icon
-        if let Some(texture) = self.anims.textures.get(&current_action.action_tag) {
+        if let Some(texture) = self.anims.textures.get(&spell_data.icon) {
             let tex_size = texture.size();
             let icon_size = castbar.height;
 
             // Create a temporary AnimatedSprite descriptor
             let aspr = crate::animation::AnimatedSprite {
-                texture_id: current_action.action_tag.clone(),
+                texture_id: spell_data.icon.clone(),
                 frame_width: tex_size.x,
                 frame_height: tex_size.y,
                 total_frames: 1,
@@ -372,6 +558,598 @@ impl Game<'_> {
         }
     }
 
+    // darkens spell buttons while their spell (or the gcd) recovers, clearing from the top.
+    fn render_cooldown_sweeps(&mut self) {
+        let Some(player_id) = self.gem.player_id else {
+            return;
+        };
+        let Some(cooldowns) = self.gem.cooldowns.get(&player_id) else {
+            return;
+        };
+
+        for eid in self.em.get_all_buttons() {
+            let Some(sp) = self.em.get_pclickable_non_mut(eid)
+                .and_then(|cb| crate::button_definitions::spell_for_action(&cb.action)) else {
+                continue;
+            };
+            let Some(rect) = self.em.get_button_rect_non_mut(eid) else {
+                continue;
+            };
+
+            let spell_cd = cooldowns.spells.get(&sp).cloned().unwrap_or(crate::g_properties::GPCooldown::ready());
+            let (fraction, remaining) = if spell_cd.remaining >= cooldowns.global.remaining {
+                (spell_cd.fraction(), spell_cd.remaining)
+            } else {
+                (cooldowns.global.fraction(), cooldowns.global.remaining)
+            };
+            if remaining == 0 {
+                continue;
+            }
+
+            let swept_height = rect.height as f32 * fraction;
+            let mut sweep = RectangleShape::new();
+            sweep.set_size((rect.width as f32, swept_height));
+            sweep.set_position((rect.x as f32, (rect.y + rect.height) as f32 - swept_height));
+            sweep.set_fill_color(Color::rgba(0, 0, 0, 160));
+            self.window.draw(&sweep);
+
+            let label = format!("{:.1}", remaining as f32 / 1000.0);
+            let mut text = Text::new(&label, &self.gbfnt, rect.height / 2);
+            let bounds = text.local_bounds();
+            text.set_position((
+                rect.x as f32 + (rect.width as f32 - bounds.width) / 2.0 - bounds.left,
+                rect.y as f32 + (rect.height as f32 - bounds.height) / 2.0 - bounds.top,
+            ));
+            text.set_fill_color(MAIN_TEXT_CLR);
+            text.set_outline_color(Color::BLACK);
+            text.set_outline_thickness(1.0);
+            self.window.draw(&text);
+        }
+    }
+
+    // line numbers, selection, text and caret of the script editor, drawn over its region's rect.
+    fn render_code_editor(&mut self) {
+        if !self.code_editor_visible() {
+            return;
+        }
+        let Some(editor) = self.code_editor().cloned() else {
+            return;
+        };
+        let scale = get_scale();
+        let s = |x: u32| scaled(scale, x) as f32;
+        let m = self.editor_metrics(&editor);
+
+        let mut gutter = RectangleShape::new();
+        gutter.set_size((m.text_x - editor.x as f32 - s(6), editor.height as f32));
+        gutter.set_position((editor.x as f32, editor.y as f32));
+        gutter.set_fill_color(ALT_BASE);
+        self.window.draw(&gutter);
+
+        let selection = editor.selection();
+        let last = (editor.scroll + m.visible_lines).min(editor.lines.len());
+        let (tokens, _) = crate::script::lex(&editor.text());
+        let paused_line = self.paused_line().filter(|_| self.script.run_revision == editor.revision);
+        for (row, line_no) in (editor.scroll..last).enumerate() {
+            let y = m.text_y + row as f32 * m.line_height;
+
+            if paused_line == Some(line_no as u32 + 1) {
+                let mut band = RectangleShape::new();
+                band.set_size(((editor.x + editor.width) as f32 - m.text_x, m.line_height));
+                band.set_position((m.text_x, y));
+                band.set_fill_color(PAUSED_LINE_COLOR);
+                self.window.draw(&band);
+            }
+
+            if editor.breakpoints.binary_search(&line_no).is_ok() {
+                let radius = s(5);
+                let mut dot = CircleShape::new(radius, 16);
+                dot.set_position((editor.x as f32 + s(6), y + m.line_height / 2.0 - radius));
+                dot.set_fill_color(BREAKPOINT_COLOR);
+                self.window.draw(&dot);
+            }
+
+            if let Some((start, end)) = selection.filter(|(start, end)| (start.line..=end.line).contains(&line_no)) {
+                let from = if line_no == start.line { start.col } else { 0 };
+                // lines the selection runs past include their line break
+                let to = if line_no == end.line { end.col } else { editor.line_len(line_no) + 1 };
+                let from = from.max(editor.scroll_x);
+                let to = to.min(editor.scroll_x + m.visible_cols);
+                if to > from {
+                    let mut highlight = RectangleShape::new();
+                    highlight.set_size(((to - from) as f32 * m.char_width, m.line_height));
+                    highlight.set_position((m.text_x + (from - editor.scroll_x) as f32 * m.char_width, y));
+                    highlight.set_fill_color(SELECTION_COLOR);
+                    self.window.draw(&highlight);
+                }
+            }
+
+            let mut number = Text::new(&(line_no + 1).to_string(), &self.fnt, m.char_size);
+            let width = number.local_bounds().width;
+            number.set_position((m.text_x - s(14) - width, y));
+            number.set_fill_color(if line_no == editor.caret.line { MAIN_TEXT_CLR } else { OFF_TEXT_CLR });
+            self.window.draw(&number);
+
+            // each token in its own color, cut to the visible columns
+            let chars: Vec<char> = editor.lines[line_no].chars().collect();
+            for token in tokens.iter().filter(|t| t.span.line as usize == line_no + 1) {
+                let start = (token.span.col as usize - 1).max(editor.scroll_x);
+                let end = (token.span.col as usize - 1 + token.span.len as usize)
+                    .min(editor.scroll_x + m.visible_cols)
+                    .min(chars.len());
+                if end <= start {
+                    continue;
+                }
+                let piece: String = chars[start..end].iter().collect();
+                let mut text = Text::new(&piece, &self.fnt, m.char_size);
+                text.set_position((m.text_x + (start - editor.scroll_x) as f32 * m.char_width, y));
+                text.set_fill_color(self.script_token_color(token));
+                self.window.draw(&text);
+            }
+        }
+
+        // compile errors in red, errors of the running script in yellow as long as it is
+        // still the text that is running.
+        let mut problems = Vec::new();
+        if let Some(e) = &self.script.diagnostic {
+            problems.push((e.clone(), DAMAGE_TAKEN_COLOR));
+        }
+        if let Some(e) = self.script.error.as_ref().filter(|_| self.script.run_revision == editor.revision) {
+            problems.push((e.clone(), WARNING_COLOR));
+        }
+        let mx = self.user_input_cache[InputSlot::MouseX as usize] as f32;
+        let my = self.user_input_cache[InputSlot::MouseY as usize] as f32;
+        let mut hovered: Option<String> = None;
+        for (problem, color) in problems {
+            let row = (problem.span.line as usize).saturating_sub(1).checked_sub(editor.scroll);
+            let Some(row) = row.filter(|row| *row < m.visible_lines) else {
+                continue;
+            };
+            let col = (problem.span.col as usize).saturating_sub(1).saturating_sub(editor.scroll_x);
+            let x = m.text_x + col as f32 * m.char_width;
+            let y = m.text_y + row as f32 * m.line_height;
+            let width = problem.span.len.max(1) as f32 * m.char_width;
+
+            let mut underline = RectangleShape::new();
+            underline.set_size((width, s(2)));
+            underline.set_position((x, y + m.line_height - s(2)));
+            underline.set_fill_color(color);
+            self.window.draw(&underline);
+
+            if mx >= x && mx <= x + width && my >= y && my <= y + m.line_height {
+                hovered = Some(problem.message.clone());
+            }
+        }
+
+        let caret_shown = editor.focused && ((self.time_elapsed * 2.0) as u32).is_multiple_of(2);
+        let caret_row = editor.caret.line.checked_sub(editor.scroll).filter(|row| *row < m.visible_lines);
+        let caret_col = editor.caret.col.checked_sub(editor.scroll_x).filter(|col| *col <= m.visible_cols);
+        if let (true, Some(row), Some(col)) = (caret_shown, caret_row, caret_col) {
+            let mut caret = RectangleShape::new();
+            caret.set_size((s(2), m.line_height));
+            caret.set_position((m.text_x + col as f32 * m.char_width, m.text_y + row as f32 * m.line_height));
+            caret.set_fill_color(MAIN_TEXT_CLR);
+            self.window.draw(&caret);
+        }
+
+        if let Some(message) = hovered {
+            self.render_editor_popup(&editor, &[(message, DAMAGE_TAKEN_COLOR)], None, (mx, my + m.line_height));
+        }
+
+        // autocomplete under the caret
+        if let (false, Some(row)) = (editor.completions.is_empty(), caret_row) {
+            let first = editor.completion_index.saturating_sub(crate::code_editor::COMPLETION_ROWS - 1);
+            let rows: Vec<(String, Color)> = editor.completions.iter()
+                .skip(first)
+                .take(crate::code_editor::COMPLETION_ROWS)
+                .map(|item| {
+                    let (r, g, b) = item.color;
+                    (format!("{:<16}{}", item.label, item.detail), Color::rgb(r, g, b))
+                })
+                .collect();
+            let col = editor.caret.col.saturating_sub(editor.completion_prefix).saturating_sub(editor.scroll_x);
+            let at = (m.text_x + col as f32 * m.char_width, m.text_y + (row + 1) as f32 * m.line_height);
+            self.render_editor_popup(&editor, &rows, Some(editor.completion_index - first), at);
+        }
+
+        if editor.lines.len() > m.visible_lines {
+            let total = editor.lines.len() as f32;
+            let mut thumb = RectangleShape::new();
+            thumb.set_size((s(6), editor.height as f32 * m.visible_lines as f32 / total));
+            thumb.set_position((
+                (editor.x + editor.width) as f32 - s(8),
+                editor.y as f32 + editor.height as f32 * editor.scroll as f32 / total,
+            ));
+            thumb.set_fill_color(OFF_OUTLINE_CLR);
+            self.window.draw(&thumb);
+        }
+    }
+
+    // what the stopped run knows: its variables, and what it has asked of the game so far.
+    fn render_debug_panel(&mut self) {
+        let Some(id) = self.em.get_id_by_tag(crate::debugger::DEBUG_PANEL_TAG.to_string()) else {
+            return;
+        };
+        let (Some(program), Some(vm)) = (self.script.program.clone(), self.script.suspended.clone()) else {
+            return;
+        };
+        let Some(panel) = self.em.get_prects_non_mut(id).and_then(|rects| rects.first()).cloned() else {
+            return;
+        };
+        let scale = get_scale();
+        let s = |x: u32| scaled(scale, x) as f32;
+        let char_size = scale * 12;
+        let line_height = s(16);
+        let max_width = panel.width as f32 - s(20);
+
+        let mut rows: Vec<(String, Color)> = vec![
+            (format!("Line {}", vm.line(&program).unwrap_or(0)), WARNING_COLOR),
+            (String::new(), MAIN_TEXT_CLR),
+            ("Variables".to_string(), BUILTIN_COLOR),
+        ];
+        let variables = vm.variables(&program);
+        if variables.is_empty() {
+            rows.push(("none yet".to_string(), OFF_TEXT_CLR));
+        }
+        rows.extend(variables.iter().map(|(name, value)| (format!("{} = {}", name, value), MAIN_TEXT_CLR)));
+        rows.push((String::new(), MAIN_TEXT_CLR));
+        rows.push(("Game state".to_string(), BUILTIN_COLOR));
+        if vm.queries.is_empty() {
+            rows.push(("nothing asked yet".to_string(), OFF_TEXT_CLR));
+        }
+        rows.extend(vm.queries.iter().map(|(query, value)| (format!("{} = {}", query, value), MAIN_TEXT_CLR)));
+
+        // everything above the buttons
+        let bottom = s(800);
+        let mut y = panel.y as f32 + s(10);
+        for (row, color) in rows {
+            for line in wrap_text(&row, &self.fnt, char_size, max_width).lines() {
+                if y + line_height > bottom {
+                    return;
+                }
+                let mut text = Text::new(line, &self.fnt, char_size);
+                text.set_position((panel.x as f32 + s(10), y));
+                text.set_fill_color(color);
+                self.window.draw(&text);
+                y += line_height;
+            }
+            if row.is_empty() {
+                y += line_height / 2.0;
+            }
+        }
+    }
+
+    // the rows of the script library; its panel and buttons are em entities.
+    fn render_script_library(&mut self) {
+        if !self.script_library_open() {
+            return;
+        }
+        let scale = get_scale();
+        let s = |x: u32| scaled(scale, x) as f32;
+        let char_size = scale * 16;
+        let row_height = s(30);
+        let library = &self.script_library;
+
+        let mut title = Text::new("Scripts", &self.gbfnt, scale * 20);
+        title.set_position((s(20), s(128)));
+        title.set_fill_color(MAIN_TEXT_CLR);
+        self.window.draw(&title);
+
+        let last = (library.scroll + crate::script_library::LIBRARY_ROWS).min(library.names.len());
+        for (row, i) in (library.scroll..last).enumerate() {
+            let name = &library.names[i];
+            let y = s(165) + row as f32 * row_height;
+            if i == library.selected {
+                let mut band = RectangleShape::new();
+                band.set_size((s(600), row_height));
+                band.set_position((s(15), y));
+                band.set_fill_color(SELECTION_COLOR);
+                self.window.draw(&band);
+            }
+
+            let renaming = library.renaming.as_ref().filter(|_| i == library.selected);
+            let shown = match renaming {
+                Some(new_name) => format!("{}|", new_name),
+                None => name.clone(),
+            };
+            let mut text = Text::new(&shown, &self.fnt, char_size);
+            text.set_position((s(25), y + s(5)));
+            text.set_fill_color(MAIN_TEXT_CLR);
+            self.window.draw(&text);
+
+            if library.active.as_ref() == Some(name) && renaming.is_none() {
+                let mut tag = Text::new("in editor", &self.fnt, scale * 12);
+                let width = tag.local_bounds().width;
+                tag.set_position((s(605) - width, y + s(8)));
+                tag.set_fill_color(HEAL_COLOR);
+                self.window.draw(&tag);
+            }
+        }
+
+        if library.names.len() > crate::script_library::LIBRARY_ROWS {
+            let more = format!("{} of {}, use the arrow keys to see the rest", last - library.scroll, library.names.len());
+            let mut text = Text::new(&more, &self.fnt, scale * 12);
+            text.set_position((s(25), s(165) + crate::script_library::LIBRARY_ROWS as f32 * row_height + s(4)));
+            text.set_fill_color(OFF_TEXT_CLR);
+            self.window.draw(&text);
+        }
+    }
+
+    // the challenge list, and below it what the selected one asks for and its best runs.
+    fn render_challenges(&mut self) {
+        if !self.challenge_panel_open() {
+            return;
+        }
+        let scale = get_scale();
+        let s = |x: u32| scaled(scale, x) as f32;
+        let char_size = scale * 16;
+        let small = scale * 12;
+        let row_height = s(30);
+        let book = &self.challenges;
+
+        let mut title = Text::new("Challenges", &self.gbfnt, scale * 20);
+        title.set_position((s(20), s(128)));
+        title.set_fill_color(MAIN_TEXT_CLR);
+        self.window.draw(&title);
+
+        for (i, challenge) in book.challenges.iter().enumerate() {
+            let y = s(165) + i as f32 * row_height;
+            if i == book.selected {
+                let mut band = RectangleShape::new();
+                band.set_size((s(600), row_height));
+                band.set_position((s(15), y));
+                band.set_fill_color(SELECTION_COLOR);
+                self.window.draw(&band);
+            }
+            let mut text = Text::new(&challenge.name, &self.fnt, char_size);
+            text.set_position((s(25), y + s(5)));
+            text.set_fill_color(MAIN_TEXT_CLR);
+            self.window.draw(&text);
+
+            if let Some(best) = self.leaderboard.entries(&challenge.id).first() {
+                let mut tag = Text::new(&format!("best {}", best.score), &self.fnt, small);
+                let width = tag.local_bounds().width;
+                tag.set_position((s(605) - width, y + s(8)));
+                tag.set_fill_color(LEGENDARY);
+                self.window.draw(&tag);
+            }
+        }
+
+        let Some(challenge) = book.challenges.get(book.selected) else {
+            return;
+        };
+        let mut lines: Vec<(String, Color)> = vec![(challenge.description.clone(), MAIN_TEXT_CLR)];
+        lines.push((format!("Time limit {} s, seed {}", challenge.time_limit_ms as f32 / 1000.0, challenge.seed), OFF_TEXT_CLR));
+        lines.extend(challenge.constraints.describe().into_iter().map(|line| (line, WARNING_COLOR)));
+        lines.push((String::new(), MAIN_TEXT_CLR));
+        lines.extend(challenge.objectives.iter().map(|o| (format!("- {}", challenge.describe(o, &self.bestiary)), MAIN_TEXT_CLR)));
+        lines.push((String::new(), MAIN_TEXT_CLR));
+        let entries = self.leaderboard.entries(&challenge.id);
+        if entries.is_empty() {
+            lines.push(("No runs passed yet".to_string(), OFF_TEXT_CLR));
+        }
+        for (place, entry) in entries.iter().take(5).enumerate() {
+            let line = format!("#{} {} - {:.1} s, {} damage, {} tokens, {}",
+                place + 1, entry.score, entry.time_ms as f32 / 1000.0, entry.damage, entry.code_size, entry.script);
+            lines.push((line, if place == 0 { LEGENDARY } else { MAIN_TEXT_CLR }));
+        }
+
+        let mut y = s(165) + book.challenges.len() as f32 * row_height + s(20);
+        for (line, color) in lines {
+            for wrapped in wrap_text(&line, &self.fnt, small, s(590)).lines() {
+                let mut text = Text::new(wrapped, &self.fnt, small);
+                text.set_position((s(25), y));
+                text.set_fill_color(color);
+                self.window.draw(&text);
+                y += s(18);
+            }
+            if line.is_empty() {
+                y += s(10);
+            }
+        }
+    }
+
+    // the dummy's setup, then dps, damage per spell and debuff uptimes; the buttons are em items.
+    fn render_training(&mut self) {
+        if !self.training_panel_open() {
+            return;
+        }
+        let scale = get_scale();
+        let s = |x: u32| scaled(scale, x) as f32;
+        let char_size = scale * 16;
+        let small = scale * 12;
+        let now = self.time_elapsed_ms;
+        let training = &self.training;
+        let stats = &training.stats;
+
+        let mut title = Text::new("Training", &self.gbfnt, scale * 20);
+        title.set_position((s(20), s(128)));
+        title.set_fill_color(MAIN_TEXT_CLR);
+        self.window.draw(&title);
+
+        let dummy = match training.dummy.and_then(|id| self.gem.stats.get(&id)) {
+            Some(dummy) => format!("Training dummy, level {}, {} health", training.level, dummy.health_max),
+            None => format!("No dummy, it spawns at level {}", training.level),
+        };
+        let mut text = Text::new(&dummy, &self.fnt, char_size);
+        text.set_position((s(25), s(165)));
+        text.set_fill_color(if training.dummy.is_some() { MAIN_TEXT_CLR } else { OFF_TEXT_CLR });
+        self.window.draw(&text);
+
+        for (i, school) in crate::g_properties::DamageSchool::ALL.iter().enumerate() {
+            let y = s(crate::training::RESISTANCE_ROWS_TOP) + i as f32 * s(crate::training::RESISTANCE_ROW_HEIGHT);
+            let mut name = Text::new(&format!("{} resistance", school.name()), &self.fnt, char_size);
+            name.set_position((s(25), y + s(5)));
+            name.set_fill_color(crate::systems::school_color(*school));
+            self.window.draw(&name);
+
+            let mut value = Text::new(&format!("{}%", training.resistance(*school)), &self.fnt, char_size);
+            let width = value.local_bounds().width;
+            value.set_position((s(605) - width, y + s(5)));
+            value.set_fill_color(MAIN_TEXT_CLR);
+            self.window.draw(&value);
+        }
+
+        let mut lines: Vec<(String, Color)> = vec![
+            (format!("Current DPS  {:.1}", stats.current_dps(now)), LEGENDARY),
+            (format!("Average DPS  {:.1}", stats.average_dps(now)), MAIN_TEXT_CLR),
+            (format!("{} damage in {:.1} s", stats.damage, stats.elapsed_ms(now) as f32 / 1000.0), OFF_TEXT_CLR),
+        ];
+        if stats.started_ms.is_none() {
+            lines.push(("The numbers start with your first hit or debuff on the dummy".to_string(), OFF_TEXT_CLR));
+        }
+        let mut y = s(340);
+        for (line, color) in lines {
+            let mut text = Text::new(&line, &self.fnt, char_size);
+            text.set_position((s(25), y));
+            text.set_fill_color(color);
+            self.window.draw(&text);
+            y += s(26);
+        }
+
+        // one row per spell, columns at these x offsets
+        let columns = [25, 260, 350, 430, 500, 555];
+        let header = ["Spell", "Damage", "DPS", "Share", "Hits", "Crit"];
+        let elapsed_secs = (stats.elapsed_ms(now) as f32 / 1000.0).max(1.0);
+        let mut rows: Vec<([String; 6], Color)> = vec![(header.map(|h| h.to_string()), OFF_TEXT_CLR)];
+        for (spell, damage) in &stats.spells {
+            rows.push(([
+                spell.clone(),
+                damage.damage.to_string(),
+                format!("{:.1}", damage.damage as f32 / elapsed_secs),
+                format!("{:.0}%", damage.damage as f32 * 100.0 / stats.damage.max(1) as f32),
+                damage.hits.to_string(),
+                format!("{:.0}%", damage.crits as f32 * 100.0 / damage.hits.max(1) as f32),
+            ], MAIN_TEXT_CLR));
+        }
+        rows.push(([String::new(), String::new(), String::new(), String::new(), String::new(), String::new()], MAIN_TEXT_CLR));
+        rows.push((["Debuff".to_string(), "Uptime".to_string(), String::new(), String::new(), String::new(), String::new()], OFF_TEXT_CLR));
+        for debuff in stats.uptime_ms.keys() {
+            rows.push(([debuff.clone(), format!("{:.1}%", stats.uptime_pct(debuff, now)), String::new(), String::new(), String::new(), String::new()], MAIN_TEXT_CLR));
+        }
+
+        y += s(14);
+        for (cells, color) in rows {
+            if y > s(790) {
+                break;
+            }
+            for (cell, x) in cells.iter().zip(columns) {
+                let mut text = Text::new(cell, &self.fnt, small);
+                text.set_position((s(x), y));
+                text.set_fill_color(color);
+                self.window.draw(&text);
+            }
+            y += s(20);
+        }
+    }
+
+    // the running challenge's clock and objectives, top left of the battlefield.
+    fn render_challenge_hud(&mut self) {
+        let Some(run) = self.challenge.as_ref() else {
+            return;
+        };
+        let Some(challenge) = self.challenges.challenges.get(run.index) else {
+            return;
+        };
+        let scale = get_scale();
+        let s = |x: u32| scaled(scale, x) as f32;
+        let elapsed = self.time_elapsed_ms.saturating_sub(run.started_ms);
+
+        let mut lines = vec![(format!("{}  {:.1} / {} s", challenge.name, elapsed as f32 / 1000.0, challenge.time_limit_ms as f32 / 1000.0), LEGENDARY)];
+        for objective in &challenge.objectives {
+            let status = run.status(objective, elapsed, self.combat_stats.damage_dealt, self.is_defeated(), false);
+            lines.push((challenge.describe(objective, &self.bestiary), crate::challenge::status_color(status)));
+        }
+        for (i, (line, color)) in lines.iter().enumerate() {
+            let mut text = Text::new(line, &self.fnt, scale * 14);
+            text.set_position((s(886), s(20) + i as f32 * s(20)));
+            text.set_fill_color(*color);
+            text.set_outline_color(Color::BLACK);
+            text.set_outline_thickness(1.0);
+            self.window.draw(&text);
+        }
+    }
+
+    // a box of lines at `at`, kept inside the editor, with one row highlighted if selected is set.
+    fn render_editor_popup(&mut self, editor: &PCodeEditor, rows: &[(String, Color)], selected: Option<usize>, at: (f32, f32)) {
+        let scale = get_scale();
+        let s = |x: u32| scaled(scale, x) as f32;
+        let m = self.editor_metrics(editor);
+        let char_size = scale * 14;
+        let pad = s(6);
+
+        let max_width = editor.width as f32 - 2.0 * pad;
+        let wrapped: Vec<(String, Color)> = rows.iter()
+            .flat_map(|(line, color)| {
+                wrap_text(line, &self.fnt, char_size, max_width - 2.0 * pad)
+                    .lines()
+                    .map(|l| (l.to_string(), *color))
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+        let width = wrapped.iter()
+            .map(|(line, _)| Text::new(line, &self.fnt, char_size).local_bounds().width)
+            .fold(0.0, f32::max) + 2.0 * pad;
+        let height = wrapped.len() as f32 * m.line_height + 2.0 * pad;
+
+        let right = (editor.x + editor.width) as f32 - pad;
+        let bottom = (editor.y + editor.height) as f32 - pad;
+        let x = at.0.min(right - width).max(editor.x as f32 + pad);
+        let y = if at.1 + height > bottom { at.1 - height - m.line_height } else { at.1 };
+
+        let mut background = RectangleShape::new();
+        background.set_size((width, height));
+        background.set_position((x, y));
+        background.set_fill_color(ALT_BASE);
+        background.set_outline_color(OFF_OUTLINE_CLR);
+        background.set_outline_thickness(1.0);
+        self.window.draw(&background);
+
+        for (i, (line, color)) in wrapped.iter().enumerate() {
+            let row_y = y + pad + i as f32 * m.line_height;
+            if selected == Some(i) {
+                let mut highlight = RectangleShape::new();
+                highlight.set_size((width - 2.0, m.line_height));
+                highlight.set_position((x + 1.0, row_y));
+                highlight.set_fill_color(SELECTION_COLOR);
+                self.window.draw(&highlight);
+            }
+            let mut text = Text::new(line, &self.fnt, char_size);
+            text.set_position((x + pad, row_y));
+            text.set_fill_color(*color);
+            self.window.draw(&text);
+        }
+    }
+
+    // the newest entries of the combat log, bottom up, heals green and damage in the color of its school.
+    fn render_combat_log(&mut self) {
+        let scale = get_scale();
+        let s = |x: u32| scaled(scale, x);
+
+        let Some(region) = self.em.get_id_by_tag("combat_log_region".to_string())
+            .and_then(|id| self.em.rectangles.get(&id))
+            .and_then(|rects| rects.first())
+            .cloned() else {
+            return;
+        };
+
+        let line_height = s(18);
+        let lines = ((region.height - s(10)) / line_height) as usize;
+        let entries: Vec<_> = self.combat_log.recent(lines).collect();
+        let first_y = region.y + region.height - s(5) - entries.len() as u32 * line_height;
+
+        for (i, entry) in entries.iter().enumerate() {
+            let mut text = Text::new(&entry.describe(), &self.fnt, s(12));
+            text.set_position(((region.x + s(6)) as f32, (first_y + i as u32 * line_height) as f32));
+            let color = match entry.school {
+                _ if entry.amount > 0 => HEAL_COLOR,
+                Some(school) => crate::systems::school_color(school),
+                None => MAIN_TEXT_CLR,
+            };
+            text.set_fill_color(color);
+            self.window.draw(&text);
+        }
+    }
+
     fn render_xp_bar(&mut self) {
         let scale_w = self.window_width as f32 / 1920.0;
         let scale_h = self.window_height as f32 / 1080.0;
@@ -382,7 +1160,7 @@ impl Game<'_> {
         let nlxp = self.gem.levels.get(&player_id).unwrap().next_level_xp;
         let player_level = self.gem.levels.get(&player_id).unwrap().curr_level;
         let cxp = self.gem.levels.get(&player_id).unwrap().curr_xp;
-        let inner_width_coef = nlxp / cxp;
+        let xp_fraction = (cxp as f32 / nlxp.max(1) as f32).min(1.0);
 
         let mut base_rect = RectangleShape::new();
         base_rect.set_size((s(502) as f32, s(50) as f32));
@@ -393,7 +1171,7 @@ impl Game<'_> {
         self.window.draw(&base_rect);
         
         let mut inner_rect = RectangleShape::new();
-        inner_rect.set_size((s((502) /  inner_width_coef) as f32, s(50) as f32));
+        inner_rect.set_size((s(502) as f32 * xp_fraction, s(50) as f32));
         inner_rect.set_position((s(10) as f32, s(930) as f32));
         inner_rect.set_fill_color(XP_COLOR);
         self.window.draw(&inner_rect);
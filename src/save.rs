@@ -0,0 +1,82 @@
+use std::fs;
+use std::path::Path;
+use serde::{Serialize, Deserialize};
+use crate::game::*;
+use crate::player_data::PlayerData;
+use crate::script::Feature;
+
+pub static CHARACTER_SAVE_PATH: &str = "./saves/character.json";
+
+// what is kept of the character between sessions.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct CharacterSave {
+    #[serde(default)]
+    pub level: Option<u32>, // None in saves from before levels were kept
+    #[serde(default)]
+    pub xp: u32,
+    #[serde(default)]
+    pub script_features: Vec<Feature>,
+    #[serde(default)]
+    pub active_script: Option<String>, // name in the script library
+}
+
+impl CharacterSave {
+    // Ok(None) when there is no save yet.
+    pub fn load(path: &str) -> Result<Option<CharacterSave>, String> {
+        if !Path::new(path).is_file() {
+            return Ok(None);
+        }
+        let raw = fs::read_to_string(path)
+            .map_err(|e| format!("could not read save {}: {}", path, e))?;
+        serde_json::from_str(&raw)
+            .map(Some)
+            .map_err(|e| format!("could not parse save {}: {}", path, e))
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        if let Some(dir) = Path::new(path).parent() {
+            fs::create_dir_all(dir).map_err(|e| format!("could not create {}: {}", dir.display(), e))?;
+        }
+        let raw = serde_json::to_string_pretty(self).map_err(|e| e.to_string())?;
+        fs::write(path, raw).map_err(|e| format!("could not write save {}: {}", path, e))
+    }
+
+    // the level and xp the character had reached, on top of what it starts as.
+    pub fn restore_progress(&self, data: &mut PlayerData) {
+        if let Some(level) = self.level.filter(|level| *level > 0) {
+            data.level = level;
+            data.xp = self.xp;
+        }
+    }
+}
+
+impl Game<'_> {
+    // a save that can't be read is reported and the character starts fresh instead.
+    pub fn load_character(&mut self) {
+        match CharacterSave::load(CHARACTER_SAVE_PATH) {
+            Ok(Some(save)) => {
+                self.script_unlocks.unlocked = save.script_features;
+                self.script_library.active = save.active_script;
+            }
+            Ok(None) => {}
+            Err(e) => {
+                println!("{}", e);
+                self.floating_message("Could not read the character save", DAMAGE_TAKEN_COLOR);
+            }
+        }
+        self.init_script_unlocks(); // branch to script_unlocks.rs
+    }
+
+    pub fn save_character(&self) {
+        let level = self.gem.player_id.and_then(|pid| self.gem.levels.get(&pid));
+        let save = CharacterSave {
+            level: level.map(|l| l.curr_level),
+            xp: level.map(|l| l.curr_xp).unwrap_or(0),
+            script_features: self.script_unlocks.unlocked.clone(),
+            active_script: self.script_library.active.clone(),
+        };
+        if let Err(e) = save.save(CHARACTER_SAVE_PATH) {
+            println!("{}", e);
+        }
+    }
+}
@@ -0,0 +1,1223 @@
+use std::fmt;
+use serde::{Serialize, Deserialize};
+use crate::script_vm::{Handler, Op, Program, Value};
+
+// the rotation scripting language: source text is tokenized, parsed and compiled to bytecode for
+// script_vm.rs. nothing in here touches the game, see scripting.rs for that.
+//
+//     let threshold = 0.3
+//     if target.health_pct < threshold {
+//         cast("umbra_mortis")
+//     } else if target.debuff("miasma").remaining < 1.5 {
+//         cast("miasma")
+//     }
+//
+//     on_debuff_expired("miasma") {
+//         cast("miasma")
+//     }
+
+// the sample rotation, what a new script library starts with and what the simulator runs.
+pub static DEFAULT_SCRIPT_PATH: &str = "./src/assets/scripts/rotation.txt";
+
+// where in the source something is, lines and columns start at 1.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Span {
+    pub line: u32,
+    pub col: u32,
+    pub len: u32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScriptError {
+    pub span: Span,
+    pub message: String,
+}
+
+impl ScriptError {
+    pub fn new(span: Span, message: impl Into<String>) -> Self {
+        ScriptError { span, message: message.into() }
+    }
+}
+
+impl fmt::Display for ScriptError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.span.line, self.message)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BuiltinKind {
+    Function(usize), // number of arguments
+    Global,
+    Field(&'static str),          // field of the named kind of value
+    Method(&'static str, usize),  // method of the named kind of value, number of arguments
+    Handler(bool),                // event handler, true if it is usually given an argument
+}
+
+// parts of the language a character unlocks as it progresses, see script_unlocks.rs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Feature {
+    Conditionals, // if and else
+    Loops,        // while, for, break and continue
+    Debuffs,      // unit.debuff(spell)
+    Cooldowns,    // cooldown(spell)
+    MultiTarget,  // enemy_count(), nearest_enemy(), set_target() and friends
+    Events,       // on_cast_finished { ... } and the other handlers
+}
+
+impl Feature {
+    pub const ALL: [Feature; 6] = [Feature::Conditionals, Feature::Loops, Feature::Debuffs, Feature::Cooldowns, Feature::MultiTarget, Feature::Events];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Feature::Conditionals => "conditionals",
+            Feature::Loops => "loops",
+            Feature::Debuffs => "debuff queries",
+            Feature::Cooldowns => "cooldown queries",
+            Feature::MultiTarget => "multi-target helpers",
+            Feature::Events => "event handlers",
+        }
+    }
+}
+
+// combat events a script can handle, the game queues the matching handlers as they happen and
+// runs them after the tick's systems, see s_script_events.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScriptEvent {
+    CastFinished, // one of your casts went off, optionally only for one spell
+    DebuffExpired, // one of your debuffs ran out, optionally only for one spell
+    TargetDied,
+    HealthBelow, // your health dropped below the given fraction
+}
+
+impl ScriptEvent {
+    pub const ALL: [ScriptEvent; 4] = [ScriptEvent::CastFinished, ScriptEvent::DebuffExpired, ScriptEvent::TargetDied, ScriptEvent::HealthBelow];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ScriptEvent::CastFinished => "on_cast_finished",
+            ScriptEvent::DebuffExpired => "on_debuff_expired",
+            ScriptEvent::TargetDied => "on_target_died",
+            ScriptEvent::HealthBelow => "on_health_below",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<ScriptEvent> {
+        ScriptEvent::ALL.into_iter().find(|event| event.name() == name)
+    }
+}
+
+// which features a script may use. hints say how to unlock the rest, e.g. "reach level 7".
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct FeatureGate {
+    pub unlocked: Vec<Feature>,
+    pub hints: Vec<(Feature, String)>,
+}
+
+impl FeatureGate {
+    pub fn allows(&self, feature: Feature) -> bool {
+        self.unlocked.contains(&feature)
+    }
+
+    fn check(&self, feature: Option<Feature>, what: &str, span: Span) -> Result<(), ScriptError> {
+        let Some(feature) = feature.filter(|f| !self.allows(*f)) else {
+            return Ok(());
+        };
+        let hint = self.hints.iter()
+            .find(|(f, _)| *f == feature)
+            .map(|(_, hint)| format!(" ({})", hint))
+            .unwrap_or_default();
+        Err(ScriptError::new(span, format!("{} needs {}, which you haven't unlocked yet{}", what, feature.name(), hint)))
+    }
+}
+
+// everything a script can reach outside of its own variables.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Builtin {
+    pub name: &'static str,
+    pub kind: BuiltinKind,
+    pub feature: Option<Feature>, // None for what every script may use
+    pub doc: &'static str,
+}
+
+pub static BUILTINS: &[Builtin] = &[
+    Builtin { name: "cast", kind: BuiltinKind::Function(1), feature: None, doc: "cast(spell) queues a spell on your target, true if it was queued" },
+    Builtin { name: "cooldown", kind: BuiltinKind::Function(1), feature: Some(Feature::Cooldowns), doc: "cooldown(spell) seconds until the spell is ready again" },
+    Builtin { name: "print", kind: BuiltinKind::Function(1), feature: None, doc: "print(value) shows a value under the editor" },
+    Builtin { name: "enemy_count", kind: BuiltinKind::Function(0), feature: Some(Feature::MultiTarget), doc: "enemy_count() how many enemies are alive" },
+    Builtin { name: "nearest_enemy", kind: BuiltinKind::Function(0), feature: Some(Feature::MultiTarget), doc: "nearest_enemy() the closest living enemy, nil if there is none" },
+    Builtin { name: "weakest_enemy", kind: BuiltinKind::Function(0), feature: Some(Feature::MultiTarget), doc: "weakest_enemy() the living enemy with the least health" },
+    Builtin { name: "next_enemy", kind: BuiltinKind::Function(0), feature: Some(Feature::MultiTarget), doc: "next_enemy() the enemy after your target, like pressing tab" },
+    Builtin { name: "set_target", kind: BuiltinKind::Function(1), feature: Some(Feature::MultiTarget), doc: "set_target(unit) makes a living enemy your target, true if it worked" },
+    Builtin { name: "player", kind: BuiltinKind::Global, feature: None, doc: "you" },
+    Builtin { name: "target", kind: BuiltinKind::Global, feature: None, doc: "your target, nil if you have none" },
+    Builtin { name: "health", kind: BuiltinKind::Field("unit"), feature: None, doc: "current health" },
+    Builtin { name: "health_max", kind: BuiltinKind::Field("unit"), feature: None, doc: "maximum health" },
+    Builtin { name: "health_pct", kind: BuiltinKind::Field("unit"), feature: None, doc: "health as a fraction from 0 to 1" },
+    Builtin { name: "mana", kind: BuiltinKind::Field("unit"), feature: None, doc: "current mana" },
+    Builtin { name: "mana_max", kind: BuiltinKind::Field("unit"), feature: None, doc: "maximum mana" },
+    Builtin { name: "alive", kind: BuiltinKind::Field("unit"), feature: None, doc: "true until it dies" },
+    Builtin { name: "casting", kind: BuiltinKind::Field("unit"), feature: None, doc: "true while it has a cast going" },
+    Builtin { name: "gcd", kind: BuiltinKind::Field("unit"), feature: None, doc: "seconds left on the global cooldown" },
+    Builtin { name: "level", kind: BuiltinKind::Field("unit"), feature: None, doc: "character level" },
+    Builtin { name: "chaos", kind: BuiltinKind::Field("unit"), feature: None, doc: "chaos stat" },
+    Builtin { name: "solidity", kind: BuiltinKind::Field("unit"), feature: None, doc: "solidity stat" },
+    Builtin { name: "vitality", kind: BuiltinKind::Field("unit"), feature: None, doc: "vitality stat" },
+    Builtin { name: "haste", kind: BuiltinKind::Field("unit"), feature: None, doc: "haste stat" },
+    Builtin { name: "will", kind: BuiltinKind::Field("unit"), feature: None, doc: "will stat" },
+    Builtin { name: "volatility", kind: BuiltinKind::Field("unit"), feature: None, doc: "volatility stat" },
+    Builtin { name: "debuff", kind: BuiltinKind::Method("unit", 1), feature: Some(Feature::Debuffs), doc: "unit.debuff(spell) your debuff from that spell on the unit" },
+    Builtin { name: "remaining", kind: BuiltinKind::Field("debuff"), feature: None, doc: "seconds left, 0 if it isn't there" },
+    Builtin { name: "stacks", kind: BuiltinKind::Field("debuff"), feature: None, doc: "number of stacks, 0 if it isn't there" },
+    Builtin { name: "active", kind: BuiltinKind::Field("debuff"), feature: None, doc: "true if it is on the unit" },
+    Builtin { name: "on_cast_finished", kind: BuiltinKind::Handler(false), feature: Some(Feature::Events), doc: "on_cast_finished { } runs when one of your casts goes off, on_cast_finished(spell) only for that spell" },
+    Builtin { name: "on_debuff_expired", kind: BuiltinKind::Handler(true), feature: Some(Feature::Events), doc: "on_debuff_expired(spell) { } runs when your debuff from that spell runs out on a unit" },
+    Builtin { name: "on_target_died", kind: BuiltinKind::Handler(false), feature: Some(Feature::Events), doc: "on_target_died { } runs when your target dies" },
+    Builtin { name: "on_health_below", kind: BuiltinKind::Handler(true), feature: Some(Feature::Events), doc: "on_health_below(fraction) { } runs when your health drops below the fraction" },
+    Builtin { name: "event", kind: BuiltinKind::Global, feature: Some(Feature::Events), doc: "inside a handler, what happened" },
+    Builtin { name: "spell", kind: BuiltinKind::Field("event"), feature: None, doc: "the spell the event is about, nil if none" },
+    Builtin { name: "unit", kind: BuiltinKind::Field("event"), feature: None, doc: "the unit it happened to, nil if none" },
+];
+
+pub fn builtin(name: &str) -> Option<&'static Builtin> {
+    BUILTINS.iter().find(|b| b.name == name)
+}
+
+pub static KEYWORDS: &[&str] = &[
+    "let", "if", "else", "while", "for", "in", "break", "continue", "true", "false", "nil", "and", "or", "not",
+];
+
+// ---------------------------------------------------------------- tokens
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TokenKind {
+    Ident(String),
+    Keyword(&'static str),
+    Number(f64),
+    Str(String),
+    Symbol(&'static str),
+    Comment,
+    Invalid,
+    Eof,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub span: Span,
+    pub newline_before: bool,
+}
+
+static SYMBOLS: &[&str] = &[
+    "..", "==", "!=", "<=", ">=", "&&", "||",
+    "(", ")", "{", "}", ".", ",", "=", "<", ">", "+", "-", "*", "/", "%", "!", ";",
+];
+
+// splits source into tokens, comments included so the editor can color them; the parser skips them.
+pub fn tokenize(source: &str) -> Result<Vec<Token>, ScriptError> {
+    let (tokens, error) = lex(source);
+    match error {
+        Some(e) => Err(e),
+        None => Ok(tokens),
+    }
+}
+
+// like tokenize, but keeps going past mistakes so half-typed scripts can still be highlighted.
+// the first mistake is returned alongside.
+pub fn lex(source: &str) -> (Vec<Token>, Option<ScriptError>) {
+    let mut error: Option<ScriptError> = None;
+    let chars: Vec<char> = source.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    let mut line = 1;
+    let mut line_start = 0;
+    let mut newline_before = true;
+
+    while i < chars.len() {
+        let c = chars[i];
+        if c == '\n' {
+            line += 1;
+            line_start = i + 1;
+            newline_before = true;
+            i += 1;
+            continue;
+        }
+        if c.is_whitespace() {
+            i += 1;
+            continue;
+        }
+
+        let start = i;
+        let col = (start - line_start) as u32 + 1;
+        let kind = if c == '/' && chars.get(i + 1) == Some(&'/') {
+            while i < chars.len() && chars[i] != '\n' {
+                i += 1;
+            }
+            TokenKind::Comment
+        } else if c.is_ascii_digit() {
+            while i < chars.len() && chars[i].is_ascii_digit() {
+                i += 1;
+            }
+            // a dot followed by a digit is a fraction, otherwise it could be a range
+            if chars.get(i) == Some(&'.') && chars.get(i + 1).is_some_and(|d| d.is_ascii_digit()) {
+                i += 1;
+                while i < chars.len() && chars[i].is_ascii_digit() {
+                    i += 1;
+                }
+            }
+            let text: String = chars[start..i].iter().collect();
+            TokenKind::Number(text.parse().unwrap_or(0.0))
+        } else if c.is_alphabetic() || c == '_' {
+            while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            let text: String = chars[start..i].iter().collect();
+            match KEYWORDS.iter().find(|k| **k == text) {
+                Some(k) => TokenKind::Keyword(k),
+                None => TokenKind::Ident(text),
+            }
+        } else if c == '"' {
+            i += 1;
+            let mut text = String::new();
+            loop {
+                match chars.get(i) {
+                    None | Some('\n') => {
+                        let span = Span { line, col, len: (i - start) as u32 };
+                        error.get_or_insert(ScriptError::new(span, "text is missing its closing \""));
+                        break;
+                    }
+                    Some('"') => {
+                        i += 1;
+                        break;
+                    }
+                    Some(ch) => {
+                        text.push(*ch);
+                        i += 1;
+                    }
+                }
+            }
+            TokenKind::Str(text)
+        } else if let Some(sym) = SYMBOLS.iter().find(|s| {
+            s.chars().enumerate().all(|(k, sc)| chars.get(i + k) == Some(&sc))
+        }) {
+            i += sym.len();
+            TokenKind::Symbol(sym)
+        } else {
+            let span = Span { line, col, len: 1 };
+            error.get_or_insert(ScriptError::new(span, format!("unexpected character '{}'", c)));
+            i += 1;
+            TokenKind::Invalid
+        };
+
+        tokens.push(Token {
+            kind,
+            span: Span { line, col, len: (i - start) as u32 },
+            newline_before,
+        });
+        newline_before = false;
+    }
+
+    let col = (chars.len() - line_start) as u32 + 1;
+    tokens.push(Token { kind: TokenKind::Eof, span: Span { line, col, len: 1 }, newline_before: true });
+    (tokens, error)
+}
+
+// ---------------------------------------------------------------- syntax tree
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinOp {
+    Add, Sub, Mul, Div, Rem, Eq, Ne, Lt, Le, Gt, Ge,
+}
+
+impl BinOp {
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            BinOp::Add => "+",
+            BinOp::Sub => "-",
+            BinOp::Mul => "*",
+            BinOp::Div => "/",
+            BinOp::Rem => "%",
+            BinOp::Eq => "==",
+            BinOp::Ne => "!=",
+            BinOp::Lt => "<",
+            BinOp::Le => "<=",
+            BinOp::Gt => ">",
+            BinOp::Ge => ">=",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExprKind {
+    Number(f64),
+    Str(String),
+    Bool(bool),
+    Nil,
+    Var(String),
+    Neg(Box<Expr>),
+    Not(Box<Expr>),
+    Binary(BinOp, Box<Expr>, Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Call(String, Vec<Expr>),
+    Field(Box<Expr>, String),
+    Method(Box<Expr>, String, Vec<Expr>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Expr {
+    pub kind: ExprKind,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum StmtKind {
+    Let(String, Expr),
+    Assign(String, Expr),
+    If(Vec<(Expr, Vec<Stmt>)>, Option<Vec<Stmt>>),
+    While(Expr, Vec<Stmt>),
+    For(String, Expr, Expr, Vec<Stmt>),
+    Break,
+    Continue,
+    Expr(Expr),
+    Handler(ScriptEvent, Option<Expr>, Vec<Stmt>), // event, argument, body
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Stmt {
+    pub kind: StmtKind,
+    pub span: Span,
+}
+
+// ---------------------------------------------------------------- parser
+
+// how deep blocks and expressions may nest, deeper ones would overflow the stack while they
+// are parsed and compiled.
+pub static MAX_NESTING: usize = 64;
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+    depth: usize,   // blocks we are inside of
+    nesting: usize, // blocks, brackets and operators we are inside of, see MAX_NESTING
+}
+
+impl Parser {
+    fn peek(&self) -> &Token {
+        &self.tokens[self.pos]
+    }
+
+    fn next(&mut self) -> Token {
+        let token = self.tokens[self.pos].clone();
+        if self.pos < self.tokens.len() - 1 {
+            self.pos += 1;
+        }
+        token
+    }
+
+    fn is_symbol(&self, sym: &str) -> bool {
+        matches!(self.peek().kind, TokenKind::Symbol(s) if s == sym)
+    }
+
+    fn is_keyword(&self, kw: &str) -> bool {
+        matches!(self.peek().kind, TokenKind::Keyword(k) if k == kw)
+    }
+
+    fn eat_symbol(&mut self, sym: &str) -> bool {
+        if self.is_symbol(sym) {
+            self.next();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn eat_keyword(&mut self, kw: &str) -> bool {
+        if self.is_keyword(kw) {
+            self.next();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect_symbol(&mut self, sym: &str) -> Result<Span, ScriptError> {
+        if self.is_symbol(sym) {
+            Ok(self.next().span)
+        } else {
+            Err(self.unexpected(&format!("'{}'", sym)))
+        }
+    }
+
+    fn expect_ident(&mut self, what: &str) -> Result<(String, Span), ScriptError> {
+        match self.peek().kind.clone() {
+            TokenKind::Ident(name) => Ok((name, self.next().span)),
+            _ => Err(self.unexpected(what)),
+        }
+    }
+
+    fn unexpected(&self, wanted: &str) -> ScriptError {
+        let token = self.peek();
+        let found = match &token.kind {
+            TokenKind::Ident(name) => format!("'{}'", name),
+            TokenKind::Keyword(k) => format!("'{}'", k),
+            TokenKind::Number(n) => format!("{}", n),
+            TokenKind::Str(s) => format!("\"{}\"", s),
+            TokenKind::Symbol(s) => format!("'{}'", s),
+            TokenKind::Comment => "a comment".to_string(),
+            TokenKind::Invalid => "an unexpected character".to_string(),
+            TokenKind::Eof => "the end of the script".to_string(),
+        };
+        ScriptError::new(token.span, format!("expected {} but found {}", wanted, found))
+    }
+
+    // one level deeper, the caller puts nesting back once it is done with it.
+    fn nest(&mut self, span: Span, what: &str) -> Result<(), ScriptError> {
+        self.nesting += 1;
+        if self.nesting > MAX_NESTING {
+            return Err(ScriptError::new(span, format!("{} nested too deeply", what)));
+        }
+        Ok(())
+    }
+
+    fn block(&mut self) -> Result<Vec<Stmt>, ScriptError> {
+        let span = self.expect_symbol("{")?;
+        self.nest(span, "blocks")?;
+        self.depth += 1;
+        let mut body = Vec::new();
+        while !self.is_symbol("}") {
+            if self.peek().kind == TokenKind::Eof {
+                return Err(self.unexpected("'}'"));
+            }
+            body.push(self.statement()?);
+        }
+        self.next();
+        self.depth -= 1;
+        self.nesting -= 1;
+        Ok(body)
+    }
+
+    // `on_health_below(0.3) { ... }`, the argument can be left out for some events.
+    fn handler(&mut self, event: ScriptEvent) -> Result<StmtKind, ScriptError> {
+        let span = self.next().span;
+        if self.depth > 0 {
+            return Err(ScriptError::new(span, format!("'{}' has to be at the top of the script, not inside a block", event.name())));
+        }
+        let mut argument = None;
+        if self.eat_symbol("(") {
+            argument = Some(self.expression()?);
+            self.expect_symbol(")")?;
+        }
+        Ok(StmtKind::Handler(event, argument, self.block()?))
+    }
+
+    fn statement(&mut self) -> Result<Stmt, ScriptError> {
+        let start = self.peek().span;
+        let kind = if self.eat_keyword("let") {
+            let (name, _) = self.expect_ident("a variable name")?;
+            self.expect_symbol("=")?;
+            StmtKind::Let(name, self.expression()?)
+        } else if self.eat_keyword("if") {
+            let mut branches = vec![(self.expression()?, self.block()?)];
+            let mut otherwise = None;
+            while self.eat_keyword("else") {
+                if self.eat_keyword("if") {
+                    branches.push((self.expression()?, self.block()?));
+                } else {
+                    otherwise = Some(self.block()?);
+                    break;
+                }
+            }
+            StmtKind::If(branches, otherwise)
+        } else if self.eat_keyword("while") {
+            StmtKind::While(self.expression()?, self.block()?)
+        } else if self.eat_keyword("for") {
+            let (name, _) = self.expect_ident("a loop variable")?;
+            if !self.eat_keyword("in") {
+                return Err(self.unexpected("'in'"));
+            }
+            let from = self.expression()?;
+            self.expect_symbol("..")?;
+            let to = self.expression()?;
+            StmtKind::For(name, from, to, self.block()?)
+        } else if let Some(event) = match &self.peek().kind {
+            TokenKind::Ident(name) => ScriptEvent::from_name(name),
+            _ => None,
+        } {
+            self.handler(event)?
+        } else if self.eat_keyword("break") {
+            StmtKind::Break
+        } else if self.eat_keyword("continue") {
+            StmtKind::Continue
+        } else {
+            let expr = self.expression()?;
+            if self.is_symbol("=") {
+                let ExprKind::Var(name) = expr.kind else {
+                    return Err(ScriptError::new(expr.span, "only variables can be assigned to"));
+                };
+                self.next();
+                StmtKind::Assign(name, self.expression()?)
+            } else {
+                StmtKind::Expr(expr)
+            }
+        };
+        self.eat_symbol(";");
+        Ok(Stmt { kind, span: start })
+    }
+
+    fn expression(&mut self) -> Result<Expr, ScriptError> {
+        let span = self.peek().span;
+        self.nest(span, "expression")?;
+        let expr = self.or()?;
+        self.nesting -= 1;
+        Ok(expr)
+    }
+
+    fn or(&mut self) -> Result<Expr, ScriptError> {
+        let outer = self.nesting;
+        let mut left = self.and()?;
+        while self.eat_keyword("or") || self.eat_symbol("||") {
+            self.nest(left.span, "expression")?;
+            let right = self.and()?;
+            let span = left.span;
+            left = Expr { kind: ExprKind::Or(Box::new(left), Box::new(right)), span };
+        }
+        self.nesting = outer;
+        Ok(left)
+    }
+
+    fn and(&mut self) -> Result<Expr, ScriptError> {
+        let outer = self.nesting;
+        let mut left = self.comparison()?;
+        while self.eat_keyword("and") || self.eat_symbol("&&") {
+            self.nest(left.span, "expression")?;
+            let right = self.comparison()?;
+            let span = left.span;
+            left = Expr { kind: ExprKind::And(Box::new(left), Box::new(right)), span };
+        }
+        self.nesting = outer;
+        Ok(left)
+    }
+
+    fn comparison(&mut self) -> Result<Expr, ScriptError> {
+        let outer = self.nesting;
+        let mut left = self.additive()?;
+        loop {
+            let op = match &self.peek().kind {
+                TokenKind::Symbol("==") => BinOp::Eq,
+                TokenKind::Symbol("!=") => BinOp::Ne,
+                TokenKind::Symbol("<") => BinOp::Lt,
+                TokenKind::Symbol("<=") => BinOp::Le,
+                TokenKind::Symbol(">") => BinOp::Gt,
+                TokenKind::Symbol(">=") => BinOp::Ge,
+                _ => break,
+            };
+            let span = self.next().span;
+            self.nest(span, "expression")?;
+            let right = self.additive()?;
+            left = Expr { kind: ExprKind::Binary(op, Box::new(left), Box::new(right)), span };
+        }
+        self.nesting = outer;
+        Ok(left)
+    }
+
+    fn additive(&mut self) -> Result<Expr, ScriptError> {
+        let outer = self.nesting;
+        let mut left = self.multiplicative()?;
+        loop {
+            // a line starting with + or - is a new statement, e.g. `-x` on its own
+            if self.peek().newline_before {
+                break;
+            }
+            let op = match &self.peek().kind {
+                TokenKind::Symbol("+") => BinOp::Add,
+                TokenKind::Symbol("-") => BinOp::Sub,
+                _ => break,
+            };
+            let span = self.next().span;
+            self.nest(span, "expression")?;
+            let right = self.multiplicative()?;
+            left = Expr { kind: ExprKind::Binary(op, Box::new(left), Box::new(right)), span };
+        }
+        self.nesting = outer;
+        Ok(left)
+    }
+
+    fn multiplicative(&mut self) -> Result<Expr, ScriptError> {
+        let outer = self.nesting;
+        let mut left = self.unary()?;
+        loop {
+            let op = match &self.peek().kind {
+                TokenKind::Symbol("*") => BinOp::Mul,
+                TokenKind::Symbol("/") => BinOp::Div,
+                TokenKind::Symbol("%") => BinOp::Rem,
+                _ => break,
+            };
+            let span = self.next().span;
+            self.nest(span, "expression")?;
+            let right = self.unary()?;
+            left = Expr { kind: ExprKind::Binary(op, Box::new(left), Box::new(right)), span };
+        }
+        self.nesting = outer;
+        Ok(left)
+    }
+
+    fn unary(&mut self) -> Result<Expr, ScriptError> {
+        let span = self.peek().span;
+        if self.eat_symbol("-") {
+            self.nest(span, "expression")?;
+            let inner = self.unary()?;
+            self.nesting -= 1;
+            return Ok(Expr { kind: ExprKind::Neg(Box::new(inner)), span });
+        }
+        if self.eat_keyword("not") || self.eat_symbol("!") {
+            self.nest(span, "expression")?;
+            let inner = self.unary()?;
+            self.nesting -= 1;
+            return Ok(Expr { kind: ExprKind::Not(Box::new(inner)), span });
+        }
+        self.postfix()
+    }
+
+    // calls and field accesses don't continue onto the next line.
+    fn postfix(&mut self) -> Result<Expr, ScriptError> {
+        let outer = self.nesting;
+        let mut expr = self.primary()?;
+        loop {
+            if self.peek().newline_before {
+                break;
+            }
+            if self.is_symbol("(") || self.is_symbol(".") {
+                self.nest(expr.span, "expression")?;
+            }
+            if self.is_symbol("(") {
+                let ExprKind::Var(name) = &expr.kind else {
+                    return Err(ScriptError::new(self.peek().span, "only built-in functions can be called"));
+                };
+                let name = name.clone();
+                let args = self.arguments()?;
+                expr = Expr { kind: ExprKind::Call(name, args), span: expr.span };
+            } else if self.eat_symbol(".") {
+                let (name, span) = self.expect_ident("a field name")?;
+                if self.is_symbol("(") && !self.peek().newline_before {
+                    let args = self.arguments()?;
+                    expr = Expr { kind: ExprKind::Method(Box::new(expr), name, args), span };
+                } else {
+                    expr = Expr { kind: ExprKind::Field(Box::new(expr), name), span };
+                }
+            } else {
+                break;
+            }
+        }
+        self.nesting = outer;
+        Ok(expr)
+    }
+
+    fn arguments(&mut self) -> Result<Vec<Expr>, ScriptError> {
+        self.expect_symbol("(")?;
+        let mut args = Vec::new();
+        if self.eat_symbol(")") {
+            return Ok(args);
+        }
+        loop {
+            args.push(self.expression()?);
+            if self.eat_symbol(")") {
+                return Ok(args);
+            }
+            self.expect_symbol(",")?;
+        }
+    }
+
+    fn primary(&mut self) -> Result<Expr, ScriptError> {
+        let token = self.peek().clone();
+        let kind = match token.kind {
+            TokenKind::Number(n) => ExprKind::Number(n),
+            TokenKind::Str(s) => ExprKind::Str(s),
+            TokenKind::Keyword("true") => ExprKind::Bool(true),
+            TokenKind::Keyword("false") => ExprKind::Bool(false),
+            TokenKind::Keyword("nil") => ExprKind::Nil,
+            TokenKind::Ident(name) => ExprKind::Var(name),
+            TokenKind::Symbol("(") => {
+                self.next();
+                let inner = self.expression()?;
+                self.expect_symbol(")")?;
+                return Ok(inner);
+            }
+            _ => return Err(self.unexpected("a value")),
+        };
+        self.next();
+        Ok(Expr { kind, span: token.span })
+    }
+}
+
+pub fn parse(source: &str) -> Result<Vec<Stmt>, ScriptError> {
+    let tokens = tokenize(source)?
+        .into_iter()
+        .filter(|t| t.kind != TokenKind::Comment)
+        .collect();
+    let mut parser = Parser { tokens, pos: 0, depth: 0, nesting: 0 };
+    let mut program = Vec::new();
+    while parser.peek().kind != TokenKind::Eof {
+        program.push(parser.statement()?);
+    }
+    Ok(program)
+}
+
+// ---------------------------------------------------------------- compiler
+
+#[derive(Default)]
+struct LoopJumps {
+    breaks: Vec<usize>,
+    continues: Vec<usize>,
+}
+
+struct Compiler<'s> {
+    program: Program,
+    scopes: Vec<Vec<(String, usize)>>,
+    loops: Vec<LoopJumps>,
+    spells: &'s [String],
+    features: &'s FeatureGate,
+    in_handler: bool,
+}
+
+impl Compiler<'_> {
+    fn emit(&mut self, op: Op, span: Span) -> usize {
+        self.program.code.push(op);
+        self.program.spans.push(span);
+        self.program.stmt_starts.push(false);
+        self.program.code.len() - 1
+    }
+
+    fn here(&self) -> usize {
+        self.program.code.len()
+    }
+
+    // points an already emitted jump at target.
+    fn patch(&mut self, at: usize, target: usize) {
+        match &mut self.program.code[at] {
+            Op::Jump(to) | Op::JumpIfFalse(to) | Op::JumpIfFalseOrPop(to) | Op::JumpIfTrueOrPop(to) => *to = target,
+            _ => {}
+        }
+    }
+
+    // lets the debugger stop before the already emitted op at `at`.
+    fn mark_statement(&mut self, at: usize) {
+        if let Some(start) = self.program.stmt_starts.get_mut(at) {
+            *start = true;
+        }
+    }
+
+    fn constant(&mut self, value: Value) -> usize {
+        if let Some(i) = self.program.constants.iter().position(|c| *c == value) {
+            return i;
+        }
+        self.program.constants.push(value);
+        self.program.constants.len() - 1
+    }
+
+    fn name(&mut self, name: &str) -> usize {
+        if let Some(i) = self.program.names.iter().position(|n| n == name) {
+            return i;
+        }
+        self.program.names.push(name.to_string());
+        self.program.names.len() - 1
+    }
+
+    fn lookup(&self, name: &str) -> Option<usize> {
+        self.scopes.iter().rev()
+            .flat_map(|scope| scope.iter().rev())
+            .find(|(n, _)| n == name)
+            .map(|(_, slot)| *slot)
+    }
+
+    fn declare(&mut self, name: &str) -> usize {
+        let slot = self.program.slot_names.len();
+        self.program.slot_names.push(name.to_string());
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.push((name.to_string(), slot));
+        }
+        slot
+    }
+
+    fn block(&mut self, body: &[Stmt]) -> Result<(), ScriptError> {
+        self.scopes.push(Vec::new());
+        for stmt in body {
+            self.statement(stmt)?;
+        }
+        self.scopes.pop();
+        Ok(())
+    }
+
+    fn statement(&mut self, stmt: &Stmt) -> Result<(), ScriptError> {
+        let span = stmt.span;
+        let first = self.here();
+        match &stmt.kind {
+            StmtKind::Let(name, value) => {
+                if builtin(name).is_some_and(|b| matches!(b.kind, BuiltinKind::Global | BuiltinKind::Handler(_))) || KEYWORDS.contains(&name.as_str()) {
+                    return Err(ScriptError::new(span, format!("'{}' is reserved and can't be a variable", name)));
+                }
+                self.expression(value)?;
+                let slot = self.declare(name);
+                self.emit(Op::Store(slot), span);
+            }
+            StmtKind::Assign(name, value) => {
+                let Some(slot) = self.lookup(name) else {
+                    let message = if builtin(name).is_some() {
+                        format!("'{}' is built in and can't be assigned to", name)
+                    } else {
+                        format!("'{}' isn't declared yet, use `let {} = ...`", name, name)
+                    };
+                    return Err(ScriptError::new(span, message));
+                };
+                self.expression(value)?;
+                self.emit(Op::Store(slot), span);
+            }
+            StmtKind::If(branches, otherwise) => {
+                self.features.check(Some(Feature::Conditionals), "'if'", span)?;
+                let mut to_end = Vec::new();
+                for (i, (cond, body)) in branches.iter().enumerate() {
+                    let at = self.here();
+                    self.expression(cond)?;
+                    // an `else if` is a stop of its own
+                    if i > 0 {
+                        self.mark_statement(at);
+                    }
+                    let skip = self.emit(Op::JumpIfFalse(0), cond.span);
+                    self.block(body)?;
+                    to_end.push(self.emit(Op::Jump(0), span));
+                    let next = self.here();
+                    self.patch(skip, next);
+                }
+                if let Some(body) = otherwise {
+                    self.block(body)?;
+                }
+                let end = self.here();
+                for jump in to_end {
+                    self.patch(jump, end);
+                }
+            }
+            StmtKind::While(cond, body) => {
+                self.features.check(Some(Feature::Loops), "'while'", span)?;
+                let start = self.here();
+                self.expression(cond)?;
+                let exit = self.emit(Op::JumpIfFalse(0), cond.span);
+                self.loops.push(LoopJumps::default());
+                self.block(body)?;
+                self.emit(Op::Jump(start), span);
+                let end = self.here();
+                self.patch(exit, end);
+                let jumps = self.loops.pop().unwrap_or_default();
+                for jump in jumps.breaks {
+                    self.patch(jump, end);
+                }
+                for jump in jumps.continues {
+                    self.patch(jump, start);
+                }
+            }
+            StmtKind::For(name, from, to, body) => {
+                self.features.check(Some(Feature::Loops), "'for'", span)?;
+                self.scopes.push(Vec::new());
+                self.expression(from)?;
+                let var = self.declare(name);
+                self.emit(Op::Store(var), span);
+                self.expression(to)?;
+                let limit = self.declare(" limit");
+                self.emit(Op::Store(limit), span);
+
+                let start = self.here();
+                self.emit(Op::Load(var), span);
+                self.emit(Op::Load(limit), span);
+                self.emit(Op::Binary(BinOp::Lt), span);
+                let exit = self.emit(Op::JumpIfFalse(0), span);
+                self.loops.push(LoopJumps::default());
+                self.block(body)?;
+
+                let step = self.here();
+                self.emit(Op::Load(var), span);
+                self.mark_statement(step);
+                let one = self.constant(Value::Number(1.0));
+                self.emit(Op::Const(one), span);
+                self.emit(Op::Binary(BinOp::Add), span);
+                self.emit(Op::Store(var), span);
+                self.emit(Op::Jump(start), span);
+                let end = self.here();
+                self.patch(exit, end);
+                let jumps = self.loops.pop().unwrap_or_default();
+                for jump in jumps.breaks {
+                    self.patch(jump, end);
+                }
+                for jump in jumps.continues {
+                    self.patch(jump, step);
+                }
+                self.scopes.pop();
+            }
+            StmtKind::Break | StmtKind::Continue => {
+                let is_break = stmt.kind == StmtKind::Break;
+                if self.loops.is_empty() {
+                    let word = if is_break { "break" } else { "continue" };
+                    return Err(ScriptError::new(span, format!("'{}' can only be used inside a loop", word)));
+                }
+                let jump = self.emit(Op::Jump(0), span);
+                if let Some(jumps) = self.loops.last_mut() {
+                    if is_break {
+                        jumps.breaks.push(jump);
+                    } else {
+                        jumps.continues.push(jump);
+                    }
+                }
+            }
+            StmtKind::Expr(expr) => {
+                self.expression(expr)?;
+                self.emit(Op::Pop, span);
+            }
+            StmtKind::Handler(event, ..) => {
+                return Err(ScriptError::new(span, format!("'{}' has to be at the top of the script, not inside a block", event.name())));
+            }
+        }
+        self.mark_statement(first);
+        Ok(())
+    }
+
+    fn expression(&mut self, expr: &Expr) -> Result<(), ScriptError> {
+        let span = expr.span;
+        match &expr.kind {
+            ExprKind::Number(n) => {
+                let c = self.constant(Value::Number(*n));
+                self.emit(Op::Const(c), span);
+            }
+            ExprKind::Str(s) => {
+                let c = self.constant(Value::Str(s.clone()));
+                self.emit(Op::Const(c), span);
+            }
+            ExprKind::Bool(b) => {
+                let c = self.constant(Value::Bool(*b));
+                self.emit(Op::Const(c), span);
+            }
+            ExprKind::Nil => {
+                let c = self.constant(Value::Nil);
+                self.emit(Op::Const(c), span);
+            }
+            ExprKind::Var(name) => {
+                if let Some(slot) = self.lookup(name) {
+                    self.emit(Op::Load(slot), span);
+                } else if let Some(global) = builtin(name).filter(|b| b.kind == BuiltinKind::Global) {
+                    self.features.check(global.feature, &format!("'{}'", name), span)?;
+                    if name == "event" && !self.in_handler {
+                        return Err(ScriptError::new(span, "'event' only exists inside an event handler like on_cast_finished"));
+                    }
+                    let n = self.name(name);
+                    self.emit(Op::Global(n), span);
+                } else {
+                    return Err(ScriptError::new(span, format!("unknown name '{}'", name)));
+                }
+            }
+            ExprKind::Neg(inner) => {
+                self.expression(inner)?;
+                self.emit(Op::Neg, span);
+            }
+            ExprKind::Not(inner) => {
+                self.expression(inner)?;
+                self.emit(Op::Not, span);
+            }
+            ExprKind::Binary(op, left, right) => {
+                self.expression(left)?;
+                self.expression(right)?;
+                self.emit(Op::Binary(*op), span);
+            }
+            ExprKind::And(left, right) => {
+                self.expression(left)?;
+                let short = self.emit(Op::JumpIfFalseOrPop(0), span);
+                self.expression(right)?;
+                let end = self.here();
+                self.patch(short, end);
+            }
+            ExprKind::Or(left, right) => {
+                self.expression(left)?;
+                let short = self.emit(Op::JumpIfTrueOrPop(0), span);
+                self.expression(right)?;
+                let end = self.here();
+                self.patch(short, end);
+            }
+            ExprKind::Call(name, args) => {
+                let Some((arity, feature)) = builtin(name).and_then(|b| match b.kind {
+                    BuiltinKind::Function(arity) => Some((arity, b.feature)),
+                    _ => None,
+                }) else {
+                    return Err(ScriptError::new(span, format!("unknown function '{}'", name)));
+                };
+                self.features.check(feature, &format!("{}()", name), span)?;
+                if args.len() != arity {
+                    return Err(ScriptError::new(span, format!("{}() takes {} argument(s), not {}", name, arity, args.len())));
+                }
+                self.check_spell_args(name, args)?;
+                for arg in args {
+                    self.expression(arg)?;
+                }
+                let n = self.name(name);
+                self.emit(Op::Call(n, args.len()), span);
+            }
+            ExprKind::Field(object, name) => {
+                if !BUILTINS.iter().any(|b| b.name == name && matches!(b.kind, BuiltinKind::Field(_))) {
+                    return Err(ScriptError::new(span, format!("unknown field '{}'", name)));
+                }
+                self.expression(object)?;
+                let n = self.name(name);
+                self.emit(Op::Field(n), span);
+            }
+            ExprKind::Method(object, name, args) => {
+                let method = BUILTINS.iter()
+                    .find_map(|b| match b.kind {
+                        BuiltinKind::Method(_, arity) if b.name == name => Some((arity, b.feature)),
+                        _ => None,
+                    });
+                let Some((arity, feature)) = method else {
+                    return Err(ScriptError::new(span, format!("unknown method '{}'", name)));
+                };
+                self.features.check(feature, &format!(".{}()", name), span)?;
+                if args.len() != arity {
+                    return Err(ScriptError::new(span, format!("{}() takes {} argument(s), not {}", name, arity, args.len())));
+                }
+                self.check_spell_args(name, args)?;
+                self.expression(object)?;
+                for arg in args {
+                    self.expression(arg)?;
+                }
+                let n = self.name(name);
+                self.emit(Op::Method(n, args.len()), span);
+            }
+        }
+        Ok(())
+    }
+
+    // spell names written out as text can be checked before the script ever runs.
+    fn check_spell_args(&self, name: &str, args: &[Expr]) -> Result<(), ScriptError> {
+        if !matches!(name, "cast" | "cooldown" | "debuff") {
+            return Ok(());
+        }
+        for arg in args {
+            if let ExprKind::Str(spell) = &arg.kind {
+                self.check_spell(spell, arg.span)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn check_spell(&self, spell: &str, span: Span) -> Result<(), ScriptError> {
+        if self.spells.is_empty() || self.spells.iter().any(|s| s == spell) {
+            return Ok(());
+        }
+        Err(ScriptError::new(span, format!("unknown spell \"{}\"", spell)))
+    }
+
+    // a handler's argument is written out, so it is known before the game dispatches anything.
+    fn handler_filter(&self, event: ScriptEvent, argument: Option<&Expr>, span: Span) -> Result<Value, ScriptError> {
+        let name = event.name();
+        match (event, argument.map(|arg| (&arg.kind, arg.span))) {
+            (ScriptEvent::CastFinished | ScriptEvent::DebuffExpired, None) => Ok(Value::Nil),
+            (ScriptEvent::CastFinished | ScriptEvent::DebuffExpired, Some((ExprKind::Str(spell), at))) => {
+                self.check_spell(spell, at)?;
+                Ok(Value::Str(spell.clone()))
+            }
+            (ScriptEvent::CastFinished | ScriptEvent::DebuffExpired, Some((_, at))) => {
+                Err(ScriptError::new(at, format!("{}() takes a spell name like \"miasma\"", name)))
+            }
+            (ScriptEvent::TargetDied, None) => Ok(Value::Nil),
+            (ScriptEvent::TargetDied, Some((_, at))) => Err(ScriptError::new(at, format!("{} doesn't take an argument", name))),
+            (ScriptEvent::HealthBelow, Some((ExprKind::Number(n), _))) if (0.0..=1.0).contains(n) => Ok(Value::Number(*n)),
+            (ScriptEvent::HealthBelow, Some((_, at))) => {
+                Err(ScriptError::new(at, format!("{}() takes a health fraction from 0 to 1 like 0.3", name)))
+            }
+            (ScriptEvent::HealthBelow, None) => {
+                Err(ScriptError::new(span, format!("{} needs a health fraction, e.g. {}(0.3)", name, name)))
+            }
+        }
+    }
+
+    // handlers go after the main script, each in its own scope and ending in a return.
+    fn handler(&mut self, event: ScriptEvent, argument: Option<&Expr>, body: &[Stmt], span: Span) -> Result<(), ScriptError> {
+        self.features.check(Some(Feature::Events), &format!("'{}'", event.name()), span)?;
+        let filter = self.handler_filter(event, argument, span)?;
+        let start = self.here();
+        let outer = std::mem::replace(&mut self.scopes, vec![Vec::new()]);
+        self.in_handler = true;
+        for stmt in body {
+            self.statement(stmt)?;
+        }
+        self.in_handler = false;
+        self.scopes = outer;
+        self.emit(Op::Return, span);
+        self.program.handlers.push(Handler { event, filter, start });
+        Ok(())
+    }
+}
+
+// spells lists the spell names the script may refer to, leave it empty to skip that check.
+// features says which parts of the language the script may use.
+pub fn compile(source: &str, spells: &[String], features: &FeatureGate) -> Result<Program, ScriptError> {
+    let statements = parse(source)?;
+    let mut compiler = Compiler {
+        program: Program::default(),
+        scopes: vec![Vec::new()],
+        loops: Vec::new(),
+        spells,
+        features,
+        in_handler: false,
+    };
+    for stmt in statements.iter().filter(|stmt| !matches!(stmt.kind, StmtKind::Handler(..))) {
+        compiler.statement(stmt)?;
+    }
+    if statements.iter().any(|stmt| matches!(stmt.kind, StmtKind::Handler(..))) {
+        let end = statements.last().map(|stmt| stmt.span).unwrap_or_default();
+        compiler.emit(Op::Return, end);
+    }
+    for stmt in &statements {
+        if let StmtKind::Handler(event, argument, body) = &stmt.kind {
+            compiler.handler(*event, argument.as_ref(), body, stmt.span)?;
+        }
+    }
+    Ok(compiler.program)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check(source: &str) -> Result<Program, ScriptError> {
+        compile(source, &["miasma".to_string()], &FeatureGate { unlocked: Feature::ALL.to_vec(), hints: vec![] })
+    }
+
+    fn nested_too_deeply(source: &str) -> bool {
+        check(source).is_err_and(|e| e.message.ends_with("nested too deeply"))
+    }
+
+    #[test]
+    fn shallow_nesting_compiles() {
+        assert!(check(&format!("let x = {}1{}", "(".repeat(50), ")".repeat(50))).is_ok());
+        assert!(check(&format!("{}{}", "if true {\n".repeat(20), "}\n".repeat(20))).is_ok());
+    }
+
+    #[test]
+    fn deep_nesting_is_an_error_not_a_crash() {
+        assert!(nested_too_deeply(&format!("let x = {}1{}", "(".repeat(8000), ")".repeat(8000))));
+        assert!(nested_too_deeply(&format!("let x = {}1", "-".repeat(5000))));
+        assert!(nested_too_deeply(&format!("let x = {}1", "1 + ".repeat(5000))));
+        assert!(nested_too_deeply(&format!("{}{}", "if true {\n".repeat(5000), "}\n".repeat(5000))));
+        assert!(nested_too_deeply(&format!("let x = a{}", ".b".repeat(5000))));
+    }
+
+    #[test]
+    fn long_else_if_chain_is_not_nesting() {
+        let mut source = "let x = 0\nif x == 0 { }".to_string();
+        for i in 1..200 {
+            source.push_str(&format!(" else if x == {} {{ }}", i));
+        }
+        assert!(check(&source).is_ok());
+    }
+
+    #[test]
+    fn break_outside_a_loop_is_an_error() {
+        assert!(check("break").is_err());
+        assert!(check("while true { break }").is_ok());
+    }
+}
@@ -0,0 +1,540 @@
+use std::fs;
+use std::path::PathBuf;
+use sfml::window::{clipboard, Key};
+use crate::game::*;
+use crate::properties::*;
+
+pub static SCRIPTS_DIR: &str = "./saves/scripts";
+pub static LIBRARY_TAG: &str = "script_library";
+pub static EXPORT_PREFIX: &str = "periodicity-script:1:";
+pub static MAX_SCRIPT_NAME: usize = 32;
+pub static LIBRARY_ROWS: usize = 18;
+pub static MAX_SHARE_CODE_BYTES: usize = 16 * 1024; // decoded, far more than any script the editor holds
+
+static LIBRARY_BUTTONS: [&str; 6] = [
+    "library_load_button", "library_duplicate_button", "library_rename_button",
+    "library_delete_button", "library_export_button", "library_import_button",
+];
+
+// the player's scripts, one text file per script in dir named after it.
+pub struct ScriptLibrary {
+    pub dir: String,
+    pub names: Vec<String>, // sorted
+    pub active: Option<String>, // the script in the editor
+    pub saved_revision: u64,    // editor revision last written to the active script's file
+    pub selected: usize,
+    pub scroll: usize,
+    pub renaming: Option<String>, // the new name being typed for the selected script
+    pub confirm_delete: Option<String>,
+}
+
+// a script name is also its file name, so only a safe set of characters is allowed.
+pub fn valid_script_name(name: &str) -> Result<String, String> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err("a script needs a name".to_string());
+    }
+    if name.chars().count() > MAX_SCRIPT_NAME {
+        return Err(format!("script names can be at most {} characters", MAX_SCRIPT_NAME));
+    }
+    if let Some(c) = name.chars().find(|c| !(c.is_alphanumeric() || matches!(c, ' ' | '_' | '-'))) {
+        return Err(format!("script names can't contain '{}'", c));
+    }
+    Ok(name.to_string())
+}
+
+impl ScriptLibrary {
+    pub fn new(dir: &str) -> Self {
+        ScriptLibrary {
+            dir: dir.to_string(),
+            names: Vec::new(),
+            active: None,
+            saved_revision: 0,
+            selected: 0,
+            scroll: 0,
+            renaming: None,
+            confirm_delete: None,
+        }
+    }
+
+    pub fn path(&self, name: &str) -> PathBuf {
+        PathBuf::from(&self.dir).join(format!("{}.txt", name))
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.names.iter().any(|n| n.eq_ignore_ascii_case(name))
+    }
+
+    pub fn selected_name(&self) -> Option<String> {
+        self.names.get(self.selected).cloned()
+    }
+
+    // lists the scripts in dir, making it if it isn't there yet.
+    pub fn refresh(&mut self) -> Result<(), String> {
+        fs::create_dir_all(&self.dir).map_err(|e| format!("could not create {}: {}", self.dir, e))?;
+        let entries = fs::read_dir(&self.dir).map_err(|e| format!("could not read {}: {}", self.dir, e))?;
+        self.names = entries
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext == "txt"))
+            .filter_map(|path| path.file_stem().map(|stem| stem.to_string_lossy().to_string()))
+            .collect();
+        self.names.sort_by_key(|name| name.to_lowercase());
+        self.selected = self.selected.min(self.names.len().saturating_sub(1));
+        Ok(())
+    }
+
+    pub fn read(&self, name: &str) -> Result<String, String> {
+        fs::read_to_string(self.path(name)).map_err(|e| format!("could not load {}: {}", name, e))
+    }
+
+    pub fn write(&self, name: &str, source: &str) -> Result<(), String> {
+        fs::write(self.path(name), source).map_err(|e| format!("could not save {}: {}", name, e))
+    }
+
+    // base itself if no script has that name yet, otherwise "base 2", "base 3" and so on.
+    pub fn unique_name(&self, base: &str) -> String {
+        if !self.contains(base) {
+            return base.to_string();
+        }
+        (2..)
+            .map(|n| {
+                let suffix = format!(" {}", n);
+                let keep = MAX_SCRIPT_NAME.saturating_sub(suffix.len());
+                format!("{}{}", base.chars().take(keep).collect::<String>().trim_end(), suffix)
+            })
+            .find(|name| !self.contains(name))
+            .unwrap_or_default()
+    }
+
+    // adds a script under a free version of name and selects it, returns the name it got.
+    pub fn create(&mut self, name: &str, source: &str) -> Result<String, String> {
+        let name = self.unique_name(&valid_script_name(name)?);
+        self.write(&name, source)?;
+        self.refresh()?;
+        self.select(&name);
+        Ok(name)
+    }
+
+    pub fn rename(&mut self, from: &str, to: &str) -> Result<String, String> {
+        let to = valid_script_name(to)?;
+        if to == from {
+            return Ok(to);
+        }
+        // only a change of case is allowed to match the old name
+        if self.contains(&to) && !to.eq_ignore_ascii_case(from) {
+            return Err(format!("there already is a script called {}", to));
+        }
+        fs::rename(self.path(from), self.path(&to)).map_err(|e| format!("could not rename {}: {}", from, e))?;
+        if self.active.as_deref() == Some(from) {
+            self.active = Some(to.clone());
+        }
+        self.refresh()?;
+        self.select(&to);
+        Ok(to)
+    }
+
+    pub fn delete(&mut self, name: &str) -> Result<(), String> {
+        fs::remove_file(self.path(name)).map_err(|e| format!("could not delete {}: {}", name, e))?;
+        self.refresh()
+    }
+
+    pub fn select(&mut self, name: &str) {
+        if let Some(i) = self.names.iter().position(|n| n == name) {
+            self.selected = i;
+        }
+        self.renaming = None;
+        self.confirm_delete = None;
+        if self.selected < self.scroll {
+            self.scroll = self.selected;
+        } else if self.selected >= self.scroll + LIBRARY_ROWS {
+            self.scroll = self.selected + 1 - LIBRARY_ROWS;
+        }
+    }
+
+    pub fn select_by(&mut self, delta: isize) {
+        if self.names.is_empty() {
+            return;
+        }
+        let i = (self.selected as isize + delta).clamp(0, self.names.len() as isize - 1) as usize;
+        let name = self.names[i].clone();
+        self.select(&name);
+    }
+}
+
+// ---------------------------------------------------------------- share codes
+
+static BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+fn base64_encode(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let b = [chunk[0], chunk.get(1).copied().unwrap_or(0), chunk.get(2).copied().unwrap_or(0)];
+        let n = (b[0] as u32) << 16 | (b[1] as u32) << 8 | b[2] as u32;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(BASE64[(n >> (18 - 6 * i) & 63) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+fn base64_decode(text: &str) -> Result<Vec<u8>, String> {
+    let digits: Vec<u32> = text.trim_end_matches('=').bytes()
+        .filter(|b| !b.is_ascii_whitespace())
+        .map(|b| BASE64.iter().position(|&c| c == b).map(|i| i as u32).ok_or("the share code has a typo in it"))
+        .collect::<Result<_, _>>()?;
+    if digits.len() % 4 == 1 {
+        return Err("the share code is cut short".to_string());
+    }
+    let mut out = Vec::with_capacity(digits.len() * 3 / 4);
+    for chunk in digits.chunks(4) {
+        let n = chunk.iter().enumerate().fold(0, |n, (i, d)| n | d << (18 - 6 * i));
+        for i in 0..chunk.len() - 1 {
+            out.push((n >> (16 - 8 * i) & 255) as u8);
+        }
+    }
+    Ok(out)
+}
+
+// a single line a player can paste anywhere and import again with import_script.
+pub fn export_script(name: &str, source: &str) -> String {
+    format!("{}{}", EXPORT_PREFIX, base64_encode(format!("{}\n{}", name, source).as_bytes()))
+}
+
+// the name and source in a share code.
+pub fn import_script(code: &str) -> Result<(String, String), String> {
+    let Some(encoded) = code.trim().strip_prefix(EXPORT_PREFIX) else {
+        return Err("that is not a script share code".to_string());
+    };
+    if encoded.len() > MAX_SHARE_CODE_BYTES.div_ceil(3) * 4 {
+        return Err("the share code is too long".to_string());
+    }
+    let bytes = base64_decode(encoded)?;
+    let text = String::from_utf8(bytes).map_err(|_| "the share code is damaged".to_string())?;
+    let (name, source) = text.split_once('\n').unwrap_or(("imported", text.as_str()));
+    let name = valid_script_name(name).unwrap_or_else(|_| "imported".to_string());
+    Ok((name, source.to_string()))
+}
+
+// ---------------------------------------------------------------- the picker panel
+
+impl Game<'_> {
+    // opens the last active script, or the sample rotation when the library is still empty.
+    pub fn init_script_library(&mut self) {
+        if let Err(e) = self.script_library.refresh() {
+            println!("{}", e);
+            self.set_script_status(&e, DAMAGE_TAKEN_COLOR);
+            return;
+        }
+        if self.script_library.names.is_empty() {
+            let sample = fs::read_to_string(crate::script::DEFAULT_SCRIPT_PATH).unwrap_or_default();
+            if let Err(e) = self.script_library.create("rotation", &sample) {
+                println!("{}", e);
+            }
+        }
+        let wanted = self.script_library.active.clone()
+            .filter(|name| self.script_library.contains(name))
+            .or_else(|| self.script_library.names.first().cloned());
+        if let Some(name) = wanted {
+            self.open_script(&name);
+        }
+    }
+
+    pub fn script_library_open(&self) -> bool {
+        self.state[4] == 1
+    }
+
+    // writes the editor back to the active script's file if it changed since.
+    pub fn save_active_script(&mut self) {
+        let (Some(active), Some(revision)) = (self.script_library.active.clone(), self.code_editor().map(|e| e.revision)) else {
+            return;
+        };
+        if revision == self.script_library.saved_revision {
+            return;
+        }
+        match self.script_library.write(&active, &self.code_editor_text()) {
+            Ok(()) => self.script_library.saved_revision = revision,
+            Err(e) => {
+                println!("{}", e);
+                self.set_script_status(&e, DAMAGE_TAKEN_COLOR);
+            }
+        }
+    }
+
+    // puts a script in the editor. one that can't be read leaves the editor as it was.
+    pub fn open_script(&mut self, name: &str) {
+        self.save_active_script();
+        let source = match self.script_library.read(name) {
+            Ok(source) => source,
+            Err(e) => {
+                println!("{}", e);
+                self.set_script_status(&e, DAMAGE_TAKEN_COLOR);
+                return;
+            }
+        };
+        let Some(id) = self.code_editor_id() else {
+            return;
+        };
+        let Some(editor) = self.em.get_pcode_editor_mut(id) else {
+            return;
+        };
+        editor.set_text(&source);
+        self.script_library.saved_revision = editor.revision;
+        self.script_library.active = Some(name.to_string());
+        self.script_library.select(name);
+        self.save_character();
+        self.set_script_status(&format!("Loaded {}", name), OFF_TEXT_CLR);
+    }
+
+    pub fn toggle_script_library(&mut self) {
+        if self.script_library_open() {
+            self.close_script_library();
+            return;
+        }
+        self.state[4] = 1;
+        self.save_active_script();
+        if let Err(e) = self.script_library.refresh() {
+            self.set_script_status(&e, DAMAGE_TAKEN_COLOR);
+        }
+        if let Some(active) = self.script_library.active.clone() {
+            self.script_library.select(&active);
+        }
+        self.show_library_panel();
+    }
+
+    pub fn close_script_library(&mut self) {
+        self.state[4] = 0;
+        self.script_library.renaming = None;
+        self.script_library.confirm_delete = None;
+        self.em.purge_entity_by_tag(LIBRARY_TAG);
+        for tag in LIBRARY_BUTTONS {
+            self.em.purge_entity_by_tag(tag);
+        }
+    }
+
+    pub fn library_load(&mut self) {
+        if let Some(name) = self.script_library.selected_name() {
+            self.open_script(&name);
+            self.close_script_library();
+        }
+    }
+
+    pub fn library_duplicate(&mut self) {
+        let Some(name) = self.script_library.selected_name() else {
+            return;
+        };
+        self.save_active_script();
+        let result = self.script_library.read(&name)
+            .and_then(|source| self.script_library.create(&format!("{} copy", name), &source));
+        match result {
+            Ok(copy) => self.set_script_status(&format!("Duplicated {} as {}", name, copy), OFF_TEXT_CLR),
+            Err(e) => self.set_script_status(&e, DAMAGE_TAKEN_COLOR),
+        }
+    }
+
+    pub fn library_rename(&mut self) {
+        if let Some(name) = self.script_library.selected_name() {
+            self.script_library.renaming = Some(name);
+            self.set_script_status("Type a new name, Enter to keep it, Escape to cancel", OFF_TEXT_CLR);
+        }
+    }
+
+    fn finish_rename(&mut self) {
+        let (Some(from), Some(to)) = (self.script_library.selected_name(), self.script_library.renaming.take()) else {
+            return;
+        };
+        self.save_active_script();
+        match self.script_library.rename(&from, &to) {
+            Ok(to) => {
+                self.save_character();
+                self.set_script_status(&format!("Renamed {} to {}", from, to), OFF_TEXT_CLR);
+            }
+            Err(e) => self.set_script_status(&e, DAMAGE_TAKEN_COLOR),
+        }
+    }
+
+    // asks once before a script is gone for good; the last one can't be deleted.
+    pub fn library_delete(&mut self) {
+        let Some(name) = self.script_library.selected_name() else {
+            return;
+        };
+        if self.script_library.names.len() == 1 {
+            self.set_script_status("The last script can't be deleted", DAMAGE_TAKEN_COLOR);
+            return;
+        }
+        if self.script_library.confirm_delete.as_ref() != Some(&name) {
+            self.script_library.confirm_delete = Some(name.clone());
+            self.set_script_status(&format!("Delete {}? Press Delete again to confirm", name), WARNING_COLOR);
+            return;
+        }
+        self.script_library.confirm_delete = None;
+        if let Err(e) = self.script_library.delete(&name) {
+            self.set_script_status(&e, DAMAGE_TAKEN_COLOR);
+            return;
+        }
+        self.set_script_status(&format!("Deleted {}", name), OFF_TEXT_CLR);
+        if self.script_library.active.as_deref() == Some(name.as_str()) {
+            // nothing is left to save the editor's text to
+            self.script_library.active = None;
+            if let Some(first) = self.script_library.names.first().cloned() {
+                self.open_script(&first);
+            }
+        }
+    }
+
+    pub fn library_export(&mut self) {
+        let Some(name) = self.script_library.selected_name() else {
+            return;
+        };
+        self.save_active_script();
+        match self.script_library.read(&name) {
+            Ok(source) => {
+                clipboard::set_string(export_script(&name, &source).as_str());
+                self.set_script_status(&format!("Copied {} to the clipboard as a share code", name), HEAL_COLOR);
+            }
+            Err(e) => self.set_script_status(&e, DAMAGE_TAKEN_COLOR),
+        }
+    }
+
+    pub fn library_import(&mut self) {
+        let code = clipboard::get_string();
+        let result = import_script(&code)
+            .and_then(|(name, source)| self.script_library.create(&name, &source));
+        match result {
+            Ok(name) => self.set_script_status(&format!("Imported {} from the clipboard", name), HEAL_COLOR),
+            Err(e) => self.set_script_status(&format!("Could not import: {}", e), DAMAGE_TAKEN_COLOR),
+        }
+    }
+
+    // the row under the mouse is selected, clicking the selected row again loads it.
+    pub fn library_mouse_pressed(&mut self, x: u32, y: u32) -> bool {
+        if !self.script_library_open() {
+            return false;
+        }
+        let scale = get_scale();
+        let s = |x: u32| scaled(scale, x);
+        let top = s(165);
+        let within_x = x >= s(10) && x <= s(620);
+        if !within_x || y < top || y >= top + s(30) * LIBRARY_ROWS as u32 {
+            return false;
+        }
+        let row = ((y - top) / s(30)) as usize + self.script_library.scroll;
+        let Some(name) = self.script_library.names.get(row).cloned() else {
+            return true;
+        };
+        if row == self.script_library.selected && self.script_library.renaming.is_none() {
+            self.library_load();
+        } else {
+            self.script_library.select(&name);
+        }
+        true
+    }
+
+    pub fn library_key_pressed(&mut self, code: Key) -> bool {
+        if !self.script_library_open() {
+            return false;
+        }
+        if let Some(name) = self.script_library.renaming.as_mut() {
+            match code {
+                Key::Enter => self.finish_rename(),
+                Key::Escape => self.script_library.renaming = None,
+                Key::Backspace => {
+                    name.pop();
+                }
+                _ => {}
+            }
+            return true;
+        }
+        match code {
+            Key::Up => self.script_library.select_by(-1),
+            Key::Down => self.script_library.select_by(1),
+            Key::Enter => self.library_load(),
+            Key::F2 => self.library_rename(),
+            Key::Delete => self.library_delete(),
+            Key::Escape => self.close_script_library(),
+            _ => return false,
+        }
+        true
+    }
+
+    pub fn library_text_entered(&mut self, c: char) -> bool {
+        let Some(name) = self.script_library.renaming.as_mut().filter(|_| self.state[4] == 1) else {
+            return false;
+        };
+        if (c.is_alphanumeric() || matches!(c, ' ' | '_' | '-')) && name.chars().count() < MAX_SCRIPT_NAME {
+            name.push(c);
+        }
+        true
+    }
+
+    // covers the editor like the stats menu does; the rows are drawn in render_script_library.
+    fn show_library_panel(&mut self) {
+        let scale = get_scale();
+        let s = |x: u32| scaled(scale, x);
+
+        let panel = self.em.add_entity(Some(LIBRARY_TAG.to_string()));
+        self.em.add_property_to_entity(PropertiesEnum::Rect, panel);
+        if let Some(rect) = self.em.get_prects_mut(panel).and_then(|rects| rects.last_mut()) {
+            rect.x = s(10);
+            rect.y = s(120);
+            rect.width = s(610);
+            rect.height = s(750);
+            rect.colors = ColorPair::from_colors(ENCAPSULATION_REGIONS, Some(sfml::graphics::Color::BLACK));
+            rect.draw = true;
+            rect.strata = 10;
+        }
+
+        let buttons = [
+            ("Load", "Open the selected script in the editor. (Enter)", ClickAction::LibraryLoad),
+            ("Duplicate", "Make a copy of the selected script.", ClickAction::LibraryDuplicate),
+            ("Rename", "Give the selected script a new name. (F2)", ClickAction::LibraryRename),
+            ("Delete", "Delete the selected script, asks again first. (Delete)", ClickAction::LibraryDelete),
+            ("Export", "Copy the selected script to the clipboard as a share code.", ClickAction::LibraryExport),
+            ("Import", "Add the script whose share code is on the clipboard.", ClickAction::LibraryImport),
+        ];
+        for (i, ((label, tooltip, action), tag)) in buttons.into_iter().zip(LIBRARY_BUTTONS).enumerate() {
+            let x = s(20 + 150 * (i as u32 % 4));
+            let y = s(750 + 60 * (i as u32 / 4));
+            self.create_panel_button(tag, label, tooltip, (x, y, s(140), s(50)), 20, action);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn base64_round_trips() {
+        for text in ["", "a", "ab", "abc", "abcd", "cast(\"miasma\")\n\u{e9}"] {
+            assert_eq!(base64_decode(&base64_encode(text.as_bytes())).unwrap(), text.as_bytes());
+        }
+        let code = export_script("opener", "if true {\n    cast(\"miasma\")\n}");
+        assert_eq!(import_script(&code).unwrap(), ("opener".to_string(), "if true {\n    cast(\"miasma\")\n}".to_string()));
+    }
+
+    #[test]
+    fn truncated_code_is_rejected() {
+        let code = export_script("opener", "cast(\"miasma\")");
+        let digits = code.trim_end_matches('=').len() - EXPORT_PREFIX.len();
+        let cut = &code[..EXPORT_PREFIX.len() + (digits - 2) / 4 * 4 + 1];
+        assert_eq!(import_script(cut).unwrap_err(), "the share code is cut short");
+    }
+
+    #[test]
+    fn typo_in_code_is_rejected() {
+        let code = export_script("opener", "cast(\"miasma\")").replacen(EXPORT_PREFIX, &format!("{}!", EXPORT_PREFIX), 1);
+        assert_eq!(import_script(&code).unwrap_err(), "the share code has a typo in it");
+        assert_eq!(import_script("periodicity-script:2:abcd").unwrap_err(), "that is not a script share code");
+    }
+
+    #[test]
+    fn oversized_code_is_rejected() {
+        let code = export_script("big", &"x".repeat(MAX_SHARE_CODE_BYTES + 1));
+        assert_eq!(import_script(&code).unwrap_err(), "the share code is too long");
+        assert!(import_script(&export_script("fits", &"x".repeat(MAX_SHARE_CODE_BYTES - 8))).is_ok());
+    }
+}
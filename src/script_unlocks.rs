@@ -0,0 +1,124 @@
+use std::fs;
+use serde::{Serialize, Deserialize};
+use crate::game::*;
+use crate::script::{Feature, FeatureGate};
+
+pub static SCRIPT_UNLOCKS_PATH: &str = "./src/assets/data/script_unlocks.json";
+
+// what unlocks a scripting feature. talents are taught by challenges and items dropped by
+// enemies, both matched by name.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UnlockSource {
+    Level(u32), // reaching this level or higher
+    Talent(String),
+    Item(String),
+}
+
+impl UnlockSource {
+    // how the player gets it, shown next to a locked feature.
+    pub fn hint(&self) -> String {
+        match self {
+            UnlockSource::Level(level) => format!("reach level {}", level),
+            UnlockSource::Talent(name) => format!("learn the talent {}", name),
+            UnlockSource::Item(name) => format!("find the item {}", name),
+        }
+    }
+
+    fn grants(&self, gained: &UnlockSource) -> bool {
+        match (self, gained) {
+            (UnlockSource::Level(needed), UnlockSource::Level(reached)) => reached >= needed,
+            _ => self == gained,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UnlockRule {
+    pub feature: Feature,
+    pub source: UnlockSource,
+}
+
+// the features every script starts without are defined in SCRIPT_UNLOCKS_PATH along with what
+// unlocks them; what the character has unlocked so far is kept in its save.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ScriptUnlocks {
+    pub rules: Vec<UnlockRule>,
+    #[serde(skip)]
+    pub unlocked: Vec<Feature>,
+}
+
+impl ScriptUnlocks {
+    pub fn load(path: &str) -> Result<ScriptUnlocks, String> {
+        let raw = fs::read_to_string(path)
+            .map_err(|e| format!("could not read script unlocks {}: {}", path, e))?;
+        let unlocks: ScriptUnlocks = serde_json::from_str(&raw)
+            .map_err(|e| format!("could not parse script unlocks {}: {}", path, e))?;
+        unlocks.validate()?;
+        Ok(unlocks)
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        for feature in Feature::ALL {
+            if !self.rules.iter().any(|rule| rule.feature == feature) {
+                return Err(format!("nothing unlocks {}", feature.name()));
+            }
+        }
+        for rule in &self.rules {
+            if let UnlockSource::Talent(name) | UnlockSource::Item(name) = &rule.source && name.trim().is_empty() {
+                return Err(format!("{} is unlocked by a talent or item without a name", rule.feature.name()));
+            }
+        }
+        Ok(())
+    }
+
+    // a locked feature's hint names every way to unlock it.
+    pub fn gate(&self) -> FeatureGate {
+        let hints = Feature::ALL.iter()
+            .filter(|f| !self.unlocked.contains(f))
+            .map(|f| {
+                let ways: Vec<String> = self.rules.iter()
+                    .filter(|rule| rule.feature == *f)
+                    .map(|rule| rule.source.hint())
+                    .collect();
+                (*f, ways.join(" or "))
+            })
+            .collect();
+        FeatureGate { unlocked: self.unlocked.clone(), hints }
+    }
+
+    // unlocks everything source grants, returns what wasn't unlocked before.
+    pub fn grant(&mut self, source: &UnlockSource) -> Vec<Feature> {
+        let mut new = Vec::new();
+        for rule in &self.rules {
+            if rule.source.grants(source) && !self.unlocked.contains(&rule.feature) && !new.contains(&rule.feature) {
+                new.push(rule.feature);
+            }
+        }
+        self.unlocked.extend(new.iter().copied());
+        new
+    }
+}
+
+impl Game<'_> {
+    // catches up on what the player's level already grants, e.g. after loading the character.
+    pub fn init_script_unlocks(&mut self) {
+        if let Some(level) = self.gem.player_id.and_then(|pid| self.gem.levels.get(&pid)).map(|l| l.curr_level) {
+            self.grant_script_unlocks(UnlockSource::Level(level));
+        }
+    }
+
+    // called when the player reaches a level, learns a talent or picks up an item.
+    pub fn grant_script_unlocks(&mut self, source: UnlockSource) {
+        let new = self.script_unlocks.grant(&source);
+        if new.is_empty() {
+            return;
+        }
+        let names: Vec<&str> = new.iter().map(|f| f.name()).collect();
+        println!("Unlocked {} for scripts", names.join(", "));
+        self.floating_message(&format!("Scripts can now use {}", names.join(" and ")), XP_COLOR);
+        // the editor's text may have become valid
+        self.script.checked_revision = None;
+        self.save_character();
+    }
+}
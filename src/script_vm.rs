@@ -0,0 +1,474 @@
+use std::fmt;
+use std::rc::Rc;
+use crate::script::{BinOp, ScriptError, ScriptEvent, Span};
+
+// instructions a script may execute per game tick before it is cut off.
+pub static SCRIPT_INSTRUCTION_BUDGET: u32 = 5000;
+// game state lookups a paused run remembers for the debugger, oldest dropped first.
+pub static QUERY_LOG_LIMIT: usize = 12;
+// longest text + may build, a loop doubling a string would otherwise eat all memory.
+pub static MAX_STRING_LEN: usize = 1024;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Nil,
+    Bool(bool),
+    Number(f64),
+    Str(String),
+    Unit(u32), // a game entity
+    Record(&'static str, Rc<Vec<(&'static str, Value)>>), // kind and named fields, e.g. a debuff
+}
+
+impl Value {
+    pub fn truthy(&self) -> bool {
+        match self {
+            Value::Nil => false,
+            Value::Bool(b) => *b,
+            Value::Number(n) => *n != 0.0,
+            Value::Str(s) => !s.is_empty(),
+            Value::Unit(_) | Value::Record(..) => true,
+        }
+    }
+
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Nil => "nil",
+            Value::Bool(_) => "true/false",
+            Value::Number(_) => "number",
+            Value::Str(_) => "text",
+            Value::Unit(_) => "unit",
+            Value::Record(kind, _) => kind,
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Nil => write!(f, "nil"),
+            Value::Bool(b) => write!(f, "{}", b),
+            Value::Number(n) if n.fract() == 0.0 && n.abs() < 1e15 => write!(f, "{}", *n as i64),
+            Value::Number(n) => write!(f, "{:.2}", n),
+            Value::Str(s) => write!(f, "{}", s),
+            Value::Unit(id) => write!(f, "unit #{}", id),
+            Value::Record(kind, fields) => {
+                write!(f, "{} {{", kind)?;
+                for (i, (name, value)) in fields.iter().enumerate() {
+                    let sep = if i == 0 { " " } else { ", " };
+                    write!(f, "{}{}: {}", sep, name, value)?;
+                }
+                write!(f, " }}")
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Op {
+    Const(usize),
+    Load(usize),
+    Store(usize),
+    Global(usize),        // name index
+    Field(usize),         // name index
+    Method(usize, usize), // name index, argument count
+    Call(usize, usize),   // name index, argument count
+    Neg,
+    Not,
+    Binary(BinOp),
+    Jump(usize),
+    JumpIfFalse(usize),
+    JumpIfFalseOrPop(usize), // keeps the value when jumping, for `and`
+    JumpIfTrueOrPop(usize),  // keeps the value when jumping, for `or`
+    Pop,
+    Return, // ends the run, between the main script and its handlers
+}
+
+// where an event handler's code starts, filter is its argument or nil.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Handler {
+    pub event: ScriptEvent,
+    pub filter: Value,
+    pub start: usize,
+}
+
+// a compiled script, see script::compile.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Program {
+    pub code: Vec<Op>,
+    pub spans: Vec<Span>, // where in the source each op came from
+    pub constants: Vec<Value>,
+    pub names: Vec<String>,
+    pub slot_names: Vec<String>, // one per variable slot
+    pub stmt_starts: Vec<bool>,  // ops that begin a statement, where the debugger can stop
+    pub handlers: Vec<Handler>,
+}
+
+// what a script can ask of the game, the game implements this in scripting.rs.
+pub trait ScriptHost {
+    fn global(&mut self, name: &str) -> Result<Value, String>;
+    fn field(&mut self, unit: u32, name: &str) -> Result<Value, String>;
+    fn method(&mut self, unit: u32, name: &str, args: &[Value]) -> Result<Value, String>;
+    fn call(&mut self, name: &str, args: &[Value]) -> Result<Value, String>;
+
+    // how a unit is shown in the debugger.
+    fn unit_name(&self, unit: u32) -> String {
+        format!("unit #{}", unit)
+    }
+}
+
+// where a run should stop early.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StopAt<'b> {
+    Breakpoints(&'b [u32]), // 1-based lines
+    NextStatement,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Exit {
+    Finished,
+    Paused, // before the statement at pc, run again to carry on
+}
+
+// one run of a program from the top.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Vm {
+    pub pc: usize,
+    pub stack: Vec<Value>,
+    pub slots: Vec<Value>,
+    pub executed: u32, // instructions so far
+    pub resume_at: Option<usize>, // the statement it paused before, not stopped at twice
+    pub queries: Vec<(String, Value)>, // what it asked the game, newest last
+}
+
+impl Vm {
+    pub fn new(program: &Program) -> Self {
+        Vm {
+            pc: 0,
+            stack: Vec::new(),
+            slots: vec![Value::Nil; program.slot_names.len()],
+            executed: 0,
+            resume_at: None,
+            queries: Vec::new(),
+        }
+    }
+
+    // a run of one of the program's event handlers.
+    pub fn at(program: &Program, start: usize) -> Self {
+        Vm { pc: start, ..Vm::new(program) }
+    }
+
+    // the source line of the next statement, where a paused run stopped.
+    pub fn line(&self, program: &Program) -> Option<u32> {
+        program.spans.get(self.pc).map(|span| span.line)
+    }
+
+    // variables that have been given a value so far, innermost last.
+    pub fn variables(&self, program: &Program) -> Vec<(String, Value)> {
+        program.slot_names.iter().zip(&self.slots)
+            .filter(|(name, value)| !name.starts_with(' ') && **value != Value::Nil)
+            .map(|(name, value)| (name.clone(), value.clone()))
+            .collect()
+    }
+
+    fn log_query(&mut self, query: String, value: &Value) {
+        self.queries.push((query, value.clone()));
+        if self.queries.len() > QUERY_LOG_LIMIT {
+            self.queries.remove(0);
+        }
+    }
+
+    fn should_stop(&self, program: &Program, stop: StopAt) -> bool {
+        if !program.stmt_starts.get(self.pc).copied().unwrap_or(false) || self.resume_at == Some(self.pc) {
+            return false;
+        }
+        match stop {
+            StopAt::Breakpoints(lines) => self.line(program).is_some_and(|line| lines.contains(&line)),
+            StopAt::NextStatement => true,
+        }
+    }
+
+    pub fn finished(&self, program: &Program) -> bool {
+        self.pc >= program.code.len()
+    }
+
+    fn pop(&mut self) -> Value {
+        self.stack.pop().unwrap_or(Value::Nil)
+    }
+
+    fn pop_args(&mut self, count: usize) -> Vec<Value> {
+        let at = self.stack.len().saturating_sub(count);
+        self.stack.split_off(at)
+    }
+
+    // runs to the end of the program or until stop says to pause, errors once budget more
+    // instructions have run.
+    pub fn run(&mut self, program: &Program, host: &mut dyn ScriptHost, budget: u32, stop: StopAt) -> Result<Exit, ScriptError> {
+        let limit = self.executed.saturating_add(budget);
+        while !self.finished(program) {
+            if self.should_stop(program, stop) {
+                self.resume_at = Some(self.pc);
+                return Ok(Exit::Paused);
+            }
+            if self.executed >= limit {
+                let span = program.spans.get(self.pc).copied().unwrap_or_default();
+                return Err(ScriptError::new(span, format!(
+                    "ran out of its budget of {} instructions this tick, is there a loop that never ends?", budget)));
+            }
+            self.executed += 1;
+            let span = program.spans[self.pc];
+            self.step(program, host).map_err(|message| ScriptError::new(span, message))?;
+            self.resume_at = None;
+        }
+        Ok(Exit::Finished)
+    }
+
+    fn step(&mut self, program: &Program, host: &mut dyn ScriptHost) -> Result<(), String> {
+        let op = program.code[self.pc];
+        self.pc += 1;
+        match op {
+            Op::Const(i) => self.stack.push(program.constants[i].clone()),
+            Op::Load(slot) => self.stack.push(self.slots[slot].clone()),
+            Op::Store(slot) => self.slots[slot] = self.pop(),
+            Op::Global(name) => {
+                let value = host.global(&program.names[name])?;
+                self.log_query(program.names[name].clone(), &value);
+                self.stack.push(value);
+            }
+            Op::Field(name) => {
+                let name = &program.names[name];
+                let value = match self.pop() {
+                    Value::Unit(id) => {
+                        let value = host.field(id, name)?;
+                        self.log_query(format!("{}.{}", host.unit_name(id), name), &value);
+                        value
+                    }
+                    Value::Record(kind, fields) => fields.iter()
+                        .find(|(field, _)| field == name)
+                        .map(|(_, value)| value.clone())
+                        .ok_or_else(|| format!("{} has no field '{}'", kind, name))?,
+                    Value::Nil => return Err(format!("can't read '{}' of nil, is there a target?", name)),
+                    other => return Err(format!("{} has no field '{}'", other.type_name(), name)),
+                };
+                self.stack.push(value);
+            }
+            Op::Method(name, argc) => {
+                let name = &program.names[name];
+                let args = self.pop_args(argc);
+                let value = match self.pop() {
+                    Value::Unit(id) => {
+                        let value = host.method(id, name, &args)?;
+                        self.log_query(format!("{}.{}({})", host.unit_name(id), name, list(&args)), &value);
+                        value
+                    }
+                    Value::Nil => return Err(format!("can't call '{}' on nil, is there a target?", name)),
+                    other => return Err(format!("{} has no method '{}'", other.type_name(), name)),
+                };
+                self.stack.push(value);
+            }
+            Op::Call(name, argc) => {
+                let args = self.pop_args(argc);
+                let name = &program.names[name];
+                let value = host.call(name, &args)?;
+                self.log_query(format!("{}({})", name, list(&args)), &value);
+                self.stack.push(value);
+            }
+            Op::Neg => match self.pop() {
+                Value::Number(n) => self.stack.push(Value::Number(-n)),
+                other => return Err(format!("can't negate {}", other.type_name())),
+            },
+            Op::Not => {
+                let value = self.pop();
+                self.stack.push(Value::Bool(!value.truthy()));
+            }
+            Op::Binary(op) => {
+                let right = self.pop();
+                let left = self.pop();
+                self.stack.push(binary(op, left, right)?);
+            }
+            Op::Jump(to) => self.pc = to,
+            Op::JumpIfFalse(to) => {
+                if !self.pop().truthy() {
+                    self.pc = to;
+                }
+            }
+            Op::JumpIfFalseOrPop(to) => {
+                if self.stack.last().is_some_and(|v| !v.truthy()) {
+                    self.pc = to;
+                } else {
+                    self.pop();
+                }
+            }
+            Op::JumpIfTrueOrPop(to) => {
+                if self.stack.last().is_some_and(|v| v.truthy()) {
+                    self.pc = to;
+                } else {
+                    self.pop();
+                }
+            }
+            Op::Pop => {
+                self.pop();
+            }
+            Op::Return => self.pc = program.code.len(),
+        }
+        Ok(())
+    }
+}
+
+fn list(args: &[Value]) -> String {
+    args.iter().map(|arg| match arg {
+        Value::Str(s) => format!("\"{}\"", s),
+        other => other.to_string(),
+    }).collect::<Vec<_>>().join(", ")
+}
+
+fn binary(op: BinOp, left: Value, right: Value) -> Result<Value, String> {
+    let value = match (op, &left, &right) {
+        (BinOp::Eq, _, _) => Value::Bool(left == right),
+        (BinOp::Ne, _, _) => Value::Bool(left != right),
+        (BinOp::Add, Value::Str(_), _) | (BinOp::Add, _, Value::Str(_)) => {
+            let joined = format!("{}{}", left, right);
+            if joined.len() > MAX_STRING_LEN {
+                return Err("text too long".to_string());
+            }
+            Value::Str(joined)
+        }
+        (_, Value::Number(a), Value::Number(b)) => {
+            let (a, b) = (*a, *b);
+            match op {
+                BinOp::Add => Value::Number(a + b),
+                BinOp::Sub => Value::Number(a - b),
+                BinOp::Mul => Value::Number(a * b),
+                BinOp::Div | BinOp::Rem if b == 0.0 => return Err("division by zero".to_string()),
+                BinOp::Div => Value::Number(a / b),
+                BinOp::Rem => Value::Number(a % b),
+                BinOp::Lt => Value::Bool(a < b),
+                BinOp::Le => Value::Bool(a <= b),
+                BinOp::Gt => Value::Bool(a > b),
+                BinOp::Ge => Value::Bool(a >= b),
+                BinOp::Eq | BinOp::Ne => unreachable!(),
+            }
+        }
+        _ => return Err(format!("can't use '{}' on {} and {}", op.symbol(), left.type_name(), right.type_name())),
+    };
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::script::{compile, Feature, FeatureGate};
+
+    // keeps what the script printed and cast, answers nothing else.
+    #[derive(Default)]
+    struct TestHost {
+        printed: Vec<Value>,
+        cast: Vec<Value>,
+    }
+
+    impl ScriptHost for TestHost {
+        fn global(&mut self, _name: &str) -> Result<Value, String> {
+            Ok(Value::Nil)
+        }
+        fn field(&mut self, _unit: u32, _name: &str) -> Result<Value, String> {
+            Ok(Value::Nil)
+        }
+        fn method(&mut self, _unit: u32, _name: &str, _args: &[Value]) -> Result<Value, String> {
+            Ok(Value::Nil)
+        }
+        fn call(&mut self, name: &str, args: &[Value]) -> Result<Value, String> {
+            match name {
+                "print" => self.printed.extend_from_slice(args),
+                "cast" => self.cast.extend_from_slice(args),
+                _ => {}
+            }
+            Ok(Value::Bool(true))
+        }
+    }
+
+    fn run(source: &str) -> (Result<Exit, ScriptError>, TestHost, Vm) {
+        let features = FeatureGate { unlocked: Feature::ALL.to_vec(), hints: vec![] };
+        let program = compile(source, &["miasma".to_string()], &features).expect("script should compile");
+        let mut host = TestHost::default();
+        let mut vm = Vm::new(&program);
+        let result = vm.run(&program, &mut host, SCRIPT_INSTRUCTION_BUDGET, StopAt::Breakpoints(&[]));
+        (result, host, vm)
+    }
+
+    fn printed(source: &str) -> Vec<Value> {
+        let (result, host, vm) = run(source);
+        assert_eq!(result.expect("script should run"), Exit::Finished);
+        assert!(vm.stack.is_empty(), "stack left behind: {:?}", vm.stack);
+        host.printed
+    }
+
+    fn error(source: &str) -> String {
+        run(source).0.expect_err("script should fail").message
+    }
+
+    #[test]
+    fn break_and_continue_jump_to_the_right_loop() {
+        let source = "let n = 0
+for i in 0..3 {
+    for j in 0..10 {
+        if j == 2 { break }
+        n = n + 1
+    }
+    if i == 1 { continue }
+    n = n + 100
+}
+print(n)
+let i = 0
+let odd = 0
+while i < 10 {
+    i = i + 1
+    if i % 2 == 0 { continue }
+    odd = odd + 1
+}
+print(odd)";
+        assert_eq!(printed(source), vec![Value::Number(206.0), Value::Number(5.0)]);
+    }
+
+    #[test]
+    fn and_or_short_circuit_without_leaking_the_stack() {
+        let source = "let a = false and cast(\"miasma\")
+let b = true or cast(\"miasma\")
+let c = 0 or \"x\"
+if false and cast(\"miasma\") { print(1) }
+if true or cast(\"miasma\") { print(2) }
+print(a)
+print(b)
+print(c)";
+        let (result, host, vm) = run(source);
+        assert_eq!(result.expect("script should run"), Exit::Finished);
+        assert!(host.cast.is_empty(), "short circuit still cast {:?}", host.cast);
+        assert!(vm.stack.is_empty(), "stack left behind: {:?}", vm.stack);
+        assert_eq!(host.printed, vec![Value::Number(2.0), Value::Bool(false), Value::Bool(true), Value::Str("x".to_string())]);
+    }
+
+    #[test]
+    fn else_if_takes_only_the_first_true_branch() {
+        let source = "for i in 0..4 {
+    if i == 0 { print(\"a\") } else if i == 1 { print(\"b\") } else if i < 3 { print(\"c\") } else { print(\"d\") }
+}";
+        let words: Vec<Value> = ["a", "b", "c", "d"].iter().map(|s| Value::Str(s.to_string())).collect();
+        assert_eq!(printed(source), words);
+    }
+
+    #[test]
+    fn endless_loop_runs_out_of_budget() {
+        assert!(error("while true { }").contains("budget"));
+    }
+
+    #[test]
+    fn division_by_zero_is_an_error() {
+        assert_eq!(error("print(1 / 0)"), "division by zero");
+        assert_eq!(error("let x = 0\nprint(5 % x)"), "division by zero");
+    }
+
+    #[test]
+    fn doubling_text_stops_at_the_cap() {
+        assert_eq!(error("let s = \"xx\"\nwhile true {\n    s = s + s\n}"), "text too long");
+        let fits = format!("print(\"{}\" + \"y\")", "x".repeat(MAX_STRING_LEN - 1));
+        assert_eq!(printed(&fits).len(), 1);
+    }
+}
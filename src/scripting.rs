@@ -0,0 +1,241 @@
+use std::rc::Rc;
+use sfml::graphics::Color;
+use crate::game::*;
+use crate::script::{self, ScriptError, ScriptEvent};
+use crate::script_vm::{Exit, Program, ScriptHost, StopAt, Value, Vm, SCRIPT_INSTRUCTION_BUDGET};
+
+// the player's rotation script, run from the top every tick while it is running. its source is
+// whatever is in the code editor when the run button is pressed. its event handlers run when the
+// matching combat events happen.
+pub struct RotationScript {
+    pub program: Option<Rc<Program>>,
+    pub running: bool,
+    pub error: Option<ScriptError>,
+    pub run_revision: u64,                 // editor revision the running program was compiled from
+    pub diagnostic: Option<ScriptError>,   // what's wrong with the editor's text, see check_code_editor
+    pub checked_revision: Option<u64>,
+    pub suspended: Option<Vm>, // a run stopped by the debugger, carried on instead of starting over
+    pub paused: bool,          // the game waits while the debugger shows a stopped run
+    pub break_on_next: bool,   // stop before the next statement that runs, for stepping
+    pub pending_events: Vec<(usize, Value)>, // handler start and the event it is run for
+    pub event: Option<Value>,  // what the running handler is handling
+    pub last_health_pct: f64,  // player's health last tick, for on_health_below
+}
+
+impl RotationScript {
+    pub fn new() -> Self {
+        RotationScript {
+            program: None,
+            running: false,
+            error: None,
+            run_revision: 0,
+            diagnostic: None,
+            checked_revision: None,
+            suspended: None,
+            paused: false,
+            break_on_next: false,
+            pending_events: Vec::new(),
+            event: None,
+            last_health_pct: 1.0,
+        }
+    }
+}
+
+impl Game<'_> {
+    // the run button starts the script in the editor, or stops the one that is running.
+    pub fn toggle_script(&mut self) {
+        if self.script.running {
+            self.stop_script();
+            self.set_script_status("Script stopped", OFF_TEXT_CLR);
+            return;
+        }
+
+        self.save_active_script();
+        match script::compile(&self.code_editor_text(), &self.script_spell_names(), &self.script_unlocks.gate()) {
+            Ok(program) => {
+                self.script.run_revision = self.code_editor().map(|editor| editor.revision).unwrap_or(0);
+                println!("Script compiled to {} instructions", program.code.len());
+                self.script.program = Some(Rc::new(program));
+                self.script.running = true;
+                self.script.error = None;
+                self.script.last_health_pct = self.player_health_pct();
+                self.set_run_button_label("Stop Code");
+                self.set_script_status("Script running", HEAL_COLOR);
+            }
+            Err(e) => {
+                println!("Script error: {}", e);
+                self.set_script_status(&e.to_string(), DAMAGE_TAKEN_COLOR);
+                self.script.error = Some(e);
+            }
+        }
+    }
+
+    pub fn stop_script(&mut self) {
+        if self.script.paused {
+            self.leave_debugger();
+        }
+        self.script.running = false;
+        self.script.program = None;
+        self.script.suspended = None;
+        self.script.break_on_next = false;
+        self.script.pending_events.clear();
+        self.script.event = None;
+        self.set_run_button_label("Run Code");
+    }
+
+    // runs the script once with a fresh set of variables, or finishes the run the debugger
+    // stopped. an error ends this tick's run and is shown under the editor, the script tries
+    // again next tick.
+    pub fn s_script(&mut self) {
+        if !self.script.running || self.is_defeated() {
+            return;
+        }
+        let Some(program) = self.script.program.clone() else {
+            return;
+        };
+
+        let mut vm = self.script.suspended.take().unwrap_or_else(|| Vm::new(&program));
+        let breakpoints = self.script_breakpoints();
+        let stop = if self.script.break_on_next { StopAt::NextStatement } else { StopAt::Breakpoints(&breakpoints) };
+        match vm.run(&program, self, SCRIPT_INSTRUCTION_BUDGET, stop) {
+            Ok(Exit::Finished) => self.script_finished(),
+            Ok(Exit::Paused) => self.pause_script(vm),
+            Err(e) => self.script_failed(e),
+        }
+    }
+
+    // queues the handlers for something that just happened, spell is the spell's tag.
+    pub fn script_event(&mut self, event: ScriptEvent, spell: Option<&str>, unit: Option<u32>) {
+        if !self.script.running {
+            return;
+        }
+        let Some(program) = self.script.program.clone() else {
+            return;
+        };
+        let handlers = crate::combat::script_event_handlers(&program, &self.spellbook, event, spell, unit);
+        self.script.pending_events.extend(handlers);
+    }
+
+    // runs the handlers queued this tick, each with the same budget as the main script. a handler
+    // the debugger stops is carried on by s_script, the rest wait until it is done.
+    pub fn s_script_events(&mut self) {
+        if !self.script.running || self.script.paused || self.is_defeated() {
+            return;
+        }
+        let Some(program) = self.script.program.clone() else {
+            return;
+        };
+
+        // on_health_below fires once each time health crosses its line
+        let health_pct = self.player_health_pct();
+        let last = std::mem::replace(&mut self.script.last_health_pct, health_pct);
+        let handlers = crate::combat::script_health_handlers(&program, last, health_pct, self.gem.player_id);
+        self.script.pending_events.extend(handlers);
+
+        let breakpoints = self.script_breakpoints();
+        while !self.script.pending_events.is_empty() && self.script.suspended.is_none() {
+            let (start, event) = self.script.pending_events.remove(0);
+            self.script.event = Some(event);
+            let mut vm = Vm::at(&program, start);
+            match vm.run(&program, self, SCRIPT_INSTRUCTION_BUDGET, StopAt::Breakpoints(&breakpoints)) {
+                Ok(Exit::Finished) => self.script_finished(),
+                Ok(Exit::Paused) => self.pause_script(vm),
+                Err(e) => self.script_failed(e),
+            }
+        }
+    }
+
+    fn player_health_pct(&self) -> f64 {
+        self.gem.player_id
+            .and_then(|pid| self.gem.stats.get(&pid))
+            .map(|stats| stats.health_curr as f64 / stats.health_max.max(1) as f64)
+            .unwrap_or(1.0)
+    }
+
+    pub fn script_finished(&mut self) {
+        self.script.event = None;
+        if self.script.error.take().is_some() {
+            self.set_script_status("Script running", HEAL_COLOR);
+        }
+    }
+
+    pub fn script_failed(&mut self, e: ScriptError) {
+        self.script.event = None;
+        if self.script.error.as_ref() != Some(&e) {
+            println!("Script error: {}", e);
+            self.set_script_status(&e.to_string(), DAMAGE_TAKEN_COLOR);
+            self.script.error = Some(e);
+        }
+    }
+
+    pub fn set_script_status(&mut self, msg: &str, color: Color) {
+        let scale = get_scale();
+        let s = |x: u32| scaled(scale, x);
+
+        // two lines fit between the editor and the xp bar
+        let wrapped = crate::helpers::wrap_text(msg, &self.gbfnt, scale * 20, s(600) as f32);
+        let shown: Vec<&str> = wrapped.lines().take(2).collect();
+
+        let Some(id) = self.em.get_id_by_tag("script_status".to_string()) else {
+            return;
+        };
+        if let Some(text) = self.em.get_ptexts_mut(id).and_then(|texts| texts.get_mut(0)) {
+            text.text = shown.join("\n");
+            text.colors.fill = (color.r, color.g, color.b);
+            text.draw = true;
+        }
+    }
+
+    fn set_run_button_label(&mut self, label: &str) {
+        let Some(id) = self.em.get_id_by_tag("run_button".to_string()) else {
+            return;
+        };
+        if let Some(text) = self.em.get_ptexts_mut(id).and_then(|texts| texts.get_mut(0)) {
+            text.text = label.to_string();
+        }
+    }
+
+    // names a script may refer to spells by.
+    pub fn script_spell_names(&self) -> Vec<String> {
+        self.spellbook.spells.iter()
+            .flat_map(|spell| [spell.id.tag().to_string(), spell.name.clone()])
+            .collect()
+    }
+}
+
+// the game answers the event, cast() and print() itself, the rest is the same in the simulator.
+impl ScriptHost for Game<'_> {
+    fn global(&mut self, name: &str) -> Result<Value, String> {
+        if name == "event" {
+            return Ok(self.script.event.clone().unwrap_or(Value::Nil));
+        }
+        self.combat().script_global(name)
+    }
+
+    fn field(&mut self, unit: u32, name: &str) -> Result<Value, String> {
+        self.combat().script_field(unit, name)
+    }
+
+    fn method(&mut self, unit: u32, name: &str, args: &[Value]) -> Result<Value, String> {
+        self.combat().script_method(unit, name, args)
+    }
+
+    fn call(&mut self, name: &str, args: &[Value]) -> Result<Value, String> {
+        match name {
+            "cast" => {
+                let sp = self.combat().script_spell(args, name)?;
+                Ok(Value::Bool(self.try_queue_spell_cast(sp).is_ok()))
+            }
+            "print" => {
+                let msg = args.first().cloned().unwrap_or(Value::Nil).to_string();
+                self.set_script_status(&format!("> {}", msg), MAIN_TEXT_CLR);
+                Ok(Value::Nil)
+            }
+            _ => self.combat().script_call(name, args),
+        }
+    }
+
+    fn unit_name(&self, unit: u32) -> String {
+        crate::combat::script_unit_name(&self.gem, unit)
+    }
+}
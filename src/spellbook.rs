@@ -0,0 +1,85 @@
+use std::fs;
+use std::path::Path;
+use serde::{Serialize, Deserialize};
+use crate::g_properties::{SpellData, Spells};
+
+pub static SPELLBOOK_PATH: &str = "./src/assets/data/spells.json";
+pub static SPRITES_PATH: &str = "./src/assets/sprites";
+
+// every spell in the game is defined once in SPELLBOOK_PATH and looked up through here.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Spellbook {
+    pub spells: Vec<SpellData>,
+}
+
+impl Spellbook {
+    pub fn load(path: &str) -> Result<Spellbook, String> {
+        let raw = fs::read_to_string(path)
+            .map_err(|e| format!("could not read spellbook {}: {}", path, e))?;
+        let book: Spellbook = serde_json::from_str(&raw)
+            .map_err(|e| format!("could not parse spellbook {}: {}", path, e))?;
+        book.validate(SPRITES_PATH)?;
+        Ok(book)
+    }
+
+    pub fn validate(&self, sprites_dir: &str) -> Result<(), String> {
+        if self.spells.is_empty() {
+            return Err("spellbook has no spells".to_string());
+        }
+
+        let texture_exists = |id: &str| Path::new(sprites_dir).join(format!("{}.png", id)).is_file();
+
+        for (i, spell) in self.spells.iter().enumerate() {
+            let tag = spell.id.tag();
+            if self.spells.iter().skip(i + 1).any(|other| other.id == spell.id) {
+                return Err(format!("spell '{}' is defined more than once", tag));
+            }
+            if spell.name.trim().is_empty() {
+                return Err(format!("spell '{}' has no name", tag));
+            }
+            if !texture_exists(&spell.icon) {
+                return Err(format!("spell '{}' uses missing icon '{}'", tag, spell.icon));
+            }
+            if spell.dps > 0 && (spell.duration == 0 || spell.coefficient == 0) {
+                return Err(format!("spell '{}' has dps but no duration or coefficient", tag));
+            }
+            if spell.dps > 0 && spell.tick_interval == 0 {
+                return Err(format!("spell '{}' has dps but a tick interval of 0", tag));
+            }
+            if let Some(contagion) = &spell.contagion {
+                if spell.dps == 0 {
+                    return Err(format!("spell '{}' is contagious but has no dps to spread with", tag));
+                }
+                if !(0.0..=1.0).contains(&contagion.chance) {
+                    return Err(format!("spell '{}' contagion chance must be between 0 and 1", tag));
+                }
+            }
+            if let Some(execute) = &spell.execute {
+                if spell.upfront_dam == 0 {
+                    return Err(format!("spell '{}' is an execute but deals no upfront damage", tag));
+                }
+                if execute.missing_health_bonus < 0.0 || execute.per_dot_bonus < 0.0 {
+                    return Err(format!("spell '{}' execute bonuses can't be negative", tag));
+                }
+            }
+            for anim in [spell.cast_anim.as_ref(), spell.projectile_anim.as_ref()].into_iter().flatten() {
+                if anim.frames == 0 {
+                    return Err(format!("spell '{}' animation '{}' has no frames", tag, anim.texture));
+                }
+                if !texture_exists(&anim.texture) {
+                    return Err(format!("spell '{}' uses missing animation '{}'", tag, anim.texture));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn get(&self, sp: &Spells) -> Option<&SpellData> {
+        self.spells.iter().find(|spell| spell.id == *sp)
+    }
+
+    // accepts either the id ("miasma") or the display name ("Miasma").
+    pub fn get_by_name(&self, name: &str) -> Option<&SpellData> {
+        self.spells.iter().find(|spell| spell.id.tag() == name || spell.name == name)
+    }
+}
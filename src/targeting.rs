@@ -0,0 +1,49 @@
+use crate::game::*;
+
+impl Game<'_> {
+    pub fn player_target(&mut self) -> Option<u32> {
+        self.combat().player_target() // branch to combat.rs
+    }
+
+    pub fn set_player_target(&mut self, target: Option<u32>) {
+        self.combat().set_player_target(target);
+    }
+
+    pub fn target_next_enemy(&mut self) {
+        let current = self.player_target();
+        let next = self.gem.next_enemy_after(current);
+        self.set_player_target(next);
+    }
+
+    pub fn target_nearest_enemy(&mut self) {
+        if let Some(player_id) = self.gem.player_id {
+            let nearest = self.gem.nearest_enemy(player_id);
+            self.set_player_target(nearest);
+        }
+    }
+
+    pub fn target_lowest_health_enemy(&mut self) {
+        let lowest = self.gem.lowest_health_enemy();
+        self.set_player_target(lowest);
+    }
+
+    // targets the living enemy whose sprite is under (x, y), returns false if there is none.
+    pub fn target_enemy_at(&mut self, x: u32, y: u32) -> bool {
+        let hit = self.anims.active.iter()
+            .rev()
+            .filter_map(|sprite| sprite.associated_g_entity.map(|id| (id, sprite)))
+            .filter(|(id, _)| self.gem.get_all_enemies().contains(id) && self.gem.is_alive(*id))
+            .find(|(_, sprite)| {
+                let w = sprite.desired_width.unwrap_or(sprite.frame_width);
+                let h = sprite.desired_height.unwrap_or(sprite.frame_height);
+                x >= sprite.position.0 && x <= sprite.position.0 + w
+                    && y >= sprite.position.1 && y <= sprite.position.1 + h
+            })
+            .map(|(id, _)| id);
+
+        if hit.is_some() {
+            self.set_player_target(hit);
+        }
+        hit.is_some()
+    }
+}
@@ -0,0 +1,250 @@
+use std::collections::HashMap;
+use sfml::window::Key;
+use crate::bestiary::SpawnData;
+use crate::game::*;
+use crate::g_properties::DamageSchool;
+use crate::properties::*;
+use crate::combat::{HealthChange, HitOutcome};
+use crate::combat_log::TrainingStats;
+
+pub static TRAINING_DUMMY_ID: &str = "training_dummy"; // in the bestiary
+pub static TRAINING_PANEL_TAG: &str = "training_panel";
+pub static DUMMY_POSITION: (u32, u32) = (1480, 190);
+pub static DUMMY_SIZE: u32 = 256;
+pub static DEFAULT_DUMMY_LEVEL: u32 = 5;
+pub static MAX_DUMMY_LEVEL: u32 = 60;
+pub static RESISTANCE_STEPS: [u32; 5] = [0, 25, 50, 75, 100]; // clicking a school cycles through these
+
+// where the resistance rows are in the panel, unscaled.
+pub static RESISTANCE_ROWS_TOP: u32 = 200;
+pub static RESISTANCE_ROW_HEIGHT: u32 = 30;
+
+static TRAINING_PANEL_BUTTONS: [&str; 4] = ["training_dummy_button", "training_reset_button", "training_level_down_button", "training_level_up_button"];
+
+// the dummy's setup is kept while it is gone, so it comes back the way it was.
+pub struct Training {
+    pub dummy: Option<u32>,
+    pub level: u32,
+    pub resistances: HashMap<DamageSchool, u32>,
+    pub stats: TrainingStats,
+}
+
+impl Training {
+    pub fn new() -> Self {
+        Training {
+            dummy: None,
+            level: DEFAULT_DUMMY_LEVEL,
+            resistances: HashMap::new(),
+            stats: TrainingStats::default(),
+        }
+    }
+
+    pub fn resistance(&self, school: DamageSchool) -> u32 {
+        self.resistances.get(&school).copied().unwrap_or(0)
+    }
+}
+
+impl Game<'_> {
+    pub fn training_panel_open(&self) -> bool {
+        self.state[6] == 1
+    }
+
+    // the dummy replaces the encounter, which comes back once the dummy is removed.
+    pub fn spawn_training_dummy(&mut self) {
+        if self.challenge.is_some() || self.is_defeated() {
+            self.set_script_status("Finish what you are doing first", WARNING_COLOR);
+            return;
+        }
+        if self.bestiary.get(TRAINING_DUMMY_ID).is_none() {
+            println!("No {} in the bestiary", TRAINING_DUMMY_ID);
+            return;
+        }
+        self.place_training_dummy();
+    }
+
+    fn place_training_dummy(&mut self) {
+        self.clear_enemies();
+        let spawn = SpawnData {
+            enemy: TRAINING_DUMMY_ID.to_string(),
+            tag: TRAINING_DUMMY_ID.to_string(),
+            level: self.training.level,
+            position: DUMMY_POSITION,
+            size: DUMMY_SIZE,
+        };
+        let spawned = self.spawn_enemies(&[spawn]);
+        self.training.dummy = spawned.first().map(|(_, id)| *id);
+        self.apply_dummy_resistances();
+        self.reset_training_stats();
+        self.update_training_buttons();
+    }
+
+    pub fn remove_training_dummy(&mut self) {
+        if self.training.dummy.take().is_none() {
+            return;
+        }
+        self.clear_enemies();
+        self.spawn_encounter(self.encounter_index);
+        self.update_training_buttons();
+    }
+
+    pub fn toggle_training_dummy(&mut self) {
+        if self.training.dummy.is_some() {
+            self.remove_training_dummy();
+        } else {
+            self.spawn_training_dummy();
+        }
+    }
+
+    pub fn reset_training_stats(&mut self) {
+        self.training.stats = TrainingStats::default();
+    }
+
+    // a dummy that is up is spawned again at the new level.
+    pub fn change_dummy_level(&mut self, delta: i32) {
+        let level = (self.training.level as i32 + delta).clamp(1, MAX_DUMMY_LEVEL as i32) as u32;
+        if level == self.training.level {
+            return;
+        }
+        self.training.level = level;
+        if self.training.dummy.is_some() {
+            self.place_training_dummy();
+        }
+    }
+
+    // numbers from before the change would muddy the averages, so the stats start over.
+    pub fn cycle_dummy_resistance(&mut self, school: DamageSchool) {
+        let curr = self.training.resistance(school);
+        let next = RESISTANCE_STEPS.iter()
+            .copied()
+            .find(|&step| step > curr)
+            .unwrap_or(RESISTANCE_STEPS[0]);
+        self.training.resistances.insert(school, next);
+        self.apply_dummy_resistances();
+        if self.training.dummy.is_some() {
+            self.reset_training_stats();
+        }
+    }
+
+    fn apply_dummy_resistances(&mut self) {
+        let Some(dummy) = self.training.dummy else {
+            return;
+        };
+        if let Some(resistances) = self.gem.resistances.get_mut(&dummy) {
+            resistances.values = self.training.resistances.clone();
+        }
+    }
+
+    // the player's hits on the dummy, see s_health.
+    pub fn track_training_damage(&mut self, change: &HealthChange) {
+        if self.training.dummy != Some(change.target) || self.gem.player_id != Some(change.source) || change.amount >= 0 {
+            return;
+        }
+        let spell = change.spell.as_ref()
+            .and_then(|sp| self.spellbook.get(sp))
+            .map(|spell| spell.name.clone())
+            .unwrap_or_else(|| "attack".to_string());
+        let crit = change.outcome == HitOutcome::Critical;
+        self.training.stats.record(self.time_elapsed_ms, &spell, change.amount.unsigned_abs(), crit);
+    }
+
+    // adds up how long each of the player's debuffs is on the dummy.
+    pub fn s_training(&mut self) {
+        let Some(dummy) = self.training.dummy else {
+            return;
+        };
+        // whatever clears the battlefield, like a challenge or a retry, takes the dummy with it
+        if !self.gem.stats.contains_key(&dummy) {
+            self.training.dummy = None;
+            self.update_training_buttons();
+            return;
+        }
+
+        let debuffs = self.combat().player_debuffs_on(dummy);
+        self.training.stats.track_uptime(self.time_elapsed_ms, self.delta_time_ms, debuffs);
+    }
+
+    // ------------------------------------------------------------ the panel
+
+    pub fn toggle_training_panel(&mut self) {
+        if self.training_panel_open() {
+            self.close_training_panel();
+            return;
+        }
+        self.state[6] = 1;
+        self.show_training_panel();
+    }
+
+    pub fn close_training_panel(&mut self) {
+        self.state[6] = 0;
+        self.em.purge_entity_by_tag(TRAINING_PANEL_TAG);
+        for tag in TRAINING_PANEL_BUTTONS {
+            self.em.purge_entity_by_tag(tag);
+        }
+    }
+
+    // clicking a resistance row cycles that school through RESISTANCE_STEPS.
+    pub fn training_mouse_pressed(&mut self, x: u32, y: u32) -> bool {
+        if !self.training_panel_open() {
+            return false;
+        }
+        let scale = get_scale();
+        let s = |x: u32| scaled(scale, x);
+        let top = s(RESISTANCE_ROWS_TOP);
+        let rows = DamageSchool::ALL.len() as u32;
+        if x < s(10) || x > s(620) || y < top || y >= top + s(RESISTANCE_ROW_HEIGHT) * rows {
+            return false;
+        }
+        let school = DamageSchool::ALL[((y - top) / s(RESISTANCE_ROW_HEIGHT)) as usize];
+        self.cycle_dummy_resistance(school);
+        true
+    }
+
+    pub fn training_key_pressed(&mut self, code: Key) -> bool {
+        if !self.training_panel_open() {
+            return false;
+        }
+        match code {
+            Key::Left => self.change_dummy_level(-1),
+            Key::Right => self.change_dummy_level(1),
+            Key::Escape => self.close_training_panel(),
+            _ => return false,
+        }
+        true
+    }
+
+    // covers the editor like the script library; the numbers are drawn in render_training.
+    fn show_training_panel(&mut self) {
+        let scale = get_scale();
+        let s = |x: u32| scaled(scale, x);
+
+        let panel = self.em.add_entity(Some(TRAINING_PANEL_TAG.to_string()));
+        self.em.add_property_to_entity(PropertiesEnum::Rect, panel);
+        if let Some(rect) = self.em.get_prects_mut(panel).and_then(|rects| rects.last_mut()) {
+            rect.x = s(10);
+            rect.y = s(120);
+            rect.width = s(610);
+            rect.height = s(750);
+            rect.colors = ColorPair::from_colors(ENCAPSULATION_REGIONS, Some(sfml::graphics::Color::BLACK));
+            rect.draw = true;
+            rect.strata = 10;
+        }
+        self.create_panel_button("training_dummy_button", "Spawn", "Spawn the training dummy in place of the encounter, or remove it again.", (s(20), s(810), s(140), s(50)), 20, ClickAction::TrainingDummy);
+        self.create_panel_button("training_reset_button", "Reset", "Start the damage, DPS and uptime numbers over.", (s(170), s(810), s(140), s(50)), 20, ClickAction::TrainingReset);
+        self.create_panel_button("training_level_down_button", "Level -", "Lower the dummy's level. (Left)", (s(320), s(810), s(140), s(50)), 20, ClickAction::TrainingLevelDown);
+        self.create_panel_button("training_level_up_button", "Level +", "Raise the dummy's level. (Right)", (s(470), s(810), s(140), s(50)), 20, ClickAction::TrainingLevelUp);
+        self.update_training_buttons();
+    }
+
+    fn update_training_buttons(&mut self) {
+        let label = if self.training.dummy.is_some() { "Remove" } else { "Spawn" };
+        let Some(button_eid) = self.em.get_id_by_tag(TRAINING_PANEL_BUTTONS[0].to_string()) else {
+            return;
+        };
+        if let Some(text) = self.em.get_ptexts_mut(button_eid).and_then(|texts| texts.last_mut()) {
+            text.text = label.to_string();
+        }
+        if let Some(tt) = self.em.get_tooltip_data_mut(button_eid) {
+            tt.header = label.to_string();
+        }
+    }
+}
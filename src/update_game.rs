@@ -2,6 +2,8 @@ use std::{collections::HashMap, hash::Hash};
 use serde::{Serialize, Deserialize};
 use crate::game::*;
 use crate::g_properties::*;
+use crate::combat::CastResult;
+use crate::script::ScriptEvent;
 
 impl Game<'_> {
     pub fn update_game_main_entry(&mut self) {
@@ -18,54 +20,141 @@ impl Game<'_> {
         let scale_h = WINDOW_HEIGHT as f32 / 1080.0;
         let scale = scale_w.min(scale_h).floor().max(1.0) as u32;
         let s = |x: u32| x * scale;
-        if let Some(player_id_gem) = self.gem.player_id {
-            if let Some(queue) = self.gem.actionqueue.get_mut(&player_id_gem) {
-                if let Some(current_action) = queue.queue.first_mut() {
-                    let dt_ms = (self.delta_time * 1000.0) as u32;
-                    current_action.time_remaining =
-                        current_action.time_remaining.saturating_sub(dt_ms);
+        let dt_ms = (self.delta_time * 1000.0) as u32;
+        let finished = self.combat().advance_casts(dt_ms);
+        for cast in finished {
+            if Some(cast.caster) != self.gem.player_id {
+                continue;
+            }
+            let caster = cast.caster;
+            let result = cast.result;
+            if let (CastResult::Success, Some(sp)) = (&result, cast.spell) {
+                self.script_event(ScriptEvent::CastFinished, Some(sp.tag()), cast.target);
+            }
+            if let Some(msg) = result.message() {
+                println!("Cast failed: {:?}", result);
+                self.floating_message(&msg, MAIN_TEXT_CLR);
+            }
 
-                    if current_action.time_remaining == 0 {
-                        if current_action.action_tag == "miasma" {
-                            self.state[0] = 1;
-                        }
-                        else if current_action.action_tag == "infernum" {
-                            self.state[1] = 1;
-                        }
-                        queue.queue.remove(0);
-                    }
-                }
+            let next_spell = self.gem.actionqueue.get(&caster)
+                .and_then(|q| q.queue.first())
+                .and_then(|a| a.spell.clone());
+            match next_spell {
+                Some(sp) => self.start_cast_animation(&sp),
+                None => self.restore_idle_animation(),
             }
         }
-        if !self.miasma_has_spawned {
-            if let Some(sprite) = self.anims.active.iter().find(|s| s.texture_id == "Miasma_anim2") {
-                if sprite.current_frame == sprite.total_frames - 2 && sprite.play_once {
-                    self.miasma_has_spawned = true;
-                    self.anims.add_animation_instance(crate::animation::AnimatedSprite {
-                        texture_id: "miasma_proj_anim2".to_string(),
-                        frame_width: 64,
-                        frame_height: 64,
-                        total_frames: 2,
-                        current_frame: 0,
-                        frame_time: Some(0.2),
-                        time_accumulator: 0.0,
-                        position: (s(1050), s(250)),
-                        inanimate: false,
-                        strata: 50,
-                        desired_width: Some(s(256)),
-                        desired_height: Some(s(256)),
-                        play_once: false,
-                        finished: false,
-                        velocity: (600.0, 0.0),
-                        lifetime: Some(1.2),
-                        associated_g_entity: None,
-                    });
-                }
-            }
+
+        self.update_target_frame();
+        self.animate_deaths();
+        self.check_code_editor();
+
+        // spawn the projectile of the spell being cast near the end of its cast animation
+        let casting = self.gem.player_id
+            .and_then(|pid| self.gem.actionqueue.get(&pid))
+            .and_then(|q| q.queue.first())
+            .and_then(|a| a.spell.as_ref())
+            .and_then(|sp| self.spellbook.get(sp))
+            .cloned();
+        if !self.projectile_has_spawned
+            && let Some(spell) = casting
+            && let (Some(proj), Some(cast_anim)) = (spell.projectile_anim, spell.cast_anim)
+            && let Some(sprite) = self.anims.active.iter().find(|s| s.texture_id == cast_anim.texture)
+            && sprite.current_frame == sprite.total_frames - 2 && sprite.play_once {
+            self.projectile_has_spawned = true;
+            self.anims.add_animation_instance(crate::animation::AnimatedSprite {
+                texture_id: proj.texture,
+                frame_width: 64,
+                frame_height: 64,
+                total_frames: proj.frames,
+                current_frame: 0,
+                frame_time: Some(0.2),
+                time_accumulator: 0.0,
+                position: (s(1050), s(250)),
+                inanimate: false,
+                strata: 50,
+                desired_width: Some(s(256)),
+                desired_height: Some(s(256)),
+                play_once: false,
+                finished: false,
+                velocity: (600.0, 0.0),
+                lifetime: Some(1.2),
+                associated_g_entity: None,
+            });
+        }
+
+        let projectiles: Vec<&String> = self.spellbook.spells.iter()
+            .filter_map(|spell| spell.projectile_anim.as_ref().map(|p| &p.texture))
+            .collect();
+        if !(self.anims.active.iter().any(|s| projectiles.contains(&&s.texture_id))) {
+            self.projectile_has_spawned = false;
+        }
+    }
+
+    // points the enemy info region at whatever the player has targeted.
+    fn update_target_frame(&mut self) {
+        let target = self.player_target();
+        let name = match target {
+            Some(t) => self.gem.get_name(t),
+            None => "No target".to_string(),
+        };
+
+        let Some(region) = self.em.get_id_by_tag("enemy_info_region".to_string()) else {
+            return;
+        };
+        if let Some(text) = self.em.get_ptexts_mut(region).and_then(|texts| texts.get_mut(0)) {
+            text.text = name;
+        }
+        if let Some(hb) = self.em.get_phealthbar_mut(region) {
+            hb.gem_entity_id = target;
+            hb.draw = target.is_some();
+        }
+    }
+
+    pub fn start_cast_animation(&mut self, sp: &Spells) {
+        let Some(spell) = self.spellbook.get(sp).cloned() else {
+            return;
+        };
+        let Some(cast_anim) = spell.cast_anim else {
+            return;
+        };
+        let scale = get_scale();
+        let s = |x: u32| scaled(scale, x);
+
+        self.anims.remove_sprite_by_texture("my_warlock");
+        for other in self.spellbook.spells.iter().filter_map(|spell| spell.cast_anim.as_ref()) {
+            self.anims.remove_sprite_by_texture(&other.texture);
         }
 
-        if !(self.anims.active.iter().any(|s| s.texture_id == "miasma_proj_anim2")) {
-            self.miasma_has_spawned = false;
+        let frame_time = spell.cast_time as f32 / 1000.0 / cast_anim.frames as f32;
+
+        self.anims.add_animation_instance(crate::animation::AnimatedSprite {
+            texture_id: cast_anim.texture.clone(),
+            frame_width: 64,
+            frame_height: 64,
+            total_frames: cast_anim.frames,
+            current_frame: 0,
+            frame_time: Some(frame_time),
+            time_accumulator: 0.0,
+            position: (s(1000) , s(207)),
+            inanimate: false,
+            strata: 20,
+            desired_width: Some(s(256)),
+            desired_height: Some(s(256)),
+            play_once: true,
+            finished: false,
+            velocity: (0.0, 0.0),
+            lifetime: None,
+            associated_g_entity: None,
+        });
+    }
+
+    // puts the player back into the idle pose after a cast finishes or is interrupted.
+    pub fn restore_idle_animation(&mut self) {
+        for cast_anim in self.spellbook.spells.iter().filter_map(|spell| spell.cast_anim.as_ref()) {
+            self.anims.remove_sprite_by_texture(&cast_anim.texture);
         }
+        self.anims.remove_sprite_by_texture("my_warlock");
+        self.add_player_idle_sprite();
     }
 }
@@ -1,4 +1,4 @@
-use sfml::window::{Event};
+use sfml::window::{Event, Key};
 use crate::{game::Game, properties::ClickAction};
 
 pub enum InputSlot {
@@ -31,11 +31,16 @@ impl Game<'_> {
         while let Some(event) = self.window.poll_event() {
             match event {
                 Event::Closed => {
+                    self.save_active_script();
                     self.window.close();
                 }
                 Event::MouseMoved { x, y } => {
                     self.user_input_cache[InputSlot::MouseX as usize] = x as u32;
                     self.user_input_cache[InputSlot::MouseY as usize] = y as u32;
+                    self.editor_mouse_moved(x.max(0) as u32, y.max(0) as u32);
+                }
+                Event::MouseWheelScrolled { delta, x, y, .. } => {
+                    self.editor_scrolled(delta, x.max(0) as u32, y.max(0) as u32);
                 }
                 Event::MouseButtonPressed { button, .. } => {
                     if button == sfml::window::mouse::Button::Left {
@@ -47,12 +52,37 @@ impl Game<'_> {
                         self.user_input_cache[LMBCurr as usize] = 0;
                     }
                 }
+                // each panel gets the key first, the game only sees it when none of them took it
+                Event::KeyPressed { code, ctrl, shift, .. }
+                    if !self.debugger_key_pressed(code, shift)
+                        && !self.library_key_pressed(code)
+                        && !self.challenge_key_pressed(code)
+                        && !self.training_key_pressed(code)
+                        && !self.editor_key_pressed(code, ctrl, shift) => {
+                    self.key_pressed(code);
+                }
+                Event::TextEntered { unicode } if !self.library_text_entered(unicode) => {
+                    self.editor_text_entered(unicode);
+                }
                 _ => {}
             }
         }
 
     }
 
+    fn key_pressed(&mut self, code: Key) {
+        if self.is_defeated() {
+            return;
+        }
+        match code {
+            Key::Escape => self.cancel_player_cast(),
+            Key::Tab => self.target_next_enemy(),
+            Key::N => self.target_nearest_enemy(),
+            Key::L => self.target_lowest_health_enemy(),
+            _ => {}
+        }
+    }
+
     fn dispatch_input_handling(&mut self) {
         if self.user_input_cache[LMBCurr as usize] == 1 && self.user_input_cache[LMBPrev as usize] != 1 {
             self.lmb_pressed();
@@ -85,6 +115,10 @@ impl Game<'_> {
     fn lmb_pressed(&mut self) {
         let mx = self.user_input_cache[MouseX as usize];
         let my = self.user_input_cache[MouseY as usize];
+        let shift = Key::LShift.is_pressed() || Key::RShift.is_pressed();
+        if self.library_mouse_pressed(mx, my) || self.challenge_mouse_pressed(mx, my) || self.training_mouse_pressed(mx, my) || self.editor_mouse_pressed(mx, my, shift) {
+            return;
+        }
         let buttons = self.em.get_all_buttons();
 
         let mut clicked_eids = vec![];
@@ -98,14 +132,24 @@ impl Game<'_> {
             }
         }
 
+        // while dead, only the defeat screen's buttons respond.
+        let defeated = self.is_defeated();
+        if clicked_eids.is_empty() && !defeated {
+            self.target_enemy_at(mx, my);
+        }
+
         for eid in clicked_eids {
             if let Some(cb) = self.em.get_pclickable_non_mut(eid) {
+                if defeated && !matches!(cb.action, ClickAction::Respawn | ClickAction::Retry) {
+                    continue;
+                }
                 self.branch_from_click(cb.action.clone());
             }
         }
     }
 
     fn lmb_released(&mut self) {
+        self.editor_mouse_released();
         let button_ids = self.em.get_all_buttons();
 
         for eid in button_ids {